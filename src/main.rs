@@ -5,6 +5,9 @@ use log4rs::append::console::ConsoleAppender;
 use log4rs::config::{Appender, Config, Root};
 use log4rs::encode::pattern::PatternEncoder;
 
+use submiter::codec::run_encode_command;
+use submiter::dead_letter::run_dlq_command;
+use submiter::replay::{replay, ReplayOptions};
 use submiter::submitter::monitor_and_submit;
 mod submiter;
 
@@ -48,5 +51,15 @@ async fn main() -> Result<()> {
     dotenv::dotenv().ok();
     init_logging();
     info!("Starting the submiter application...");
-    monitor_and_submit().await
+
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|arg| arg.as_str()) {
+        // 离线回放: arbi_submiter replay <file> [--mode ..] [--speed ..] [--stream-ts ..]
+        Some("replay") => replay(ReplayOptions::from_args(&args[2..])?).await,
+        // 死信队列: arbi_submiter dlq list [count] | dlq requeue [count] [target_queue] [--refresh-ts]
+        Some("dlq") => run_dlq_command(&args[2..]).await,
+        // 生产端编码: arbi_submiter encode <raw|lz4|zlib|zstd> [--binary] [file]
+        Some("encode") => run_encode_command(&args[2..]),
+        _ => monitor_and_submit().await,
+    }
 }
@@ -0,0 +1,287 @@
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+/// 十进制小数位数上限，保证 10^scale 不溢出 u128
+const MAX_SCALE: u8 = 30;
+
+/// 换算为最小单位时的取整规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// 向下取整，用于输入数量和 jito 小费，不会超过事件给出的值
+    Down,
+    /// 向上取整，用于最小利润和优先费，不会低于事件给出的值
+    Up,
+}
+
+/// 事件中的数量。JSON 中可以是:
+/// - 数字: 旧格式，按其最短十进制表示 (与 f64 的 Display 一致) 解析，不经过浮点乘法
+/// - 十进制字符串: "1.000000001"
+/// - 最小单位整数: {"units": 1000000001}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Amount {
+    /// digits / 10^scale
+    Decimal { digits: u128, scale: u8 },
+    /// 已按 token 精度换算好的最小单位
+    Units(u64),
+}
+
+impl Amount {
+    /// 近似值，只用于排序、日志等不参与交易的场景
+    pub fn to_f64(self, decimals: u8) -> f64 {
+        match self {
+            Amount::Decimal { digits, scale } => digits as f64 / 10f64.powi(scale as i32),
+            Amount::Units(units) => units as f64 / 10f64.powi(decimals as i32),
+        }
+    }
+
+    /// 按 decimals 精度换算为最小单位
+    pub fn to_units(self, decimals: u8, rounding: Rounding) -> Result<u64> {
+        self.to_scaled_units(decimals, 1, 1, rounding)
+    }
+
+    /// 换算为最小单位后再乘以 numerator / denominator，只在最后取整一次
+    pub fn to_scaled_units(
+        self,
+        decimals: u8,
+        numerator: u128,
+        denominator: u128,
+        rounding: Rounding,
+    ) -> Result<u64> {
+        let overflow = || anyhow::anyhow!("amount {} overflows at {} decimals", self, decimals);
+        let (value, divisor) = match self {
+            Amount::Units(units) => (units as u128, 1),
+            Amount::Decimal { digits, scale } if scale <= decimals => (
+                digits
+                    .checked_mul(pow10(decimals - scale).ok_or_else(overflow)?)
+                    .ok_or_else(overflow)?,
+                1,
+            ),
+            Amount::Decimal { digits, scale } => {
+                (digits, pow10(scale - decimals).ok_or_else(overflow)?)
+            }
+        };
+        let value = value.checked_mul(numerator).ok_or_else(overflow)?;
+        let divisor = divisor.checked_mul(denominator).ok_or_else(overflow)?;
+        let units = match rounding {
+            Rounding::Down => value / divisor,
+            Rounding::Up => value.div_ceil(divisor),
+        };
+        u64::try_from(units).map_err(|_| overflow())
+    }
+}
+
+fn pow10(exp: u8) -> Option<u128> {
+    10u128.checked_pow(exp as u32)
+}
+
+impl FromStr for Amount {
+    type Err = anyhow::Error;
+
+    /// 非负十进制数，如 "12"、"0.5"、"1.000000001"
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("invalid amount: {:?}", value);
+        let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+        if (integer.is_empty() && fraction.is_empty())
+            || !integer.bytes().all(|b| b.is_ascii_digit())
+            || !fraction.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        // 末尾的 0 不影响数值
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > MAX_SCALE as usize {
+            return Err(invalid());
+        }
+        let digits = format!("{}{}", integer, fraction);
+        let digits = if digits.is_empty() {
+            0
+        } else {
+            digits.parse::<u128>().map_err(|_| invalid())?
+        };
+        Ok(Amount::Decimal {
+            digits,
+            scale: fraction.len() as u8,
+        })
+    }
+}
+
+impl TryFrom<f64> for Amount {
+    type Error = anyhow::Error;
+
+    /// 按最短十进制表示转换，0.29 得到 29 / 10^2 而不是 0.28999999999999998
+    fn try_from(value: f64) -> Result<Self> {
+        if value == 0.0 {
+            return Ok(Amount::Decimal {
+                digits: 0,
+                scale: 0,
+            });
+        }
+        value.to_string().parse()
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Amount::Decimal { digits, scale: 0 } => write!(f, "{}", digits),
+            Amount::Decimal { digits, scale } => {
+                let digits = format!("{:0>width$}", digits, width = *scale as usize + 1);
+                let (integer, fraction) = digits.split_at(digits.len() - *scale as usize);
+                write!(f, "{}.{}", integer, fraction)
+            }
+            Amount::Units(units) => write!(f, "{} units", units),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonAmount {
+    Number(f64),
+    Decimal(String),
+    Units { units: u64 },
+}
+
+/// 二进制格式中 Amount 以 variant 下标开头
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Amount")]
+enum BinaryAmount {
+    Decimal { digits: u128, scale: u8 },
+    Units(u64),
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return BinaryAmount::serialize(self, serializer);
+        }
+        match self {
+            Amount::Decimal { .. } => JsonAmount::Decimal(self.to_string()),
+            Amount::Units(units) => JsonAmount::Units { units: *units },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return BinaryAmount::deserialize(deserializer);
+        }
+        match JsonAmount::deserialize(deserializer)? {
+            JsonAmount::Number(value) => Amount::try_from(value),
+            JsonAmount::Decimal(value) => value.parse(),
+            JsonAmount::Units { units } => Ok(Amount::Units(units)),
+        }
+        .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_decimal_strings() {
+        assert_eq!(
+            decimal("1.000000001"),
+            Amount::Decimal {
+                digits: 1_000_000_001,
+                scale: 9
+            }
+        );
+        assert_eq!(
+            decimal("12.500"),
+            Amount::Decimal {
+                digits: 125,
+                scale: 1
+            }
+        );
+        assert_eq!(
+            decimal(".5"),
+            Amount::Decimal {
+                digits: 5,
+                scale: 1
+            }
+        );
+        for invalid in ["", ".", "-1", "1e9", "1.2.3", " 1"] {
+            assert!(invalid.parse::<Amount>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn f64_uses_shortest_representation() {
+        assert_eq!(
+            Amount::try_from(0.29).unwrap(),
+            Amount::Decimal {
+                digits: 29,
+                scale: 2
+            }
+        );
+        assert_eq!(
+            Amount::try_from(0.29)
+                .unwrap()
+                .to_units(6, Rounding::Down)
+                .unwrap(),
+            290_000
+        );
+    }
+
+    #[test]
+    fn to_units_rounding() {
+        let amount = decimal("1.0000000015");
+        assert_eq!(amount.to_units(9, Rounding::Down).unwrap(), 1_000_000_001);
+        assert_eq!(amount.to_units(9, Rounding::Up).unwrap(), 1_000_000_002);
+        // 精度足够时两种取整结果相同
+        assert_eq!(amount.to_units(10, Rounding::Down).unwrap(), 10_000_000_015);
+        assert_eq!(amount.to_units(10, Rounding::Up).unwrap(), 10_000_000_015);
+        assert_eq!(decimal("0.0000001").to_units(6, Rounding::Down).unwrap(), 0);
+        assert_eq!(decimal("0.0000001").to_units(6, Rounding::Up).unwrap(), 1);
+        assert_eq!(Amount::Units(42).to_units(0, Rounding::Up).unwrap(), 42);
+    }
+
+    #[test]
+    fn to_scaled_units_rounds_once() {
+        // 1.5 * 2 / 3 = 1，先取整再缩放会得到 0 或 2
+        let amount = decimal("0.0000015");
+        assert_eq!(amount.to_scaled_units(6, 2, 3, Rounding::Down).unwrap(), 1);
+        assert_eq!(amount.to_scaled_units(6, 2, 3, Rounding::Up).unwrap(), 1);
+        assert_eq!(
+            Amount::Units(10)
+                .to_scaled_units(0, 1, 3, Rounding::Up)
+                .unwrap(),
+            4
+        );
+    }
+
+    #[test]
+    fn to_units_overflow() {
+        assert!(Amount::Units(u64::MAX).to_units(0, Rounding::Down).is_ok());
+        assert!(decimal("18446744073709551616")
+            .to_units(0, Rounding::Down)
+            .is_err());
+        assert!(decimal("1").to_units(40, Rounding::Down).is_err());
+    }
+
+    #[test]
+    fn json_forms() {
+        let parse = |json: &str| serde_json::from_str::<Amount>(json).unwrap();
+        assert_eq!(parse("0.29"), decimal("0.29"));
+        assert_eq!(parse("\"0.29\""), decimal("0.29"));
+        assert_eq!(parse("{\"units\": 7}"), Amount::Units(7));
+        assert_eq!(
+            serde_json::to_string(&decimal("0.050")).unwrap(),
+            "\"0.05\""
+        );
+        assert_eq!(
+            serde_json::to_string(&Amount::Units(7)).unwrap(),
+            "{\"units\":7}"
+        );
+    }
+}
@@ -1,24 +1,27 @@
 use anchor_client::{
-    solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig},
+    solana_client::{
+        nonblocking::rpc_client::RpcClient,
+        rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig},
+    },
     solana_sdk::{
         address_lookup_table::AddressLookupTableAccount,
         compute_budget::ComputeBudgetInstruction,
-        hash::Hash,
-        instruction::AccountMeta,
+        hash::{hashv, Hash},
+        instruction::{AccountMeta, InstructionError},
         message::{v0::Message, VersionedMessage},
-        native_token::LAMPORTS_PER_SOL,
         pubkey::Pubkey,
         signature::Keypair,
         signer::Signer as _,
         system_instruction::transfer,
-        transaction::VersionedTransaction,
+        transaction::{TransactionError, VersionedTransaction},
     },
     Program,
 };
 use anchor_lang::prelude::*;
 use anyhow::Result;
+use base64::Engine;
 use futures::future::join_all;
-use log::{debug, error, trace};
+use log::{debug, error, info, trace, warn};
 use rand::{rngs::OsRng, seq::SliceRandom};
 use reqwest::Client as ReqwestClient;
 use serde::{Deserialize, Serialize};
@@ -29,12 +32,27 @@ use std::{
     sync::Arc,
     time::{Duration, SystemTime},
 };
-declare_program!(sol_arbitrage);
-use sol_arbitrage::{client::accounts::Arbi, client::args::Arbi as ArbiArgs};
-
-use crate::submiter::kamino::{
-    get_kamino_flashloan_borrow_ix, get_kamino_flashloan_repay_ix, KAMINO_ADDITIONAL_COMPUTE_UNITS,
+// arbi 指令的参数个数由 IDL 决定
+#[allow(clippy::too_many_arguments)]
+mod program {
+    use anchor_lang::declare_program;
+    declare_program!(sol_arbitrage);
+}
+use program::sol_arbitrage::{client::accounts::Arbi, client::args::Arbi as ArbiArgs};
+
+use crate::submiter::amount::{Amount, Rounding};
+use crate::submiter::compute::ComputeUnitModel;
+use crate::submiter::dead_letter::{FailureStage, StageContext};
+use crate::submiter::dex::{
+    parse_pubkey, tick_arrays, AccountMetaError, DexAccount, DexAdapter, DexType, LegAccountError,
+    OptionalAccount, PROGRAM_SLOTS,
 };
+use crate::submiter::kamino::{get_kamino_flashloan_borrow_ix, get_kamino_flashloan_repay_ix};
+use crate::submiter::mints::{MintInfo, MintRegistry};
+use crate::submiter::pool_cache::PoolCache;
+use crate::submiter::priority_fee::PriorityFees;
+use crate::submiter::route::{Leg, Route};
+use crate::submiter::wire::{option_pubkey_serde, pubkey_serde, pubkey_vec_serde};
 
 const PROGRAM_PUBKEY_STR: &str = "";
 const BASE_GAS: u64 = 5_000;
@@ -59,47 +77,21 @@ const JITO_ENDPOINTS: [&str; 6] = [
     "https://tokyo.mainnet.block-engine.jito.wtf",
     "https://london.mainnet.block-engine.jito.wtf",
 ];
-const UNIT_LIMIT: u32 = 300_000;
+const SOL_DECIMALS: u8 = 9;
 const JITO_TIMEOUT: u64 = 3;
 
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub enum DexType {
-    RaydiumAmm = 1,
-    RaydiumCpmm = 2,
-    RaydiumClmm = 3,
-    Orca = 4,
-    MeteoraDlmm = 5,
-    MeteoraAmm = 6,
-    Solfi = 7,
-    Lifinity = 8,
-    Phoenix = 9,
-    Pump = 10,
-    Obric = 11,
-    Openbook = 12,
-    JupPerp = 13,
-    MeteoraCpam = 14,
-}
-
-impl DexType {
-    fn to_u8(&self) -> u8 {
-        match self {
-            DexType::RaydiumAmm => 1,
-            DexType::RaydiumCpmm => 2,
-            DexType::RaydiumClmm => 3,
-            DexType::Orca => 4,
-            DexType::MeteoraDlmm => 5,
-            DexType::MeteoraAmm => 6,
-            DexType::Solfi => 7,
-            DexType::Lifinity => 8,
-            DexType::Phoenix => 9,
-            DexType::Pump => 10,
-            DexType::Obric => 11,
-            DexType::Openbook => 12,
-            DexType::JupPerp => 13,
-            DexType::MeteoraCpam => 14,
-        }
-    }
+/// 提交方式，Auto 时由事件自身的 simulate / jito 参数决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SendPath {
+    Auto,
+    /// 始终以 bundle 发送到 jito
+    Jito,
+    /// 始终通过 RPC 发送，不附带 jito tip
+    Rpc,
+    Simulate,
+    /// 只组装签名，不发送
+    DryRun,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -107,6 +99,14 @@ struct JitoError {
     message: String,
 }
 
+impl std::fmt::Display for JitoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "jito error: {}", self.message)
+    }
+}
+
+impl std::error::Error for JitoError {}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct BundleResponse {
     id: String,
@@ -114,658 +114,815 @@ struct BundleResponse {
     error: Option<JitoError>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TransactionDetail {
     #[serde(default)]
     simulate: bool,
-    min_profit: f64,
+    /// 以 token A 计价
+    pub min_profit: Amount,
     jito_tip_ratio: u8,
-    jito_tip: f64,
-    priority_fee: f64,
+    /// SOL
+    jito_tip: Amount,
+    /// SOL，换算为 lamports 后乘以 3.3 作为每 CU 的 micro-lamports，
+    /// 路由写账户没有近期优先费数据时使用，见 PriorityFees::unit_price
+    priority_fee: Amount,
     #[serde(default)]
     pub use_proxy_account: bool,
     #[serde(default)]
     pub use_kamino: bool,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CommonAccounts {
-    token_vault_a_mint: String,
-    token_vault_b_mint: String,
-    vault: String,
-    user_token_account_a: String,
-    user_token_account_b: String,
+    #[serde(with = "pubkey_serde")]
+    pub token_vault_a_mint: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    token_vault_b_mint: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    user_token_account_a: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    user_token_account_b: Pubkey,
 }
 
-trait AccountMetaFormattable {
-    fn to_account_metas(&self, direction: bool) -> Vec<AccountMeta>;
-}
-
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RaydiumAmmAccounts {
-    raydium_amm: String,
-    raydium_amm_open_orders: String,
-    raydium_amm_authority: String,
-    raydium_amm_coin_vault: String,
-    raydium_amm_pc_vault: String,
-    raydium_amm_market: String,
-    raydium_amm_market_bids: String,
-    raydium_amm_market_asks: String,
-    raydium_amm_market_event_queue: String,
-    raydium_amm_market_coin_vault: String,
-    raydium_amm_market_pc_vault: String,
-    raydium_amm_market_vault_signer: String,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_open_orders: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_authority: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_coin_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_pc_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_market: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_market_bids: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_market_asks: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_market_event_queue: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_market_coin_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_market_pc_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_market_vault_signer: Pubkey,
 }
 
-impl AccountMetaFormattable for RaydiumAmmAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
-        vec![
-            AccountMeta::new(Pubkey::from_str(&self.raydium_amm).unwrap(), false),
-            AccountMeta::new(
-                Pubkey::from_str(&self.raydium_amm_open_orders).unwrap(),
-                false,
-            ),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.raydium_amm_authority).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.raydium_amm_coin_vault).unwrap(),
-                false,
-            ),
-            AccountMeta::new(Pubkey::from_str(&self.raydium_amm_pc_vault).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.raydium_amm_market).unwrap(), false),
-            AccountMeta::new(
-                Pubkey::from_str(&self.raydium_amm_market_bids).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.raydium_amm_market_asks).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.raydium_amm_market_event_queue).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.raydium_amm_market_coin_vault).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.raydium_amm_market_pc_vault).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.raydium_amm_market_vault_signer).unwrap(),
-                false,
-            ),
-        ]
+/// 可选账户缺省时使用的占位账户
+fn program_pubkey() -> Result<Pubkey, AccountMetaError> {
+    parse_pubkey(PROGRAM_PUBKEY_STR, "PROGRAM_PUBKEY_STR")
+}
+
+impl DexAdapter for RaydiumAmmAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.raydium_amm]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
+        vec![self.raydium_amm_coin_vault, self.raydium_amm_pc_vault]
+    }
+
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new(self.raydium_amm, false),
+            AccountMeta::new(self.raydium_amm_open_orders, false),
+            AccountMeta::new_readonly(self.raydium_amm_authority, false),
+            AccountMeta::new(self.raydium_amm_coin_vault, false),
+            AccountMeta::new(self.raydium_amm_pc_vault, false),
+            AccountMeta::new(self.raydium_amm_market, false),
+            AccountMeta::new(self.raydium_amm_market_bids, false),
+            AccountMeta::new(self.raydium_amm_market_asks, false),
+            AccountMeta::new(self.raydium_amm_market_event_queue, false),
+            AccountMeta::new(self.raydium_amm_market_coin_vault, false),
+            AccountMeta::new(self.raydium_amm_market_pc_vault, false),
+            AccountMeta::new(self.raydium_amm_market_vault_signer, false),
+        ])
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RaydiumClmmAccounts {
-    pub raydium_amm_config: String,
-    pub raydium_pool_state: String,
-    pub raydium_input_vault: String,
-    pub raydium_output_vault: String,
-    pub raydium_observation_state: String,
-    pub raydium_a_to_b_tick_array_0: Option<String>,
-    pub raydium_a_to_b_tick_array_1: Option<String>,
-    pub raydium_a_to_b_tick_array_2: Option<String>,
-    pub raydium_b_to_a_tick_array_0: Option<String>,
-    pub raydium_b_to_a_tick_array_1: Option<String>,
-    pub raydium_b_to_a_tick_array_2: Option<String>,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_amm_config: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_pool_state: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_input_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_output_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub raydium_observation_state: Pubkey,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub raydium_a_to_b_tick_array_0: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub raydium_a_to_b_tick_array_1: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub raydium_a_to_b_tick_array_2: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub raydium_b_to_a_tick_array_0: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub raydium_b_to_a_tick_array_1: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub raydium_b_to_a_tick_array_2: Option<Pubkey>,
+    /// 按 swap 顺序排列的 tick array，不为空时替代上面的三个字段
+    #[serde(default, with = "pubkey_vec_serde")]
+    pub raydium_a_to_b_tick_arrays: Vec<Pubkey>,
+    #[serde(default, with = "pubkey_vec_serde")]
+    pub raydium_b_to_a_tick_arrays: Vec<Pubkey>,
+    /// 跨越 tick array bitmap 范围时需要。链上程序每个 leg 固定 8 个账户，
+    /// 暂时无法传入，给出时报错
+    #[serde(default, with = "option_pubkey_serde")]
+    pub raydium_tick_array_bitmap_extension: Option<Pubkey>,
 }
 
-impl AccountMetaFormattable for RaydiumClmmAccounts {
-    fn to_account_metas(&self, direction: bool) -> Vec<AccountMeta> {
-        let (tick_array_0, tick_array_1, tick_array_2) = if direction {
-            (
-                self.raydium_a_to_b_tick_array_0.as_ref().unwrap(),
-                self.raydium_a_to_b_tick_array_1.as_ref().unwrap(),
-                self.raydium_a_to_b_tick_array_2.as_ref().unwrap(),
-            )
+impl DexAdapter for RaydiumClmmAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.raydium_pool_state]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
+        vec![self.raydium_input_vault, self.raydium_output_vault]
+    }
+
+    fn to_account_metas(&self, direction: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        if self.raydium_tick_array_bitmap_extension.is_some() {
+            return Err(AccountMetaError::UnsupportedAccount {
+                field: "raydiumTickArrayBitmapExtension",
+            });
+        }
+        let tick_arrays = if direction {
+            tick_arrays(
+                &self.raydium_a_to_b_tick_arrays,
+                [
+                    self.raydium_a_to_b_tick_array_0,
+                    self.raydium_a_to_b_tick_array_1,
+                    self.raydium_a_to_b_tick_array_2,
+                ],
+                "raydiumAToBTickArrays",
+            )?
         } else {
-            (
-                self.raydium_b_to_a_tick_array_0.as_ref().unwrap(),
-                self.raydium_b_to_a_tick_array_1.as_ref().unwrap(),
-                self.raydium_b_to_a_tick_array_2.as_ref().unwrap(),
-            )
+            tick_arrays(
+                &self.raydium_b_to_a_tick_arrays,
+                [
+                    self.raydium_b_to_a_tick_array_0,
+                    self.raydium_b_to_a_tick_array_1,
+                    self.raydium_b_to_a_tick_array_2,
+                ],
+                "raydiumBToATickArrays",
+            )?
         };
-        vec![
-            AccountMeta::new_readonly(Pubkey::from_str(&self.raydium_amm_config).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.raydium_pool_state).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.raydium_input_vault).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.raydium_output_vault).unwrap(), false),
-            AccountMeta::new(
-                Pubkey::from_str(&self.raydium_observation_state).unwrap(),
-                false,
-            ),
-            AccountMeta::new(Pubkey::from_str(&tick_array_0).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&tick_array_1).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&tick_array_2).unwrap(), false),
-        ]
+        let mut metas = vec![
+            AccountMeta::new_readonly(self.raydium_amm_config, false),
+            AccountMeta::new(self.raydium_pool_state, false),
+            AccountMeta::new(self.raydium_input_vault, false),
+            AccountMeta::new(self.raydium_output_vault, false),
+            AccountMeta::new(self.raydium_observation_state, false),
+        ];
+        metas.extend(
+            tick_arrays
+                .iter()
+                .map(|tick_array| AccountMeta::new(*tick_array, false)),
+        );
+        Ok(metas)
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RaydiumCpmmAccounts {
-    authority: String,
-    amm_config: String,
-    pool_state: String,
-    input_vault: String,
-    output_vault: String,
-    observation_state: String,
+    #[serde(with = "pubkey_serde")]
+    pub authority: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub amm_config: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub pool_state: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub input_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub output_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub observation_state: Pubkey,
 }
 
-impl AccountMetaFormattable for RaydiumCpmmAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
-        vec![
-            AccountMeta::new_readonly(Pubkey::from_str(&self.authority).unwrap(), false),
-            AccountMeta::new_readonly(Pubkey::from_str(&self.amm_config).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.pool_state).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.input_vault).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.output_vault).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.observation_state).unwrap(), false),
-        ]
+impl DexAdapter for RaydiumCpmmAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.pool_state]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
+        vec![self.input_vault, self.output_vault]
+    }
+
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(self.amm_config, false),
+            AccountMeta::new(self.pool_state, false),
+            AccountMeta::new(self.input_vault, false),
+            AccountMeta::new(self.output_vault, false),
+            AccountMeta::new(self.observation_state, false),
+        ])
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OrcaAccounts {
-    pub whirlpool: String,
-    pub whirlpool_token_vault_a: String,
-    pub whirlpool_token_vault_b: String,
-    pub whirlpool_a_to_b_tick_array_0: Option<String>,
-    pub whirlpool_a_to_b_tick_array_1: Option<String>,
-    pub whirlpool_a_to_b_tick_array_2: Option<String>,
-    pub whirlpool_b_to_a_tick_array_0: Option<String>,
-    pub whirlpool_b_to_a_tick_array_1: Option<String>,
-    pub whirlpool_b_to_a_tick_array_2: Option<String>,
-    pub whirlpool_oracle: String,
+    #[serde(with = "pubkey_serde")]
+    pub whirlpool: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub whirlpool_token_vault_a: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub whirlpool_token_vault_b: Pubkey,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub whirlpool_a_to_b_tick_array_0: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub whirlpool_a_to_b_tick_array_1: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub whirlpool_a_to_b_tick_array_2: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub whirlpool_b_to_a_tick_array_0: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub whirlpool_b_to_a_tick_array_1: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub whirlpool_b_to_a_tick_array_2: Option<Pubkey>,
+    /// 按 swap 顺序排列的 tick array，不为空时替代上面的三个字段，最多三个 (swap 指令不支持 supplemental tick array)
+    #[serde(default, with = "pubkey_vec_serde")]
+    pub whirlpool_a_to_b_tick_arrays: Vec<Pubkey>,
+    #[serde(default, with = "pubkey_vec_serde")]
+    pub whirlpool_b_to_a_tick_arrays: Vec<Pubkey>,
+    #[serde(with = "pubkey_serde")]
+    pub whirlpool_oracle: Pubkey,
 }
 
-impl AccountMetaFormattable for OrcaAccounts {
-    fn to_account_metas(&self, direction: bool) -> Vec<AccountMeta> {
-        let (tick_array_0, tick_array_1, tick_array_2) = if direction {
-            (
-                self.whirlpool_a_to_b_tick_array_0.as_ref().unwrap(),
-                self.whirlpool_a_to_b_tick_array_1.as_ref().unwrap(),
-                self.whirlpool_a_to_b_tick_array_2.as_ref().unwrap(),
-            )
+impl DexAdapter for OrcaAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.whirlpool]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
+        vec![self.whirlpool_token_vault_a, self.whirlpool_token_vault_b]
+    }
+
+    fn to_account_metas(&self, direction: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        let tick_arrays = if direction {
+            tick_arrays(
+                &self.whirlpool_a_to_b_tick_arrays,
+                [
+                    self.whirlpool_a_to_b_tick_array_0,
+                    self.whirlpool_a_to_b_tick_array_1,
+                    self.whirlpool_a_to_b_tick_array_2,
+                ],
+                "whirlpoolAToBTickArrays",
+            )?
         } else {
-            (
-                self.whirlpool_b_to_a_tick_array_0.as_ref().unwrap(),
-                self.whirlpool_b_to_a_tick_array_1.as_ref().unwrap(),
-                self.whirlpool_b_to_a_tick_array_2.as_ref().unwrap(),
-            )
+            tick_arrays(
+                &self.whirlpool_b_to_a_tick_arrays,
+                [
+                    self.whirlpool_b_to_a_tick_array_0,
+                    self.whirlpool_b_to_a_tick_array_1,
+                    self.whirlpool_b_to_a_tick_array_2,
+                ],
+                "whirlpoolBToATickArrays",
+            )?
         };
-        vec![
-            AccountMeta::new(Pubkey::from_str(&self.whirlpool).unwrap(), false),
-            AccountMeta::new(
-                Pubkey::from_str(&self.whirlpool_token_vault_a).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.whirlpool_token_vault_b).unwrap(),
-                false,
-            ),
-            AccountMeta::new(Pubkey::from_str(&tick_array_0).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&tick_array_1).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&tick_array_2).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.whirlpool_oracle).unwrap(), false),
-        ]
+        let mut metas = vec![
+            AccountMeta::new(self.whirlpool, false),
+            AccountMeta::new(self.whirlpool_token_vault_a, false),
+            AccountMeta::new(self.whirlpool_token_vault_b, false),
+        ];
+        metas.extend(
+            tick_arrays
+                .iter()
+                .map(|tick_array| AccountMeta::new(*tick_array, false)),
+        );
+        metas.push(AccountMeta::new(self.whirlpool_oracle, false));
+        Ok(metas)
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MeteoraDlmmAccounts {
-    pub meteora_lb_pair: String,
-    pub meteora_reserve_x: String,
-    pub meteora_reserve_y: String,
-    pub meteora_oracle: String,
-    pub meteora_event_authority: String,
-    pub meteora_a_to_b_tick_array_0: Option<String>,
-    pub meteora_a_to_b_tick_array_1: Option<String>,
-    pub meteora_a_to_b_tick_array_2: Option<String>,
-    pub meteora_b_to_a_tick_array_0: Option<String>,
-    pub meteora_b_to_a_tick_array_1: Option<String>,
-    pub meteora_b_to_a_tick_array_2: Option<String>,
+    #[serde(with = "pubkey_serde")]
+    pub meteora_lb_pair: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub meteora_reserve_x: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub meteora_reserve_y: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub meteora_oracle: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub meteora_event_authority: Pubkey,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub meteora_a_to_b_tick_array_0: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub meteora_a_to_b_tick_array_1: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub meteora_a_to_b_tick_array_2: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub meteora_b_to_a_tick_array_0: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub meteora_b_to_a_tick_array_1: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub meteora_b_to_a_tick_array_2: Option<Pubkey>,
+    /// 按 swap 顺序排列的 bin array，不为空时替代上面的三个字段
+    #[serde(default, with = "pubkey_vec_serde")]
+    pub meteora_a_to_b_bin_arrays: Vec<Pubkey>,
+    #[serde(default, with = "pubkey_vec_serde")]
+    pub meteora_b_to_a_bin_arrays: Vec<Pubkey>,
+    /// active bin 超出 lb pair 内置 bitmap 范围时需要。链上程序每个 leg 固定 8 个账户，
+    /// 暂时无法传入，给出时报错
+    #[serde(default, with = "option_pubkey_serde")]
+    pub meteora_bin_array_bitmap_extension: Option<Pubkey>,
 }
 
-impl AccountMetaFormattable for MeteoraDlmmAccounts {
-    fn to_account_metas(&self, direction: bool) -> Vec<AccountMeta> {
-        let (tick_array_0, tick_array_1, tick_array_2) = if direction {
-            (
-                self.meteora_a_to_b_tick_array_0.as_ref().unwrap(),
-                self.meteora_a_to_b_tick_array_1.as_ref().unwrap(),
-                self.meteora_a_to_b_tick_array_2.as_ref().unwrap(),
-            )
+impl DexAdapter for MeteoraDlmmAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.meteora_lb_pair]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
+        vec![self.meteora_reserve_x, self.meteora_reserve_y]
+    }
+
+    fn to_account_metas(&self, direction: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        if self.meteora_bin_array_bitmap_extension.is_some() {
+            return Err(AccountMetaError::UnsupportedAccount {
+                field: "meteoraBinArrayBitmapExtension",
+            });
+        }
+        let bin_arrays = if direction {
+            tick_arrays(
+                &self.meteora_a_to_b_bin_arrays,
+                [
+                    self.meteora_a_to_b_tick_array_0,
+                    self.meteora_a_to_b_tick_array_1,
+                    self.meteora_a_to_b_tick_array_2,
+                ],
+                "meteoraAToBBinArrays",
+            )?
         } else {
-            (
-                self.meteora_b_to_a_tick_array_0.as_ref().unwrap(),
-                self.meteora_b_to_a_tick_array_1.as_ref().unwrap(),
-                self.meteora_b_to_a_tick_array_2.as_ref().unwrap(),
-            )
+            tick_arrays(
+                &self.meteora_b_to_a_bin_arrays,
+                [
+                    self.meteora_b_to_a_tick_array_0,
+                    self.meteora_b_to_a_tick_array_1,
+                    self.meteora_b_to_a_tick_array_2,
+                ],
+                "meteoraBToABinArrays",
+            )?
         };
-        vec![
-            AccountMeta::new(Pubkey::from_str(&self.meteora_lb_pair).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.meteora_reserve_x).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.meteora_reserve_y).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.meteora_oracle).unwrap(), false),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.meteora_event_authority).unwrap(),
-                false,
-            ),
-            AccountMeta::new(Pubkey::from_str(&tick_array_0).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&tick_array_1).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&tick_array_2).unwrap(), false),
-        ]
+        let mut metas = vec![
+            AccountMeta::new(self.meteora_lb_pair, false),
+            AccountMeta::new(self.meteora_reserve_x, false),
+            AccountMeta::new(self.meteora_reserve_y, false),
+            AccountMeta::new(self.meteora_oracle, false),
+            AccountMeta::new_readonly(self.meteora_event_authority, false),
+        ];
+        metas.extend(
+            bin_arrays
+                .iter()
+                .map(|bin_array| AccountMeta::new(*bin_array, false)),
+        );
+        Ok(metas)
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MeteoraAmmAccounts {
-    pub pool: String,
-    pub a_vault: String,
-    pub b_vault: String,
-    pub a_token_vault: String,
-    pub b_token_vault: String,
-    pub a_vault_lp: String,
-    pub b_vault_lp: String,
-    pub a_vault_lp_mint: String,
-    pub b_vault_lp_mint: String,
-    pub protocol_token_a_fee: String,
-    pub protocol_token_b_fee: String,
+    #[serde(with = "pubkey_serde")]
+    pub pool: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub a_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub b_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub a_token_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub b_token_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub a_vault_lp: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub b_vault_lp: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub a_vault_lp_mint: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub b_vault_lp_mint: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub protocol_token_a_fee: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub protocol_token_b_fee: Pubkey,
 }
-impl AccountMetaFormattable for MeteoraAmmAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
-        vec![
-            AccountMeta::new(Pubkey::from_str(&self.pool).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.a_vault).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.b_vault).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.a_token_vault).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.b_token_vault).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.a_vault_lp_mint).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.b_vault_lp_mint).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.a_vault_lp).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.b_vault_lp).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.protocol_token_a_fee).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.protocol_token_b_fee).unwrap(), false),
-        ]
+impl DexAdapter for MeteoraAmmAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.pool]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
+        vec![self.a_token_vault, self.b_token_vault]
+    }
+
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new(self.pool, false),
+            AccountMeta::new(self.a_vault, false),
+            AccountMeta::new(self.b_vault, false),
+            AccountMeta::new(self.a_token_vault, false),
+            AccountMeta::new(self.b_token_vault, false),
+            AccountMeta::new(self.a_vault_lp_mint, false),
+            AccountMeta::new(self.b_vault_lp_mint, false),
+            AccountMeta::new(self.a_vault_lp, false),
+            AccountMeta::new(self.b_vault_lp, false),
+            AccountMeta::new(self.protocol_token_a_fee, false),
+            AccountMeta::new(self.protocol_token_b_fee, false),
+        ])
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SolfiAccounts {
-    pub solfi_pair: String,
-    pub solfi_pool_token_a: String,
-    pub solfi_pool_token_b: String,
+    #[serde(with = "pubkey_serde")]
+    pub solfi_pair: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub solfi_pool_token_a: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub solfi_pool_token_b: Pubkey,
 }
-impl AccountMetaFormattable for SolfiAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
-        vec![
-            AccountMeta::new(Pubkey::from_str(&self.solfi_pair).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.solfi_pool_token_a).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.solfi_pool_token_b).unwrap(), false),
-        ]
+impl DexAdapter for SolfiAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.solfi_pair]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
+        vec![self.solfi_pool_token_a, self.solfi_pool_token_b]
+    }
+
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new(self.solfi_pair, false),
+            AccountMeta::new(self.solfi_pool_token_a, false),
+            AccountMeta::new(self.solfi_pool_token_b, false),
+        ])
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LifinityAccounts {
-    pub lifinity_authority: String,
-    pub lifinity_amm: String,
-    pub lifinity_swap_source: String,
-    pub lifinity_swap_destination: String,
-    pub lifinity_pool_mint: String,
-    pub lifinity_fee_account: String,
-    pub lifinity_oracle_main_account: String,
-    pub lifinity_oracle_sub_account: String,
-    pub lifinity_oracle_pc_account: String,
+    #[serde(with = "pubkey_serde")]
+    pub lifinity_authority: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub lifinity_amm: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub lifinity_swap_source: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub lifinity_swap_destination: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub lifinity_pool_mint: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub lifinity_fee_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub lifinity_oracle_main_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub lifinity_oracle_sub_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub lifinity_oracle_pc_account: Pubkey,
 }
-impl AccountMetaFormattable for LifinityAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
-        vec![
-            AccountMeta::new_readonly(Pubkey::from_str(&self.lifinity_authority).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.lifinity_amm).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.lifinity_swap_source).unwrap(), false),
-            AccountMeta::new(
-                Pubkey::from_str(&self.lifinity_swap_destination).unwrap(),
-                false,
-            ),
-            AccountMeta::new(Pubkey::from_str(&self.lifinity_pool_mint).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.lifinity_fee_account).unwrap(), false),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.lifinity_oracle_main_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.lifinity_oracle_sub_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.lifinity_oracle_pc_account).unwrap(),
-                false,
-            ),
-        ]
+impl DexAdapter for LifinityAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.lifinity_amm]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
+        vec![self.lifinity_swap_source, self.lifinity_swap_destination]
+    }
+
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new_readonly(self.lifinity_authority, false),
+            AccountMeta::new(self.lifinity_amm, false),
+            AccountMeta::new(self.lifinity_swap_source, false),
+            AccountMeta::new(self.lifinity_swap_destination, false),
+            AccountMeta::new(self.lifinity_pool_mint, false),
+            AccountMeta::new(self.lifinity_fee_account, false),
+            AccountMeta::new_readonly(self.lifinity_oracle_main_account, false),
+            AccountMeta::new_readonly(self.lifinity_oracle_sub_account, false),
+            AccountMeta::new_readonly(self.lifinity_oracle_pc_account, false),
+        ])
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PhoenixAccounts {
-    pub phoenix_log_authority: String,
-    pub phoenix_market: String,
-    pub phoenix_base_vault: String,
-    pub phoenix_quote_vault: String,
+    #[serde(with = "pubkey_serde")]
+    pub phoenix_log_authority: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub phoenix_market: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub phoenix_base_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub phoenix_quote_vault: Pubkey,
 }
-impl AccountMetaFormattable for PhoenixAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
-        vec![
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.phoenix_log_authority).unwrap(),
-                false,
-            ),
-            AccountMeta::new(Pubkey::from_str(&self.phoenix_market).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.phoenix_base_vault).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.phoenix_quote_vault).unwrap(), false),
-        ]
+impl DexAdapter for PhoenixAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.phoenix_market]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
+        vec![self.phoenix_base_vault, self.phoenix_quote_vault]
+    }
+
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new_readonly(self.phoenix_log_authority, false),
+            AccountMeta::new(self.phoenix_market, false),
+            AccountMeta::new(self.phoenix_base_vault, false),
+            AccountMeta::new(self.phoenix_quote_vault, false),
+        ])
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PumpAccounts {
-    pub pump_pool: String,
-    pub pump_global_config: String,
-    pub pump_pool_base_token_account: String,
-    pub pump_pool_quote_token_account: String,
-    pub pump_protocol_fee_recipient: String,
-    pub pump_protocol_fee_recipient_token_account: String,
-    pub pump_event_authority: String,
-    pub pump_coin_creator_vault_ata: String,
-    pub pump_coin_creator_vault_authority: String,
+    #[serde(with = "pubkey_serde")]
+    pub pump_pool: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub pump_global_config: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub pump_pool_base_token_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub pump_pool_quote_token_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub pump_protocol_fee_recipient: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub pump_protocol_fee_recipient_token_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub pump_event_authority: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub pump_coin_creator_vault_ata: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub pump_coin_creator_vault_authority: Pubkey,
 }
-impl AccountMetaFormattable for PumpAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
+impl DexAdapter for PumpAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.pump_pool]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
         vec![
-            AccountMeta::new_readonly(Pubkey::from_str(&self.pump_pool).unwrap(), false),
-            AccountMeta::new_readonly(Pubkey::from_str(&self.pump_global_config).unwrap(), false),
-            AccountMeta::new(
-                Pubkey::from_str(&self.pump_pool_base_token_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.pump_pool_quote_token_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.pump_protocol_fee_recipient).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.pump_protocol_fee_recipient_token_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new_readonly(Pubkey::from_str(&self.pump_event_authority).unwrap(), false),
-            AccountMeta::new(
-                Pubkey::from_str(&self.pump_coin_creator_vault_ata).unwrap(),
-                false,
-            ),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.pump_coin_creator_vault_authority).unwrap(),
-                false,
-            ),
+            self.pump_pool_base_token_account,
+            self.pump_pool_quote_token_account,
         ]
     }
+
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new_readonly(self.pump_pool, false),
+            AccountMeta::new_readonly(self.pump_global_config, false),
+            AccountMeta::new(self.pump_pool_base_token_account, false),
+            AccountMeta::new(self.pump_pool_quote_token_account, false),
+            AccountMeta::new_readonly(self.pump_protocol_fee_recipient, false),
+            AccountMeta::new(self.pump_protocol_fee_recipient_token_account, false),
+            AccountMeta::new_readonly(self.pump_event_authority, false),
+            AccountMeta::new(self.pump_coin_creator_vault_ata, false),
+            AccountMeta::new_readonly(self.pump_coin_creator_vault_authority, false),
+        ])
+    }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ObricAccounts {
-    pub obric_trading_pair: String,
-    pub obric_mint_x: String,
-    pub obric_mint_y: String,
-    pub obric_reserve_x: String,
-    pub obric_reserve_y: String,
-    pub obric_protocol_fee: String,
-    pub obric_x_price_feed: String,
-    pub obric_y_price_feed: String,
+    #[serde(with = "pubkey_serde")]
+    pub obric_trading_pair: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub obric_mint_x: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub obric_mint_y: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub obric_reserve_x: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub obric_reserve_y: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub obric_protocol_fee: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub obric_x_price_feed: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub obric_y_price_feed: Pubkey,
 }
-impl AccountMetaFormattable for ObricAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
-        vec![
-            AccountMeta::new(Pubkey::from_str(&self.obric_trading_pair).unwrap(), false),
-            AccountMeta::new_readonly(Pubkey::from_str(&self.obric_mint_x).unwrap(), false),
-            AccountMeta::new_readonly(Pubkey::from_str(&self.obric_mint_y).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.obric_reserve_x).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.obric_reserve_y).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.obric_protocol_fee).unwrap(), false),
-            AccountMeta::new_readonly(Pubkey::from_str(&self.obric_x_price_feed).unwrap(), false),
-            AccountMeta::new_readonly(Pubkey::from_str(&self.obric_y_price_feed).unwrap(), false),
-        ]
+impl DexAdapter for ObricAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.obric_trading_pair]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
+        vec![self.obric_reserve_x, self.obric_reserve_y]
+    }
+
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new(self.obric_trading_pair, false),
+            AccountMeta::new_readonly(self.obric_mint_x, false),
+            AccountMeta::new_readonly(self.obric_mint_y, false),
+            AccountMeta::new(self.obric_reserve_x, false),
+            AccountMeta::new(self.obric_reserve_y, false),
+            AccountMeta::new(self.obric_protocol_fee, false),
+            AccountMeta::new_readonly(self.obric_x_price_feed, false),
+            AccountMeta::new_readonly(self.obric_y_price_feed, false),
+        ])
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OpenbookAccounts {
-    pub openbook_market: String,
-    pub openbook_market_authority: String,
-    pub openbook_bids: String,
-    pub openbook_asks: String,
-    pub openbook_market_base_vault: String,
-    pub openbook_market_quote_vault: String,
-    pub openbook_event_heap: String,
-    pub openbook_oracle_a: String,
-    pub openbook_oracle_b: String,
-    pub openbook_open_orders_admin: String,
+    #[serde(with = "pubkey_serde")]
+    pub openbook_market: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub openbook_market_authority: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub openbook_bids: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub openbook_asks: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub openbook_market_base_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub openbook_market_quote_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub openbook_event_heap: Pubkey,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub openbook_oracle_a: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub openbook_oracle_b: Option<Pubkey>,
+    #[serde(default, with = "option_pubkey_serde")]
+    pub openbook_open_orders_admin: Option<Pubkey>,
 }
-impl AccountMetaFormattable for OpenbookAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
+impl DexAdapter for OpenbookAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.openbook_market]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
         vec![
-            AccountMeta::new(Pubkey::from_str(&self.openbook_market).unwrap(), false),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.openbook_market_authority).unwrap(),
-                false,
-            ),
-            AccountMeta::new(Pubkey::from_str(&self.openbook_bids).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.openbook_asks).unwrap(), false),
-            AccountMeta::new(
-                Pubkey::from_str(&self.openbook_market_base_vault).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.openbook_market_quote_vault).unwrap(),
-                false,
-            ),
-            AccountMeta::new(Pubkey::from_str(&self.openbook_event_heap).unwrap(), false),
+            self.openbook_market_base_vault,
+            self.openbook_market_quote_vault,
+        ]
+    }
+
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new(self.openbook_market, false),
+            AccountMeta::new_readonly(self.openbook_market_authority, false),
+            AccountMeta::new(self.openbook_bids, false),
+            AccountMeta::new(self.openbook_asks, false),
+            AccountMeta::new(self.openbook_market_base_vault, false),
+            AccountMeta::new(self.openbook_market_quote_vault, false),
+            AccountMeta::new(self.openbook_event_heap, false),
             AccountMeta::new_readonly(
-                Pubkey::from_str(if self.openbook_oracle_a.len() == 0 {
-                    PROGRAM_PUBKEY_STR
-                } else {
-                    &self.openbook_oracle_a
-                })
-                .unwrap(),
+                self.openbook_oracle_a.map_or_else(program_pubkey, Ok)?,
                 false,
             ),
             AccountMeta::new_readonly(
-                Pubkey::from_str(if self.openbook_oracle_b.len() == 0 {
-                    PROGRAM_PUBKEY_STR
-                } else {
-                    &self.openbook_oracle_b
-                })
-                .unwrap(),
+                self.openbook_oracle_b.map_or_else(program_pubkey, Ok)?,
                 false,
             ),
             AccountMeta::new_readonly(
-                Pubkey::from_str(if self.openbook_open_orders_admin.len() == 0 {
-                    PROGRAM_PUBKEY_STR
-                } else {
-                    &self.openbook_open_orders_admin
-                })
-                .unwrap(),
+                self.openbook_open_orders_admin
+                    .map_or_else(program_pubkey, Ok)?,
                 false,
             ),
-        ]
+        ])
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JupPerpAccounts {
-    pub transfer_authority: String,
-    pub perpetuals: String,
-    pub pool: String,
-    pub receiving_custody: String,
-    pub receiving_custody_doves_price_account: String,
-    pub receiving_custody_pythnet_price_account: String,
-    pub receiving_custody_token_account: String,
-    pub dispensing_custody: String,
-    pub dispensing_custody_doves_price_account: String,
-    pub dispensing_custody_pythnet_price_account: String,
-    pub dispensing_custody_token_account: String,
-    pub event_authority: String,
+    #[serde(with = "pubkey_serde")]
+    pub transfer_authority: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub perpetuals: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub pool: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub receiving_custody: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub receiving_custody_doves_price_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub receiving_custody_pythnet_price_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub receiving_custody_token_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub dispensing_custody: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub dispensing_custody_doves_price_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub dispensing_custody_pythnet_price_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub dispensing_custody_token_account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub event_authority: Pubkey,
 }
-impl AccountMetaFormattable for JupPerpAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
+impl DexAdapter for JupPerpAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        // 所有 JupPerp 共用一个 pool，用 custody 区分
+        vec![self.receiving_custody, self.dispensing_custody]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
         vec![
-            AccountMeta::new_readonly(Pubkey::from_str(&self.transfer_authority).unwrap(), false),
-            AccountMeta::new_readonly(Pubkey::from_str(&self.perpetuals).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.pool).unwrap(), false),
-            AccountMeta::new(Pubkey::from_str(&self.receiving_custody).unwrap(), false),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.receiving_custody_doves_price_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.receiving_custody_pythnet_price_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.receiving_custody_token_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new(Pubkey::from_str(&self.dispensing_custody).unwrap(), false),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.dispensing_custody_doves_price_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.dispensing_custody_pythnet_price_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.dispensing_custody_token_account).unwrap(),
-                false,
-            ),
-            AccountMeta::new_readonly(Pubkey::from_str(&self.event_authority).unwrap(), false),
+            self.receiving_custody_token_account,
+            self.dispensing_custody_token_account,
         ]
     }
+
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new_readonly(self.transfer_authority, false),
+            AccountMeta::new_readonly(self.perpetuals, false),
+            AccountMeta::new(self.pool, false),
+            AccountMeta::new(self.receiving_custody, false),
+            AccountMeta::new_readonly(self.receiving_custody_doves_price_account, false),
+            AccountMeta::new_readonly(self.receiving_custody_pythnet_price_account, false),
+            AccountMeta::new(self.receiving_custody_token_account, false),
+            AccountMeta::new(self.dispensing_custody, false),
+            AccountMeta::new_readonly(self.dispensing_custody_doves_price_account, false),
+            AccountMeta::new_readonly(self.dispensing_custody_pythnet_price_account, false),
+            AccountMeta::new(self.dispensing_custody_token_account, false),
+            AccountMeta::new_readonly(self.event_authority, false),
+        ])
+    }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MeteoraCpamAccounts {
-    pub meteora_cpam_pool_authority: String,
-    pub meteora_cpam_pool: String,
-    pub meteora_cpam_token_a_vault: String,
-    pub meteora_cpam_token_b_vault: String,
-    pub meteora_cpam_event_authority: String,
+    #[serde(with = "pubkey_serde")]
+    pub meteora_cpam_pool_authority: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub meteora_cpam_pool: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub meteora_cpam_token_a_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub meteora_cpam_token_b_vault: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub meteora_cpam_event_authority: Pubkey,
 }
-impl AccountMetaFormattable for MeteoraCpamAccounts {
-    fn to_account_metas(&self, _: bool) -> Vec<AccountMeta> {
+impl DexAdapter for MeteoraCpamAccounts {
+    fn pool_keys(&self) -> Vec<Pubkey> {
+        vec![self.meteora_cpam_pool]
+    }
+
+    fn token_vaults(&self) -> Vec<Pubkey> {
         vec![
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.meteora_cpam_pool_authority).unwrap(),
-                false,
-            ),
-            AccountMeta::new(Pubkey::from_str(&self.meteora_cpam_pool).unwrap(), false),
-            AccountMeta::new(
-                Pubkey::from_str(&self.meteora_cpam_token_a_vault).unwrap(),
-                false,
-            ),
-            AccountMeta::new(
-                Pubkey::from_str(&self.meteora_cpam_token_b_vault).unwrap(),
-                false,
-            ),
-            AccountMeta::new_readonly(
-                Pubkey::from_str(&self.meteora_cpam_event_authority).unwrap(),
-                false,
-            ),
+            self.meteora_cpam_token_a_vault,
+            self.meteora_cpam_token_b_vault,
         ]
     }
-}
 
-#[derive(Debug, Clone, Deserialize)]
-#[serde(untagged)]
-pub enum DexAccount {
-    MeteoraDlmm(MeteoraDlmmAccounts),
-    RaydiumAmm(RaydiumAmmAccounts),
-    RaydiumClmm(RaydiumClmmAccounts),
-    RaydiumCpmm(RaydiumCpmmAccounts),
-    Orca(OrcaAccounts),
-    MeteoraAmm(MeteoraAmmAccounts),
-    Solfi(SolfiAccounts),
-    Lifinity(LifinityAccounts),
-    Phoenix(PhoenixAccounts),
-    Pump(PumpAccounts),
-    Obric(ObricAccounts),
-    Openbook(OpenbookAccounts),
-    JupPerp(JupPerpAccounts),
-    MeteoraCpam(MeteoraCpamAccounts),
-}
-
-impl DexAccount {
-    fn to_account_metas(&self, direction: bool) -> Vec<AccountMeta> {
-        match self {
-            DexAccount::MeteoraDlmm(accounts) => accounts.to_account_metas(direction),
-            DexAccount::RaydiumAmm(accounts) => accounts.to_account_metas(direction),
-            DexAccount::RaydiumClmm(accounts) => accounts.to_account_metas(direction),
-            DexAccount::RaydiumCpmm(accounts) => accounts.to_account_metas(direction),
-            DexAccount::Orca(accounts) => accounts.to_account_metas(direction),
-            DexAccount::MeteoraAmm(accounts) => accounts.to_account_metas(direction),
-            DexAccount::Solfi(accounts) => accounts.to_account_metas(direction),
-            DexAccount::Lifinity(accounts) => accounts.to_account_metas(direction),
-            DexAccount::Phoenix(accounts) => accounts.to_account_metas(direction),
-            DexAccount::Pump(accounts) => accounts.to_account_metas(direction),
-            DexAccount::Obric(accounts) => accounts.to_account_metas(direction),
-            DexAccount::Openbook(accounts) => accounts.to_account_metas(direction),
-            DexAccount::JupPerp(accounts) => accounts.to_account_metas(direction),
-            DexAccount::MeteoraCpam(accounts) => accounts.to_account_metas(direction),
-        }
+    fn to_account_metas(&self, _: bool) -> Result<Vec<AccountMeta>, AccountMetaError> {
+        Ok(vec![
+            AccountMeta::new_readonly(self.meteora_cpam_pool_authority, false),
+            AccountMeta::new(self.meteora_cpam_pool, false),
+            AccountMeta::new(self.meteora_cpam_token_a_vault, false),
+            AccountMeta::new(self.meteora_cpam_token_b_vault, false),
+            AccountMeta::new_readonly(self.meteora_cpam_event_authority, false),
+        ])
     }
 }
-#[derive(Debug, Clone, Deserialize)]
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SwapAccounts {
-    common_accounts: CommonAccounts,
-    dexes: Vec<DexAccount>,
+    pub common_accounts: CommonAccounts,
+    pub dexes: Vec<DexAccount>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ArbiEvent {
-    pub accounts: SwapAccounts,
-    pub dex_types: Vec<DexType>,
-    pub decimals: Vec<u8>,
+    /// 事件结构版本，1 为并行数组格式 (见 route::LegacyArbiEvent)，2 起为 route 格式
+    pub schema_version: u16,
+    pub common_accounts: CommonAccounts,
+    pub route: Route,
     pub transaction: TransactionDetail,
-    pub same_a_b: Vec<bool>,
-    pub input_amounts: Vec<f64>,
-    pub reverse_input_amounts: Vec<f64>,
-    pub token_output_amounts: Vec<u64>,
+    /// 上游给出的 token B 是否为 Token-2022，仅用于与 mint 注册表比对
+    #[serde(default)]
     pub is_token_b_2022: bool,
     pub blockhash: String,
     pub trace_id: String,
@@ -773,11 +930,32 @@ pub struct ArbiEvent {
     pub stream_ts: i64,
 }
 
+pub const CURRENT_SCHEMA_VERSION: u16 = 2;
+
+impl ArbiEvent {
+    /// 路由指纹: 每个 leg 的 dex 类型、池子与方向，以及正反向 leg 的划分
+    pub fn route_fingerprint(&self) -> String {
+        let mut route = vec![self.route.forward_legs().count() as u8];
+        for leg in &self.route.legs {
+            route.push(leg.dex_type().to_u8());
+            route.push(leg.same_a_b() as u8);
+            for pool in leg.dex.adapter().pool_keys() {
+                route.extend_from_slice(pool.as_ref());
+            }
+        }
+        hashv(&[&route]).to_string()
+    }
+}
+
 pub struct TransactionHelpers<'info> {
     pub alt_account: Arc<AddressLookupTableAccount>,
     pub program: Arc<Program<&'info Keypair>>,
     pub connection: Arc<RpcClient>,
     pub wallet: &'info Keypair,
+    pub mints: Arc<MintRegistry>,
+    pub pool_states: Option<Arc<PoolCache>>,
+    pub compute_units: Arc<ComputeUnitModel>,
+    pub priority_fees: Arc<PriorityFees>,
 }
 
 async fn send_bundle_using_jito(
@@ -796,11 +974,10 @@ async fn send_bundle_using_jito(
             signatures.push(signature);
             let serialized_tx = bincode::serialize(&transaction).unwrap();
             let encoded_bytes = bs58::encode(&serialized_tx).into_vec();
-            let encoded_tx = String::from_utf8(encoded_bytes).unwrap();
-            encoded_tx
+            String::from_utf8(encoded_bytes).unwrap()
         })
         .collect();
-    let signature = signatures.get(0).unwrap();
+    let signature = signatures.first().unwrap();
 
     let payload = json!({
         "jsonrpc": "2.0",
@@ -831,7 +1008,7 @@ async fn send_bundle_using_jito(
             "Error: {}, dex_types:{:?}, trace_id:{}",
             error.message, dex_types, trace_id
         );
-        return Ok("".to_string());
+        return Err(error.into());
     }
 
     debug!("Sent jito bundle to region {}: {}", endpoint, signature);
@@ -839,31 +1016,108 @@ async fn send_bundle_using_jito(
     Ok(json.result.unwrap_or_default())
 }
 
+/// 合并重复的账户，保留第一次出现的位置，可写性取并集
+fn merge_account_metas(metas: Vec<AccountMeta>) -> Vec<AccountMeta> {
+    let mut merged: Vec<AccountMeta> = Vec::with_capacity(metas.len());
+    for meta in metas {
+        match merged
+            .iter_mut()
+            .find(|existing| existing.pubkey == meta.pubkey)
+        {
+            Some(existing) => existing.is_writable |= meta.is_writable,
+            None => merged.push(meta),
+        }
+    }
+    merged
+}
+
+/// 上游给出的精度、Token-2022 标记与注册表不一致时记录日志
+fn warn_upstream_mismatch(arbi_event: &ArbiEvent, mint_a: &MintInfo, mint_b: &MintInfo) {
+    if arbi_event.is_token_b_2022 != mint_b.is_token_2022() {
+        warn!(
+            "trace_id: {}, isTokenB2022 {} does not match token program {}",
+            arbi_event.trace_id, arbi_event.is_token_b_2022, mint_b.token_program
+        );
+    }
+    for (index, leg) in arbi_event.route.legs.iter().enumerate() {
+        let expected = if leg.reverse {
+            mint_b.decimals
+        } else {
+            mint_a.decimals
+        };
+        if leg.decimals.is_some_and(|decimals| decimals != expected) {
+            warn!(
+                "trace_id: {}, route.legs[{}].decimals {:?} does not match mint decimals {}",
+                arbi_event.trace_id, index, leg.decimals, expected
+            );
+        }
+    }
+}
+
 pub async fn assemble_and_submit_transaction<'info>(
     arbi_event: ArbiEvent,
     transaction_helpers: TransactionHelpers<'info>,
     request_client: Arc<ReqwestClient>,
+    send_path: SendPath,
 ) -> Result<()> {
     let start = SystemTime::now();
     debug!("Start: {}", start.elapsed().unwrap().as_millis());
     let mut remaining_accounts: Vec<AccountMeta> =
-        vec![AccountMeta::new_readonly(Pubkey::from_str(PROGRAM_PUBKEY_STR).unwrap(), false); 16];
+        vec![
+            AccountMeta::new_readonly(program_pubkey().stage(FailureStage::Assemble)?, false);
+            PROGRAM_SLOTS
+        ];
+
+    // 精度与 token program 以 mint 注册表为准，上游给出的值只用于比对
+    let mint_a = transaction_helpers
+        .mints
+        .get(&arbi_event.common_accounts.token_vault_a_mint)
+        .await
+        .stage(FailureStage::Assemble)?;
+    let mint_b = transaction_helpers
+        .mints
+        .get(&arbi_event.common_accounts.token_vault_b_mint)
+        .await
+        .stage(FailureStage::Assemble)?;
+    warn_upstream_mismatch(&arbi_event, &mint_a, &mint_b);
+    // 链上程序只有 token_b_2022 标记，token_program 按 spl-token 调用，token A 不支持 Token-2022
+    if mint_a.is_token_2022() {
+        return Err(anyhow::anyhow!(
+            "token A {} is a Token-2022 mint, only token B may use Token-2022",
+            arbi_event.common_accounts.token_vault_a_mint
+        ))
+        .stage(FailureStage::Validate);
+    }
 
+    // 闪电贷借出 token A，reserve 由 mint 注册表给出
+    let kamino_reserve = if arbi_event.transaction.use_kamino {
+        Some(
+            transaction_helpers
+                .mints
+                .kamino_reserve(&arbi_event.common_accounts.token_vault_a_mint)
+                .stage(FailureStage::Assemble)?,
+        )
+    } else {
+        None
+    };
+
+    // ATA 地址与 mint 所属的 token program 相关，token A 固定为 spl-token
     let (user_token_account_a, user_token_account_b) = if arbi_event.transaction.use_kamino {
         (
             spl_associated_token_account::get_associated_token_address(
                 &transaction_helpers.wallet.pubkey(),
-                &Pubkey::from_str(&arbi_event.accounts.common_accounts.token_vault_a_mint).unwrap(),
+                &arbi_event.common_accounts.token_vault_a_mint,
             ),
-            spl_associated_token_account::get_associated_token_address(
+            spl_associated_token_account::get_associated_token_address_with_program_id(
                 &transaction_helpers.wallet.pubkey(),
-                &Pubkey::from_str(&arbi_event.accounts.common_accounts.token_vault_b_mint).unwrap(),
+                &arbi_event.common_accounts.token_vault_b_mint,
+                &mint_b.token_program,
             ),
         )
     } else {
         (
-            Pubkey::from_str(&arbi_event.accounts.common_accounts.user_token_account_a).unwrap(),
-            Pubkey::from_str(&arbi_event.accounts.common_accounts.user_token_account_b).unwrap(),
+            arbi_event.common_accounts.user_token_account_a,
+            arbi_event.common_accounts.user_token_account_b,
         )
     };
 
@@ -873,185 +1127,213 @@ pub async fn assemble_and_submit_transaction<'info>(
         system_program: Pubkey::from_str("11111111111111111111111111111111").unwrap(),
         jito_tip_account: transaction_helpers.wallet.pubkey(),
         payer: transaction_helpers.wallet.pubkey(),
-        token_program: Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").unwrap(),
-        token_b_program: if arbi_event.is_token_b_2022 {
-            Pubkey::from_str("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb").unwrap()
-        } else {
-            Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").unwrap()
-        },
+        token_program: spl_token::ID,
+        token_b_program: mint_b.token_program,
         token_program_2022: None,
         memo_program: None,
         instructions_sysvar: None,
-        vault: Pubkey::from_str(&arbi_event.accounts.common_accounts.vault).unwrap(),
-        token_vault_a_mint: Pubkey::from_str(
-            &arbi_event.accounts.common_accounts.token_vault_a_mint,
-        )
-        .unwrap(),
-        token_vault_b_mint: Pubkey::from_str(
-            &arbi_event.accounts.common_accounts.token_vault_b_mint,
-        )
-        .unwrap(),
+        vault: arbi_event.common_accounts.vault,
+        token_vault_a_mint: arbi_event.common_accounts.token_vault_a_mint,
+        token_vault_b_mint: arbi_event.common_accounts.token_vault_b_mint,
         user_token_account_a,
         user_token_account_b,
     };
 
-    if arbi_event.is_token_b_2022 {
-        accounts.token_program_2022 =
-            Some(Pubkey::from_str("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb").unwrap());
+    if mint_b.is_token_2022() {
+        accounts.token_program_2022 = Some(spl_token_2022::ID);
     }
 
-    if arbi_event.transaction.jito_tip_ratio != 0 {
+    // 通过 RPC 发送时不附带 jito tip，链上的 tip 比例为 0，tip 账户为 payer
+    let jito_tip_ratio = if send_path == SendPath::Rpc {
+        0
+    } else {
+        arbi_event.transaction.jito_tip_ratio
+    };
+    if jito_tip_ratio != 0 {
         accounts.jito_tip_account =
             Pubkey::from_str(JITO_TIP_ACCOUNTS.choose(&mut OsRng).unwrap()).unwrap();
     }
 
-    for (index, dex_type) in arbi_event.dex_types.iter().enumerate() {
-        let direction = !(arbi_event.same_a_b[index] ^ (index < arbi_event.input_amounts.len()));
-        remaining_accounts.extend(arbi_event.accounts.dexes[index].to_account_metas(direction));
-        match dex_type.clone() {
-            DexType::RaydiumAmm => {
-                remaining_accounts[0] = AccountMeta::new_readonly(
-                    Pubkey::from_str("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8").unwrap(),
-                    false,
-                );
-                remaining_accounts[1] = AccountMeta::new_readonly(
-                    Pubkey::from_str("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX").unwrap(),
-                    false,
-                );
-            }
-            DexType::RaydiumCpmm => {
-                remaining_accounts[2] = AccountMeta::new_readonly(
-                    Pubkey::from_str("CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C").unwrap(),
-                    false,
-                );
-                accounts.token_program_2022 =
-                    Some(Pubkey::from_str("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb").unwrap());
-            }
-            DexType::RaydiumClmm => {
-                remaining_accounts[3] = AccountMeta::new_readonly(
-                    Pubkey::from_str("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK").unwrap(),
-                    false,
-                );
-                accounts.memo_program =
-                    Some(Pubkey::from_str("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr").unwrap());
-                accounts.token_program_2022 =
-                    Some(Pubkey::from_str("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb").unwrap());
-            }
-            DexType::Orca => {
-                remaining_accounts[4] = AccountMeta::new_readonly(
-                    Pubkey::from_str("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc").unwrap(),
-                    false,
-                );
-            }
-            DexType::MeteoraDlmm => {
-                remaining_accounts[5] = AccountMeta::new_readonly(
-                    Pubkey::from_str("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo").unwrap(),
-                    false,
-                );
-            }
-            DexType::MeteoraAmm => {
-                remaining_accounts[6] = AccountMeta::new_readonly(
-                    Pubkey::from_str("Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB").unwrap(),
-                    false,
-                );
-                remaining_accounts[7] = AccountMeta::new_readonly(
-                    Pubkey::from_str("24Uqj9JCLxUeoC3hGfh5W3s9FM9uCHDS2SG3LYwBpyTi").unwrap(),
-                    false,
-                );
-            }
+    // transfer hook 的 authority 为转出 user token account 的 owner
+    let transfer_authority = if arbi_event.transaction.use_kamino {
+        transaction_helpers.wallet.pubkey()
+    } else {
+        arbi_event.common_accounts.vault
+    };
 
-            DexType::Solfi => {
-                accounts.instructions_sysvar =
-                    Some(Pubkey::from_str("Sysvar1nstructions1111111111111111111111111").unwrap());
-                remaining_accounts[8] = AccountMeta::new_readonly(
-                    Pubkey::from_str("SoLFiHG9TfgtdUXUjWAxi3LtvYuFyDLVhBWxdMZxyCe").unwrap(),
-                    false,
-                );
-            }
-            DexType::Lifinity => {
-                remaining_accounts[9] = AccountMeta::new_readonly(
-                    Pubkey::from_str("2wT8Yq49kHgDzXuPxZSaeLaH1qbmGXtEyPy64bL7aD3c").unwrap(),
-                    false,
-                );
-            }
-            DexType::Phoenix => {
-                remaining_accounts[10] = AccountMeta::new_readonly(
-                    Pubkey::from_str("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY").unwrap(),
-                    false,
-                );
-            }
-            DexType::Pump => {
-                remaining_accounts[11] = AccountMeta::new_readonly(
-                    Pubkey::from_str("pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA").unwrap(),
-                    false,
-                );
-            }
-            DexType::Obric => {
-                remaining_accounts[12] = AccountMeta::new_readonly(
-                    Pubkey::from_str("obriQD1zbpyLz95G5n7nJe6a4DPjpFwa5XYPoNm113y").unwrap(),
-                    false,
-                );
-            }
-            DexType::Openbook => {
-                remaining_accounts[13] = AccountMeta::new_readonly(
-                    Pubkey::from_str("opnb2LAfJYbRMAHHvqjCwQxanZn7ReEHp1k81EohpZb").unwrap(),
-                    false,
-                );
-            }
-            DexType::JupPerp => {
-                remaining_accounts[14] = AccountMeta::new_readonly(
-                    Pubkey::from_str("PERPHjGBqRHArX4DySjwM6UJHiR3sWAatqfdBS2qQJu").unwrap(),
-                    false,
-                );
-            }
-            DexType::MeteoraCpam => {
-                remaining_accounts[15] = AccountMeta::new_readonly(
-                    Pubkey::from_str("cpamdpZCGKUy5JxQXB4dcpGPiikHawvSWAd6mEn1sGG").unwrap(),
-                    false,
-                );
+    // 各 leg 的写账户，用于查询近期优先费
+    let mut writable_accounts: Vec<Pubkey> = vec![];
+    // 各 leg 中转账的 transfer hook 账户
+    let mut hook_accounts: Vec<AccountMeta> = vec![];
+    for (index, leg) in arbi_event.route.legs.iter().enumerate() {
+        let adapter = leg.dex.adapter();
+        let leg_error = |error| LegAccountError {
+            index,
+            dex: leg.dex_type(),
+            error,
+        };
+        let leg_accounts = adapter
+            .to_account_metas(leg.a_to_b)
+            .map_err(leg_error)
+            .stage(FailureStage::Assemble)?;
+        writable_accounts.extend(
+            leg_accounts
+                .iter()
+                .filter(|meta| meta.is_writable)
+                .map(|meta| meta.pubkey),
+        );
+        remaining_accounts.extend(leg_accounts);
+        // 只有 token B 可能带 transfer hook: 反向 leg 从用户账户转入池子的 vault，
+        // 正向 leg 从池子的 vault 转出到用户账户
+        if mint_b.transfer_hook_program.is_some() {
+            let token_b_mint = &arbi_event.common_accounts.token_vault_b_mint;
+            let (vault, vault_owner) = transaction_helpers
+                .mints
+                .find_vault(&adapter.token_vaults(), token_b_mint)
+                .await
+                .stage(FailureStage::Assemble)?;
+            let (source, destination, authority, amount) = if leg.reverse {
+                (
+                    user_token_account_b,
+                    vault,
+                    transfer_authority,
+                    leg.input_units(mint_b.decimals)
+                        .stage(FailureStage::Assemble)?,
+                )
+            } else {
+                (
+                    vault,
+                    user_token_account_b,
+                    vault_owner,
+                    leg.expected_output,
+                )
+            };
+            hook_accounts.extend(
+                transaction_helpers
+                    .mints
+                    .transfer_hook_accounts(
+                        token_b_mint,
+                        &mint_b,
+                        &source,
+                        &destination,
+                        &authority,
+                        amount,
+                    )
+                    .await
+                    .stage(FailureStage::Assemble)?,
+            );
+        }
+        // DEX 程序放入前缀中的固定槽位，并按需传入 Arbi 的可选账户
+        let profile = leg.dex_type().profile();
+        for (slot, program) in profile.program_slots {
+            remaining_accounts[*slot] = AccountMeta::new_readonly(
+                parse_pubkey(program, "programSlots")
+                    .map_err(leg_error)
+                    .stage(FailureStage::Assemble)?,
+                false,
+            );
+        }
+        for account in profile.optional_accounts {
+            let pubkey = Some(account.pubkey());
+            match account {
+                OptionalAccount::MemoProgram => accounts.memo_program = pubkey,
+                OptionalAccount::TokenProgram2022 => accounts.token_program_2022 = pubkey,
+                OptionalAccount::InstructionsSysvar => accounts.instructions_sysvar = pubkey,
             }
         }
     }
 
+    // 按路由中各 leg 的 DEX 估算 CU，模拟结果用于修正估计
+    let dex_types: Vec<DexType> = arbi_event.route.legs.iter().map(Leg::dex_type).collect();
+    let unit_limit = transaction_helpers
+        .compute_units
+        .unit_limit(&dex_types, arbi_event.transaction.use_kamino);
+
+    // transfer hook 账户合并去重后统一放在所有 leg 之后，链上仍按固定宽度解析各 leg，
+    // Token-2022 按地址从中查找每次转账需要的 hook 账户
+    remaining_accounts.extend(merge_account_metas(hook_accounts));
+
     let mut account_metas = accounts.to_account_metas(None);
     account_metas.extend(remaining_accounts);
 
-    let amount_per_token = match arbi_event
-        .accounts
-        .common_accounts
-        .token_vault_a_mint
-        .as_str()
-    {
-        "So11111111111111111111111111111111111111112" => LAMPORTS_PER_SOL,
-        "J1toso1uCk3RLmjorhTtrVwY9HJ7X8V9yYac6Y7kGCPn" => LAMPORTS_PER_SOL,
-        "5XZw2LKTyrfvfiskJ78AMpackRjPcyCif1WhUsPDuVqQ" => 100000000,
-        _ => 1000000,
-    };
-
-    let inputs_size = arbi_event.input_amounts.len();
-    let min_profit = (arbi_event.transaction.min_profit * amount_per_token as f64) as i64;
+    // 最小利润向上取整，小费向下取整，优先费向上取整
+    let min_profit_units = arbi_event
+        .transaction
+        .min_profit
+        .to_units(mint_a.profit_decimals, Rounding::Up)
+        .stage(FailureStage::Assemble)?;
+    let min_profit = i64::try_from(min_profit_units)
+        .map_err(|_| anyhow::anyhow!("min_profit {} overflows i64", min_profit_units))
+        .stage(FailureStage::Assemble)?;
+    let jito_tip_lamports = arbi_event
+        .transaction
+        .jito_tip
+        .to_units(SOL_DECIMALS, Rounding::Down)
+        .stage(FailureStage::Assemble)?;
+    // 上游的 priority_fee 换算为 lamports 后乘以 3.3 作为每 CU 的 micro-lamports
+    let upstream_priority_fee_price = arbi_event
+        .transaction
+        .priority_fee
+        .to_units(SOL_DECIMALS, Rounding::Up)
+        .stage(FailureStage::Assemble)?
+        .saturating_mul(33)
+        .div_ceil(10);
+    let mut route_args = arbi_event
+        .route
+        .to_args(mint_a.decimals, mint_b.decimals)
+        .stage(FailureStage::Assemble)?;
+    let kamino_borrow_amount: u64 = route_args.token_a_amount_list.iter().sum();
+
+    // Token-2022 转账手续费: 预期输出扣除手续费，扣费后利润不足链上的 min_profit 时放弃。
+    // 预期利润以 token A 的最小单位计，链上的 min_profit 换算到同一单位后比较
+    let withheld = route_args.apply_transfer_fees(&arbi_event.route, &mint_a, &mint_b);
+    if !withheld.is_zero() {
+        let required = Amount::Decimal {
+            digits: min_profit_units as u128,
+            scale: mint_a.profit_decimals,
+        }
+        .to_units(mint_a.decimals, Rounding::Up)
+        .stage(FailureStage::Assemble)?;
+        if let Some(profit) = route_args
+            .expected_profit(&arbi_event.route)
+            .filter(|profit| *profit < required as i128)
+        {
+            return Err(anyhow::anyhow!(
+                "expected profit {} after transfer fees is below min_profit {} (withheld token a: {}, token b: {})",
+                profit,
+                required,
+                withheld.token_a,
+                withheld.token_b
+            ))
+            .stage(FailureStage::Unprofitable);
+        }
+        debug!(
+            "trace_id: {}, transfer fees withheld: {:?}",
+            arbi_event.trace_id, withheld
+        );
+    }
+    // 优先费按写账户的近期优先费取分位数，利润以 SOL 计时按预期利润限制上限
+    let profit_lamports = route_args
+        .expected_profit(&arbi_event.route)
+        .filter(|_| arbi_event.common_accounts.token_vault_a_mint == spl_token::native_mint::ID);
+    writable_accounts.sort_unstable();
+    writable_accounts.dedup();
+    let priority_fee_micro_lamports = transaction_helpers.priority_fees.unit_price(
+        &writable_accounts,
+        unit_limit,
+        upstream_priority_fee_price,
+        profit_lamports,
+    );
     let args = ArbiArgs {
         use_pda_vault: !arbi_event.transaction.use_kamino,
-        dex_type_list: arbi_event.dex_types.iter().map(|dt| dt.to_u8()).collect(),
-        same_ab_list: arbi_event.same_a_b.clone(),
-        token_a_amount_list: arbi_event
-            .input_amounts
-            .iter()
-            .enumerate()
-            .map(|(i, amount)| (amount * 10u64.pow(arbi_event.decimals[i] as u32) as f64) as u64)
-            .collect(),
-        token_b_amount_list: arbi_event
-            .reverse_input_amounts
-            .iter()
-            .enumerate()
-            .map(|(i, amount)| {
-                (amount * 10u64.pow(arbi_event.decimals[i + inputs_size] as u32) as f64) as u64
-            })
-            .collect(),
-        token_output_amount_list: arbi_event.token_output_amounts,
-        token_b_2022: arbi_event.is_token_b_2022,
+        dex_type_list: route_args.dex_type_list,
+        same_ab_list: route_args.same_ab_list,
+        token_a_amount_list: route_args.token_a_amount_list,
+        token_b_amount_list: route_args.token_b_amount_list,
+        token_output_amount_list: route_args.token_output_amount_list,
+        token_b_2022: mint_b.is_token_2022(),
         min_profit,
-        jito_tip_ratio: arbi_event.transaction.jito_tip_ratio,
+        jito_tip_ratio,
     };
 
     let swap_instruction = transaction_helpers
@@ -1060,16 +1342,24 @@ pub async fn assemble_and_submit_transaction<'info>(
         .accounts(account_metas)
         .args(args)
         .instructions()
-        .unwrap()
-        .get(0)
-        .unwrap()
-        .clone();
+        .stage(FailureStage::Assemble)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("arbi request built no instruction"))
+        .stage(FailureStage::Assemble)?;
+
+    let blockhash = Hash::from_str(&arbi_event.blockhash)
+        .map_err(|e| anyhow::anyhow!("invalid blockhash {}: {}", arbi_event.blockhash, e))
+        .stage(FailureStage::Assemble)?;
 
     let jito_slice_start;
     let jito_slice_end;
 
-    let using_jito =
-        arbi_event.transaction.jito_tip_ratio > 0 || arbi_event.transaction.jito_tip > 0.0;
+    let using_jito = match send_path {
+        SendPath::Jito => true,
+        SendPath::Rpc => false,
+        _ => jito_tip_ratio > 0 || jito_tip_lamports > 0,
+    };
 
     if using_jito {
         let jito_slice = std::env::var("JITO_SLICE").unwrap_or_else(|_| "0,5".to_string());
@@ -1088,59 +1378,39 @@ pub async fn assemble_and_submit_transaction<'info>(
     let transactions: Vec<Vec<VersionedTransaction>> = jito_endpoints
         .into_iter()
         .enumerate()
-        .map(|(i, _)| {
+        .map(|(i, _)| -> Result<Vec<VersionedTransaction>> {
             let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(
-                UNIT_LIMIT
-                    + if arbi_event.transaction.use_kamino {
-                        KAMINO_ADDITIONAL_COMPUTE_UNITS
-                    } else {
-                        0
-                    }
-                    + i as u32,
+                unit_limit + i as u32,
             )];
 
-            if arbi_event.transaction.priority_fee > 0.0 {
-                let micro_lamports =
-                    (arbi_event.transaction.priority_fee as f64 * 10f64.powi(9) * 3.3).ceil()
-                        as u64;
+            if priority_fee_micro_lamports > 0 {
                 instructions.push(ComputeBudgetInstruction::set_compute_unit_price(
-                    micro_lamports,
+                    priority_fee_micro_lamports,
                 ));
             }
 
-            let kamino_borrow_amount = arbi_event
-                .input_amounts
-                .iter()
-                .enumerate()
-                .map(|(i, amount)| {
-                    (amount * 10u64.pow(arbi_event.decimals[i] as u32) as f64) as u64
-                })
-                .sum();
-
-            if arbi_event.transaction.use_kamino {
+            if let Some(kamino_reserve) = &kamino_reserve {
                 instructions.push(
                     get_kamino_flashloan_borrow_ix(
                         &transaction_helpers.wallet.pubkey(),
                         user_token_account_a,
-                        Pubkey::from_str(&arbi_event.accounts.common_accounts.token_vault_a_mint)
-                            .unwrap(),
+                        arbi_event.common_accounts.token_vault_a_mint,
+                        kamino_reserve,
                         kamino_borrow_amount,
                     )
-                    .unwrap(),
+                    .stage(FailureStage::Assemble)?,
                 );
             }
 
             instructions.push(swap_instruction.clone());
             let proxy_wallet = Keypair::new();
 
-            if arbi_event.transaction.jito_tip > 0.0 {
-                let jito_tip_amount =
-                    (arbi_event.transaction.jito_tip * LAMPORTS_PER_SOL as f64).floor() as u64;
+            if using_jito && jito_tip_lamports > 0 {
                 if arbi_event.transaction.use_proxy_account {
                     instructions.push(transfer(
                         &transaction_helpers.wallet.pubkey(),
                         &proxy_wallet.pubkey(),
-                        jito_tip_amount + PROXY_PRESERVED_BALANCE,
+                        jito_tip_lamports + PROXY_PRESERVED_BALANCE,
                     ));
                 } else {
                     let jito_tip_account =
@@ -1148,26 +1418,26 @@ pub async fn assemble_and_submit_transaction<'info>(
                     instructions.push(transfer(
                         &transaction_helpers.wallet.pubkey(),
                         &jito_tip_account,
-                        jito_tip_amount,
+                        jito_tip_lamports,
                     ));
                 }
             }
 
-            if arbi_event.transaction.use_kamino {
+            if let Some(kamino_reserve) = &kamino_reserve {
                 instructions.push(
                     get_kamino_flashloan_repay_ix(
                         &transaction_helpers.wallet.pubkey(),
                         user_token_account_a,
-                        Pubkey::from_str(&arbi_event.accounts.common_accounts.token_vault_a_mint)
-                            .unwrap(),
-                        if arbi_event.transaction.priority_fee > 0.0 {
+                        arbi_event.common_accounts.token_vault_a_mint,
+                        kamino_reserve,
+                        if priority_fee_micro_lamports > 0 {
                             2
                         } else {
                             1
                         },
                         kamino_borrow_amount,
                     )
-                    .unwrap(),
+                    .stage(FailureStage::Assemble)?,
                 );
             }
 
@@ -1175,25 +1445,23 @@ pub async fn assemble_and_submit_transaction<'info>(
                 &transaction_helpers.wallet.pubkey(),
                 &instructions,
                 &[transaction_helpers.alt_account.as_ref().clone()],
-                Hash::from_str(&arbi_event.blockhash).unwrap(),
+                blockhash,
             )
-            .unwrap();
+            .stage(FailureStage::Assemble)?;
 
             let tx1 = VersionedTransaction::try_new(
                 VersionedMessage::V0(message),
                 &[&transaction_helpers.wallet],
             )
-            .unwrap();
+            .stage(FailureStage::Assemble)?;
             let mut transaction_vec = vec![tx1];
 
-            if arbi_event.transaction.jito_tip > 0.0 && arbi_event.transaction.use_proxy_account {
+            if using_jito && jito_tip_lamports > 0 && arbi_event.transaction.use_proxy_account {
                 let jito_tip_account =
                     Pubkey::from_str(JITO_TIP_ACCOUNTS.choose(&mut OsRng).unwrap()).unwrap();
-                let jito_tip_amount =
-                    (arbi_event.transaction.jito_tip * LAMPORTS_PER_SOL as f64).floor() as u64;
 
                 let tx2_instructions = vec![
-                    transfer(&proxy_wallet.pubkey(), &jito_tip_account, jito_tip_amount),
+                    transfer(&proxy_wallet.pubkey(), &jito_tip_account, jito_tip_lamports),
                     transfer(
                         &proxy_wallet.pubkey(),
                         &transaction_helpers.wallet.pubkey(),
@@ -1201,44 +1469,96 @@ pub async fn assemble_and_submit_transaction<'info>(
                     ),
                 ];
 
-                let tx2_message = Message::try_compile(
-                    &proxy_wallet.pubkey(),
-                    &tx2_instructions,
-                    &[],
-                    Hash::from_str(&arbi_event.blockhash).unwrap(),
-                )
-                .unwrap();
+                let tx2_message =
+                    Message::try_compile(&proxy_wallet.pubkey(), &tx2_instructions, &[], blockhash)
+                        .stage(FailureStage::Assemble)?;
 
                 let tx2 = VersionedTransaction::try_new(
                     VersionedMessage::V0(tx2_message),
                     &[&proxy_wallet],
                 )
-                .unwrap();
+                .stage(FailureStage::Assemble)?;
                 transaction_vec.push(tx2);
             }
-            transaction_vec
+            Ok(transaction_vec)
         })
-        .collect();
+        .collect::<Result<Vec<_>>>()?;
     debug!(
         "trace_id: {}, assemble duration: {}",
         arbi_event.trace_id,
         SystemTime::now().duration_since(start).unwrap().as_millis()
     );
 
-    if arbi_event.transaction.simulate {
+    if send_path == SendPath::DryRun {
+        for transaction_vec in transactions.iter() {
+            let encoded: Vec<String> = transaction_vec
+                .iter()
+                .map(|transaction| {
+                    base64::engine::general_purpose::STANDARD
+                        .encode(bincode::serialize(transaction).unwrap())
+                })
+                .collect();
+            info!(
+                "dry run trace_id: {}, signature: {}, transactions: {:?}",
+                arbi_event.trace_id, transaction_vec[0].signatures[0], encoded
+            );
+        }
+    } else if arbi_event.transaction.simulate || send_path == SendPath::Simulate {
+        // 缓存中路由池子状态的最新 slot，模拟的节点需要至少到达该 slot
+        let pool_slot = transaction_helpers.pool_states.as_ref().and_then(|cache| {
+            arbi_event
+                .route
+                .legs
+                .iter()
+                .flat_map(|leg| leg.dex.adapter().pool_keys())
+                .filter_map(|pool| cache.snapshot(&pool))
+                .map(|snapshot| snapshot.slot)
+                .max()
+        });
         let result = transaction_helpers
             .connection
-            .simulate_transaction(transactions.get(0).unwrap().get(0).unwrap())
+            .simulate_transaction_with_config(
+                transactions
+                    .first()
+                    .and_then(|transaction_vec| transaction_vec.first())
+                    .ok_or_else(|| anyhow::anyhow!("no transaction to simulate"))
+                    .stage(FailureStage::Submit)?,
+                RpcSimulateTransactionConfig {
+                    commitment: Some(transaction_helpers.connection.commitment()),
+                    min_context_slot: pool_slot,
+                    ..Default::default()
+                },
+            )
             .await
-            .unwrap();
+            .stage(FailureStage::Submit)?;
         debug!("simulate_transaction: {:#?}", result);
+        // 超出 CU 上限时的消耗即为上限，只用于向上修正估计
+        let budget_exceeded = matches!(
+            result.value.err,
+            Some(TransactionError::InstructionError(
+                _,
+                InstructionError::ComputationalBudgetExceeded
+            ))
+        );
+        if let Some(units_consumed) = result
+            .value
+            .units_consumed
+            .filter(|_| result.value.err.is_none() || budget_exceeded)
+        {
+            transaction_helpers.compute_units.observe(
+                &dex_types,
+                arbi_event.transaction.use_kamino,
+                units_consumed,
+                budget_exceeded,
+            );
+        }
     } else if using_jito {
         let futures = JITO_ENDPOINTS[jito_slice_start..jito_slice_end]
-            .into_iter()
-            .zip(transactions.into_iter())
+            .iter()
+            .zip(transactions)
             .map(|(endpoint, transaction_vec)| {
                 let request_client_clone = request_client.clone();
-                let dex_types_clone = arbi_event.dex_types.clone();
+                let dex_types_clone = dex_types.clone();
                 let trace_id_clone = arbi_event.trace_id.clone();
                 tokio::spawn(async move {
                     send_bundle_using_jito(
@@ -1253,17 +1573,23 @@ pub async fn assemble_and_submit_transaction<'info>(
             })
             .collect::<Vec<_>>();
 
+        let mut errors = vec![];
         let result: Vec<String> = join_all(futures)
             .await
             .into_iter()
             .map(|result| match result {
                 Ok(Ok(value)) => value,
                 Ok(Err(e)) => {
-                    error!("Error: {}", e);
+                    // jito 拒绝 bundle 已在 send_bundle_using_jito 中记录
+                    if e.downcast_ref::<JitoError>().is_none() {
+                        error!("Error: {}", e);
+                    }
+                    errors.push(e.to_string());
                     "".to_string()
                 }
                 Err(e) => {
                     error!("Error: {}", e);
+                    errors.push(e.to_string());
                     "".to_string()
                 }
             })
@@ -1272,21 +1598,28 @@ pub async fn assemble_and_submit_transaction<'info>(
             "trace_id: {}, submit txids: {:?}",
             arbi_event.trace_id, result
         );
+        if result.iter().all(|txid| txid.is_empty()) {
+            return Err(anyhow::anyhow!(
+                "no jito endpoint accepted the bundle: {}",
+                errors.join("; ")
+            ))
+            .stage(FailureStage::Submit);
+        }
     } else {
         let result = transaction_helpers
             .connection
             .send_transaction_with_config(
-                transactions.get(0).unwrap().get(0).unwrap(),
+                transactions.first().unwrap().first().unwrap(),
                 RpcSendTransactionConfig {
                     skip_preflight: true,
                     ..RpcSendTransactionConfig::default()
                 },
             )
-            .await;
+            .await
+            .stage(FailureStage::Submit)?;
         debug!(
             "normal submit with trace_id: {}, submit txids: [{:?}]",
-            arbi_event.trace_id,
-            result.unwrap()
+            arbi_event.trace_id, result
         );
     }
     debug!(
@@ -0,0 +1,267 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use std::io::{BufRead, Read, Write};
+
+use crate::submiter::dead_letter::{FailureStage, StageContext};
+use crate::submiter::wire::{encode_arbi_event, parse_json_event};
+
+/// 帧头: MAGIC(4) + VERSION(1) + CODEC(1) + payload。
+/// 未带帧头的消息按旧格式 (LZ4 size-prepended) 解码，
+/// MAGIC 按旧格式解读时的长度约 1.1GB，远超解压上限，不会与合法的旧消息混淆
+pub const FRAME_MAGIC: &[u8; 4] = b"ARBE";
+pub const FRAME_VERSION: u8 = 1;
+const FRAME_HEADER_LEN: usize = FRAME_MAGIC.len() + 2;
+
+/// 解压后大小默认上限 4MB，可用 MAX_DECOMPRESSED_SIZE 调整
+const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 4 * 1024 * 1024;
+
+/// 消息体的压缩方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Raw = 0,
+    Lz4 = 1,
+    Zlib = 2,
+    Zstd = 3,
+}
+
+impl Codec {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Codec::Raw),
+            1 => Some(Codec::Lz4),
+            2 => Some(Codec::Zlib),
+            3 => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "raw" => Some(Codec::Raw),
+            "lz4" => Some(Codec::Lz4),
+            "zlib" => Some(Codec::Zlib),
+            "zstd" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+}
+
+pub fn max_decompressed_size() -> usize {
+    static MAX_SIZE: std::sync::OnceLock<usize> = std::sync::OnceLock::new();
+    *MAX_SIZE.get_or_init(|| {
+        std::env::var("MAX_DECOMPRESSED_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_DECOMPRESSED_SIZE)
+    })
+}
+
+/// 解码消息: 以 `{` 开头视为明文 JSON，否则 base64 解码后按帧头选择解压方式
+pub fn decode_payload(value: &str) -> Result<Vec<u8>> {
+    if value.trim_start().starts_with('{') {
+        return Ok(value.as_bytes().to_vec());
+    }
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(value.trim())
+        .stage(FailureStage::Decode)?;
+    decode_frame(&data, max_decompressed_size())
+}
+
+/// 帧使用的压缩算法，未带帧头的旧格式为 LZ4
+pub fn frame_codec(data: &[u8]) -> Result<Codec> {
+    if !data.starts_with(FRAME_MAGIC) {
+        return Ok(Codec::Lz4);
+    }
+    let codec_id = *data
+        .get(FRAME_MAGIC.len() + 1)
+        .ok_or_else(|| anyhow::anyhow!("frame too short: {} bytes", data.len()))?;
+    Codec::from_u8(codec_id).ok_or_else(|| anyhow::anyhow!("unsupported codec: {}", codec_id))
+}
+
+/// 解码二进制帧，未带帧头时按旧格式 LZ4 处理
+pub fn decode_frame(data: &[u8], max_size: usize) -> Result<Vec<u8>> {
+    if !data.starts_with(FRAME_MAGIC) {
+        return decompress(Codec::Lz4, data, max_size);
+    }
+    if data.len() < FRAME_HEADER_LEN {
+        return Err(anyhow::anyhow!("frame too short: {} bytes", data.len()))
+            .stage(FailureStage::Decode);
+    }
+    let version = data[FRAME_MAGIC.len()];
+    if version != FRAME_VERSION {
+        return Err(anyhow::anyhow!("unsupported frame version: {}", version))
+            .stage(FailureStage::Decode);
+    }
+    let codec_id = data[FRAME_MAGIC.len() + 1];
+    let codec = Codec::from_u8(codec_id)
+        .ok_or_else(|| anyhow::anyhow!("unsupported codec: {}", codec_id))
+        .stage(FailureStage::Decode)?;
+    decompress(codec, &data[FRAME_HEADER_LEN..], max_size)
+}
+
+/// 按 codec 解压，超过 max_size 时报错
+pub fn decompress(codec: Codec, payload: &[u8], max_size: usize) -> Result<Vec<u8>> {
+    let data = match codec {
+        Codec::Raw => payload.to_vec(),
+        Codec::Lz4 => {
+            // 先检查声明的长度，避免按伪造的长度分配内存
+            let (size, block) = lz4_flex::block::uncompressed_size(payload)
+                .context("LZ4 解压失败")
+                .stage(FailureStage::Decompress)?;
+            check_size(size, max_size)?;
+            lz4_flex::decompress(block, size)
+                .context("LZ4 解压失败")
+                .stage(FailureStage::Decompress)?
+        }
+        Codec::Zlib => read_limited(flate2::read::ZlibDecoder::new(payload), max_size)
+            .context("zlib 解压失败")
+            .stage(FailureStage::Decompress)?,
+        Codec::Zstd => {
+            let decoder = zstd::stream::read::Decoder::new(payload)
+                .context("zstd 解压失败")
+                .stage(FailureStage::Decompress)?;
+            read_limited(decoder, max_size)
+                .context("zstd 解压失败")
+                .stage(FailureStage::Decompress)?
+        }
+    };
+    check_size(data.len(), max_size)?;
+    Ok(data)
+}
+
+/// 最多读取 max_size + 1 字节，多出的一个字节用于判断是否超限
+fn read_limited<R: Read>(reader: R, max_size: usize) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.take(max_size as u64 + 1).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn check_size(size: usize, max_size: usize) -> Result<()> {
+    if size > max_size {
+        return Err(anyhow::anyhow!(
+            "decompressed size {} exceeds limit {}",
+            size,
+            max_size
+        ))
+        .stage(FailureStage::Decompress);
+    }
+    Ok(())
+}
+
+/// 生产端使用: 按 codec 压缩并加上帧头，返回 base64 字符串
+pub fn encode_payload(codec: Codec, data: &[u8]) -> Result<String> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + data.len());
+    frame.extend_from_slice(FRAME_MAGIC);
+    frame.push(FRAME_VERSION);
+    frame.push(codec as u8);
+    match codec {
+        Codec::Raw => frame.extend_from_slice(data),
+        Codec::Lz4 => frame.extend_from_slice(&lz4_flex::compress_prepend_size(data)),
+        Codec::Zlib => {
+            let mut encoder = flate2::write::ZlibEncoder::new(frame, flate2::Compression::fast());
+            encoder.write_all(data)?;
+            frame = encoder.finish()?;
+        }
+        Codec::Zstd => frame.extend_from_slice(&zstd::stream::encode_all(data, 0)?),
+    }
+    Ok(base64::engine::general_purpose::STANDARD.encode(frame))
+}
+
+const ENCODE_USAGE: &str = "usage: encode <raw|lz4|zlib|zstd> [--binary] [file]";
+
+/// 生产端工具: 将 JSONL (文件或标准输入) 的每一行按 codec 编码后输出到标准输出，
+/// --binary 时先将 JSON ArbiEvent 转为二进制格式
+pub fn run_encode_command(args: &[String]) -> Result<()> {
+    let codec = args
+        .first()
+        .and_then(|name| Codec::from_name(name))
+        .context(ENCODE_USAGE)?;
+    let binary = args.get(1).map(|arg| arg.as_str()) == Some("--binary");
+    let path = args.get(if binary { 2 } else { 1 });
+    let reader: Box<dyn BufRead> = match path {
+        Some(path) => Box::new(std::io::BufReader::new(
+            std::fs::File::open(path).with_context(|| format!("打开文件失败: {}", path))?,
+        )),
+        None => Box::new(std::io::stdin().lock()),
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let data = if binary {
+            let arbi_event = parse_json_event(line.trim())?;
+            encode_arbi_event(&arbi_event)?
+        } else {
+            line.trim().as_bytes().to_vec()
+        };
+        writeln!(stdout, "{}", encode_payload(codec, &data)?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODECS: [Codec; 4] = [Codec::Raw, Codec::Lz4, Codec::Zlib, Codec::Zstd];
+
+    fn frame(codec: Codec, data: &[u8]) -> Vec<u8> {
+        base64::engine::general_purpose::STANDARD
+            .decode(encode_payload(codec, data).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip_every_codec() {
+        let data = br#"{"schema_version":1,"legs":[]}"#.repeat(10);
+        for codec in CODECS {
+            let payload = encode_payload(codec, &data).unwrap();
+            assert_eq!(decode_payload(&payload).unwrap(), data, "{:?}", codec);
+        }
+    }
+
+    #[test]
+    fn plain_json_passes_through() {
+        assert_eq!(decode_payload(" {\"a\":1}").unwrap(), b" {\"a\":1}");
+    }
+
+    #[test]
+    fn legacy_lz4_without_header() {
+        let data = b"legacy payload".repeat(4);
+        let legacy = lz4_flex::compress_prepend_size(&data);
+        assert_eq!(decode_frame(&legacy, 1024).unwrap(), data);
+    }
+
+    #[test]
+    fn size_limit_is_inclusive() {
+        let data = vec![7u8; 1000];
+        for codec in CODECS {
+            let frame = frame(codec, &data);
+            assert_eq!(decode_frame(&frame, 1000).unwrap(), data, "{:?}", codec);
+            assert!(decode_frame(&frame, 999).is_err(), "{:?}", codec);
+        }
+    }
+
+    #[test]
+    fn lz4_declared_size_checked_before_allocation() {
+        // 声明 1GB 的解压长度，只带几个字节的数据
+        let mut forged = (1u32 << 30).to_le_bytes().to_vec();
+        forged.extend_from_slice(&[0x10, 0x41]);
+        let error = decode_frame(&forged, max_decompressed_size()).unwrap_err();
+        assert!(error.to_string().contains("exceeds limit"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_bad_header() {
+        let mut frame = frame(Codec::Raw, b"{}");
+        assert!(decode_frame(&frame[..FRAME_HEADER_LEN - 1], 1024).is_err());
+        frame[FRAME_MAGIC.len()] = FRAME_VERSION + 1;
+        assert!(decode_frame(&frame, 1024).is_err());
+        frame[FRAME_MAGIC.len()] = FRAME_VERSION;
+        frame[FRAME_MAGIC.len() + 1] = 9;
+        assert!(decode_frame(&frame, 1024).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use log::{debug, warn};
+use std::{collections::HashMap, sync::RwLock};
+
+use crate::submiter::dex::DexType;
+use crate::submiter::kamino::KAMINO_ADDITIONAL_COMPUTE_UNITS;
+
+/// 套利程序自身的 CU 消耗，不含各 leg
+const ARBI_BASE_COMPUTE_UNITS: u32 = 50_000;
+/// 单笔交易的 CU 上限
+const MAX_COMPUTE_UNITS: u32 = 1_400_000;
+/// 路由中有 DEX 的模拟次数不足 CU_MIN_OBSERVATIONS 时 compute unit limit 不低于该值
+const UNIT_LIMIT: u32 = 300_000;
+
+/// 按路由估算 compute unit limit: 套利程序基础消耗 + 各 leg 所在 DEX 的单 leg 消耗
+/// (+ Kamino 闪电贷)，再上浮 CU_SAFETY_MARGIN_PCT。
+/// 单 leg 消耗的初始值取自 CU_PROFILE 或 DexProfile::compute_units，
+/// 之后按模拟结果的 unitsConsumed 以 CU_LEARNING_RATE 逐步修正，为 0 时不修正。
+/// 只有 simulate 模式会模拟交易，通过 Jito / RPC 发送时不修正。
+/// 路由中每个 DEX 都修正过 CU_MIN_OBSERVATIONS 次之前，limit 不低于 UNIT_LIMIT
+pub struct ComputeUnitModel {
+    costs: RwLock<HashMap<DexType, f64>>,
+    /// 各 DEX 按实际消耗修正的次数，超出 CU 上限的模拟不计入
+    observations: RwLock<HashMap<DexType, u32>>,
+    margin: f64,
+    learning_rate: f64,
+    min_observations: u32,
+}
+
+impl ComputeUnitModel {
+    pub fn from_env() -> Self {
+        // CU_PROFILE=raydiumClmm:110000,orca:90000
+        let costs = std::env::var("CU_PROFILE")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|pair| !pair.trim().is_empty())
+            .filter_map(|pair| {
+                let parsed = pair.trim().split_once(':').and_then(|(dex, units)| {
+                    Some((
+                        serde_json::from_value::<DexType>(serde_json::Value::String(
+                            dex.to_string(),
+                        ))
+                        .ok()?,
+                        units.parse::<f64>().ok()?,
+                    ))
+                });
+                if parsed.is_none() {
+                    warn!("invalid CU_PROFILE entry: {}", pair);
+                }
+                parsed
+            })
+            .collect();
+        let margin = std::env::var("CU_SAFETY_MARGIN_PCT")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(20.0)
+            / 100.0;
+        let learning_rate = std::env::var("CU_LEARNING_RATE")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(0.2)
+            .clamp(0.0, 1.0);
+        let min_observations = std::env::var("CU_MIN_OBSERVATIONS")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(20);
+        ComputeUnitModel {
+            costs: RwLock::new(costs),
+            observations: RwLock::new(HashMap::new()),
+            margin,
+            learning_rate,
+            min_observations,
+        }
+    }
+
+    fn fixed_units(use_kamino: bool) -> u32 {
+        ARBI_BASE_COMPUTE_UNITS
+            + if use_kamino {
+                KAMINO_ADDITIONAL_COMPUTE_UNITS
+            } else {
+                0
+            }
+    }
+
+    fn leg_cost(costs: &HashMap<DexType, f64>, dex_type: DexType) -> f64 {
+        costs
+            .get(&dex_type)
+            .copied()
+            .unwrap_or_else(|| dex_type.profile().compute_units as f64)
+    }
+
+    /// 路由的预估消耗，不含余量
+    pub fn estimate(&self, dex_types: &[DexType], use_kamino: bool) -> f64 {
+        let costs = self.costs.read().unwrap();
+        Self::fixed_units(use_kamino) as f64
+            + dex_types
+                .iter()
+                .map(|dex_type| Self::leg_cost(&costs, *dex_type))
+                .sum::<f64>()
+    }
+
+    /// 交易的 compute unit limit
+    pub fn unit_limit(&self, dex_types: &[DexType], use_kamino: bool) -> u32 {
+        let limit = (self.estimate(dex_types, use_kamino) * (1.0 + self.margin)).ceil() as u32;
+        let observations = self.observations.read().unwrap();
+        let learned = dex_types.iter().all(|dex_type| {
+            observations.get(dex_type).copied().unwrap_or(0) >= self.min_observations
+        });
+        // 观测不足时估计值可能偏低，仍以 UNIT_LIMIT 兜底
+        let limit = if learned {
+            limit
+        } else {
+            limit.max(UNIT_LIMIT)
+        };
+        limit.min(MAX_COMPUTE_UNITS)
+    }
+
+    /// 记录一次模拟的实际消耗: 扣除固定部分后按当前估计的比例分摊到各 leg，
+    /// 每个 DEX 的单 leg 消耗向分摊值移动 learning_rate。
+    /// budget_exceeded 为 true 时 units_consumed 即为 CU 上限，实际消耗只知道下限，
+    /// 只向上修正且不计入观测次数
+    pub fn observe(
+        &self,
+        dex_types: &[DexType],
+        use_kamino: bool,
+        units_consumed: u64,
+        budget_exceeded: bool,
+    ) {
+        if self.learning_rate == 0.0 || dex_types.is_empty() {
+            return;
+        }
+        let residual = units_consumed.saturating_sub(Self::fixed_units(use_kamino) as u64) as f64;
+        let mut costs = self.costs.write().unwrap();
+        let total: f64 = dex_types
+            .iter()
+            .map(|dex_type| Self::leg_cost(&costs, *dex_type))
+            .sum();
+        if total <= 0.0 {
+            return;
+        }
+        let mut observations = self.observations.write().unwrap();
+        let mut updated: Vec<DexType> = Vec::with_capacity(dex_types.len());
+        for dex_type in dex_types {
+            // 同一 DEX 的多个 leg 分摊值相同，只更新一次
+            if updated.contains(dex_type) {
+                continue;
+            }
+            updated.push(*dex_type);
+            let cost = Self::leg_cost(&costs, *dex_type);
+            let share = residual * cost / total;
+            if budget_exceeded {
+                if share > cost {
+                    costs.insert(*dex_type, cost + self.learning_rate * (share - cost));
+                }
+                continue;
+            }
+            costs.insert(*dex_type, cost + self.learning_rate * (share - cost));
+            *observations.entry(*dex_type).or_default() += 1;
+        }
+        debug!(
+            "compute units observed {} for {:?}, leg costs: {:?}",
+            units_consumed, dex_types, costs
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(learning_rate: f64) -> ComputeUnitModel {
+        ComputeUnitModel {
+            costs: RwLock::new(HashMap::from([
+                (DexType::RaydiumCpmm, 60_000.0),
+                (DexType::Orca, 100_000.0),
+            ])),
+            observations: RwLock::new(HashMap::new()),
+            margin: 0.2,
+            learning_rate,
+            min_observations: 0,
+        }
+    }
+
+    fn cost(model: &ComputeUnitModel, dex_type: DexType) -> f64 {
+        model.costs.read().unwrap()[&dex_type]
+    }
+
+    #[test]
+    fn unit_limit_adds_margin_and_caps() {
+        let model = model(0.5);
+        let route = [DexType::RaydiumCpmm, DexType::Orca];
+        assert_eq!(model.estimate(&route, false), 210_000.0);
+        assert_eq!(model.unit_limit(&route, false), 252_000);
+        assert_eq!(
+            model.estimate(&route, true),
+            210_000.0 + KAMINO_ADDITIONAL_COMPUTE_UNITS as f64
+        );
+        assert_eq!(
+            model.unit_limit(&[DexType::Orca; 20], false),
+            MAX_COMPUTE_UNITS
+        );
+    }
+
+    #[test]
+    fn unit_limit_floor_until_observed() {
+        let model = ComputeUnitModel {
+            min_observations: 2,
+            ..model(0.5)
+        };
+        let route = [DexType::RaydiumCpmm, DexType::Orca];
+        let units = ARBI_BASE_COMPUTE_UNITS as u64 + 160_000;
+        assert_eq!(model.unit_limit(&route, false), UNIT_LIMIT);
+        model.observe(&route, false, units, false);
+        assert_eq!(model.unit_limit(&route, false), UNIT_LIMIT);
+        model.observe(&route, false, units, false);
+        assert_eq!(model.unit_limit(&route, false), 252_000);
+        // 未观测过的 DEX 出现在路由中时恢复兜底
+        assert_eq!(
+            model.unit_limit(&[DexType::RaydiumCpmm, DexType::MeteoraDlmm], false),
+            UNIT_LIMIT
+        );
+    }
+
+    #[test]
+    fn observe_splits_residual_by_current_costs() {
+        let model = model(0.5);
+        let route = [DexType::RaydiumCpmm, DexType::Orca];
+        // 扣除基础消耗后 240k 按 60k:100k 分摊为 90k 与 150k
+        model.observe(
+            &route,
+            false,
+            ARBI_BASE_COMPUTE_UNITS as u64 + 240_000,
+            false,
+        );
+        assert_eq!(cost(&model, DexType::RaydiumCpmm), 75_000.0);
+        assert_eq!(cost(&model, DexType::Orca), 125_000.0);
+    }
+
+    #[test]
+    fn observe_updates_repeated_dex_once() {
+        let model = model(0.5);
+        let route = [DexType::RaydiumCpmm, DexType::RaydiumCpmm];
+        model.observe(
+            &route,
+            false,
+            ARBI_BASE_COMPUTE_UNITS as u64 + 100_000,
+            false,
+        );
+        assert_eq!(cost(&model, DexType::RaydiumCpmm), 55_000.0);
+    }
+
+    #[test]
+    fn observe_excludes_kamino_units() {
+        let model = model(1.0);
+        let units = (ARBI_BASE_COMPUTE_UNITS + KAMINO_ADDITIONAL_COMPUTE_UNITS) as u64 + 80_000;
+        model.observe(&[DexType::Orca], true, units, false);
+        assert_eq!(cost(&model, DexType::Orca), 80_000.0);
+    }
+
+    #[test]
+    fn budget_exceeded_only_raises_and_is_not_counted() {
+        let model = ComputeUnitModel {
+            min_observations: 1,
+            ..model(0.5)
+        };
+        let route = [DexType::Orca];
+        model.observe(&route, false, ARBI_BASE_COMPUTE_UNITS as u64 + 50_000, true);
+        assert_eq!(cost(&model, DexType::Orca), 100_000.0);
+        model.observe(
+            &route,
+            false,
+            ARBI_BASE_COMPUTE_UNITS as u64 + 200_000,
+            true,
+        );
+        assert_eq!(cost(&model, DexType::Orca), 150_000.0);
+        assert_eq!(model.unit_limit(&route, false), UNIT_LIMIT);
+    }
+
+    #[test]
+    fn observe_disabled_without_learning_rate() {
+        let model = model(0.0);
+        model.observe(&[DexType::Orca], false, 1_000_000, false);
+        assert_eq!(cost(&model, DexType::Orca), 100_000.0);
+    }
+}
@@ -0,0 +1,459 @@
+use anyhow::Result;
+use base64::Engine;
+use log::{error, info, warn};
+use redis::{aio::ConnectionManager, AsyncCommands, Direction};
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::SystemTime};
+
+use crate::submiter::codec::{decode_frame, encode_payload, frame_codec, max_decompressed_size};
+use crate::submiter::queues::{load_queue_policies, QueuePolicy};
+use crate::submiter::submitter::get_or_init_redis;
+use crate::submiter::wire::{decode_arbi_event, encode_arbi_event, BINARY_MAGIC};
+
+/// 事件处理失败的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureStage {
+    /// base64 解码
+    Decode,
+    /// 解压
+    Decompress,
+    /// UTF-8 解码
+    Utf8,
+    /// JSON 解析
+    Parse,
+    /// 事件结构校验
+    Validate,
+    /// stream_ts 超时
+    Stale,
+    /// 扣除转账手续费后预期利润不足
+    Unprofitable,
+    /// 组装交易
+    Assemble,
+    /// 发送交易 / bundle
+    Submit,
+}
+
+/// 带失败阶段的错误，写入死信队列时用于生成结构化原因
+#[derive(Debug)]
+pub struct StageError {
+    /// 未经 stage() 标记的错误只携带 trace_id，阶段为空
+    pub stage: Option<FailureStage>,
+    pub trace_id: Option<String>,
+    pub stream_ts: Option<i64>,
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stage {
+            Some(stage) => write!(f, "[{:?}] {:#}", stage, self.error),
+            None => write!(f, "{:#}", self.error),
+        }
+    }
+}
+
+impl std::error::Error for StageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+impl StageError {
+    /// 解析出事件后补充 trace_id 与 stream_ts
+    pub fn attach_event(err: anyhow::Error, trace_id: &str, stream_ts: i64) -> anyhow::Error {
+        let mut stage_error = match err.downcast::<StageError>() {
+            Ok(stage_error) => stage_error,
+            Err(err) => StageError {
+                stage: None,
+                trace_id: None,
+                stream_ts: None,
+                error: err,
+            },
+        };
+        stage_error.trace_id = Some(trace_id.to_string());
+        stage_error.stream_ts = Some(stream_ts);
+        stage_error.into()
+    }
+}
+
+pub trait StageContext<T> {
+    /// 标记错误发生的阶段，已标记过的错误保持不变
+    fn stage(self, stage: FailureStage) -> Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> StageContext<T> for std::result::Result<T, E> {
+    fn stage(self, stage: FailureStage) -> Result<T> {
+        self.map_err(|e| {
+            let err: anyhow::Error = e.into();
+            if err.is::<StageError>() {
+                err
+            } else {
+                StageError {
+                    stage: Some(stage),
+                    trace_id: None,
+                    stream_ts: None,
+                    error: err,
+                }
+                .into()
+            }
+        })
+    }
+}
+
+/// 死信队列中的一条记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    pub trace_id: Option<String>,
+    pub stage: Option<FailureStage>,
+    pub reason: String,
+    /// 消息来源的队列 / stream
+    pub source: String,
+    /// 原始消息体，重新入队时原样写回
+    pub payload: String,
+    pub received_ts: i64,
+    pub stream_ts: Option<i64>,
+    pub failed_ts: i64,
+}
+
+impl DeadLetter {
+    pub fn from_error(
+        source: &str,
+        payload: String,
+        received_ts: i64,
+        err: &anyhow::Error,
+    ) -> Self {
+        let failed_ts = now_ms();
+        match err.downcast_ref::<StageError>() {
+            Some(stage_error) => DeadLetter {
+                trace_id: stage_error.trace_id.clone(),
+                stage: stage_error.stage,
+                reason: format!("{:#}", stage_error.error),
+                source: source.to_string(),
+                payload,
+                received_ts,
+                stream_ts: stage_error.stream_ts,
+                failed_ts,
+            },
+            None => DeadLetter {
+                trace_id: None,
+                stage: None,
+                reason: format!("{:#}", err),
+                source: source.to_string(),
+                payload,
+                received_ts,
+                stream_ts: None,
+                failed_ts,
+            },
+        }
+    }
+}
+
+/// 基于 Redis list 的死信队列，超过 DEAD_LETTER_MAX_LEN 时丢弃最旧的记录
+#[derive(Clone)]
+pub struct DeadLetterQueue {
+    conn: ConnectionManager,
+    pub key: String,
+    max_len: isize,
+}
+
+impl DeadLetterQueue {
+    /// 未设置 DEAD_LETTER_QUEUE 时不启用
+    pub async fn from_env() -> Result<Option<Self>> {
+        let key = match std::env::var("DEAD_LETTER_QUEUE") {
+            Ok(key) if !key.is_empty() => key,
+            _ => return Ok(None),
+        };
+        let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "127.0.0.1:6379".to_string());
+        let conn = get_or_init_redis(redis_url, 0).await?;
+        let max_len = std::env::var("DEAD_LETTER_MAX_LEN")
+            .ok()
+            .and_then(|v| v.parse::<isize>().ok())
+            .unwrap_or(100_000);
+        info!("Dead letter queue enabled: {}", key);
+        Ok(Some(DeadLetterQueue { conn, key, max_len }))
+    }
+
+    pub async fn push(&self, letter: &DeadLetter) -> Result<()> {
+        let mut conn = self.conn.clone();
+        let _: i64 = conn
+            .rpush(&self.key, serde_json::to_string(letter)?)
+            .await?;
+        let _: () = conn.ltrim(&self.key, -self.max_len, -1).await?;
+        Ok(())
+    }
+
+    /// 写入失败只记录日志，不影响主流程
+    pub async fn push_or_log(&self, letter: &DeadLetter) {
+        if let Err(e) = self.push(letter).await {
+            error!(
+                "push dead letter trace_id: {:?} to {} failed: {:?}",
+                letter.trace_id, self.key, e
+            );
+        }
+    }
+
+    pub async fn list(&self, count: isize) -> Result<Vec<DeadLetter>> {
+        let mut conn = self.conn.clone();
+        let values: Vec<String> = conn.lrange(&self.key, 0, count - 1).await?;
+        Ok(values
+            .iter()
+            .filter_map(|value| serde_json::from_str(value).ok())
+            .collect())
+    }
+
+    /// 从队首依次取 count 条记录，将原始消息写回 target (默认为原来源)，
+    /// 按目标队列的 ingest_mode 选择 XADD 或 RPUSH。先写回再从死信队列删除，
+    /// 写回失败时记录保留在队首。refresh_ts 为 true 时将消息的 streamTs 改为当前时间。
+    /// 无法写回的记录 (消息无法解码、目标不是 list / stream) 移到队尾，不阻塞后面的记录
+    pub async fn requeue(
+        &self,
+        count: usize,
+        target: Option<&str>,
+        policies: &[QueuePolicy],
+        refresh_ts: bool,
+    ) -> Result<RequeueSummary> {
+        let mut conn = self.conn.clone();
+        let default_mode = std::env::var("INGEST_MODE").unwrap_or_else(|_| "list".to_string());
+        let field = std::env::var("REDIS_STREAM_FIELD").unwrap_or_else(|_| "data".to_string());
+        let mut summary = RequeueSummary::default();
+
+        // 移到队尾的记录不在本次重复处理
+        let len: usize = conn.llen(&self.key).await?;
+        for _ in 0..count.min(len) {
+            let value: Option<String> = conn.lindex(&self.key, 0).await?;
+            let Some(value) = value else {
+                break;
+            };
+            let letter: DeadLetter = match serde_json::from_str(&value) {
+                Ok(letter) => letter,
+                Err(e) => {
+                    warn!("drop malformed dead letter: {:?}", e);
+                    let _: i64 = conn.lrem(&self.key, 1, &value).await?;
+                    continue;
+                }
+            };
+            let target = target.unwrap_or(&letter.source);
+            let ingest_mode = policies
+                .iter()
+                .find(|policy| policy.name == target)
+                .map(|policy| policy.ingest_mode.as_str())
+                .unwrap_or(default_mode.as_str());
+            let payload = match requeue_payload(&letter, ingest_mode, refresh_ts, now_ms()) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!(
+                        "cannot requeue trace_id: {:?} to {}, moved to the tail of {}: {:#}",
+                        letter.trace_id, target, self.key, e
+                    );
+                    let _: Option<String> = conn
+                        .lmove(&self.key, &self.key, Direction::Left, Direction::Right)
+                        .await?;
+                    summary.failed += 1;
+                    continue;
+                }
+            };
+            if ingest_mode == "stream" {
+                let _: String = conn
+                    .xadd(target, "*", &[(field.as_str(), payload.as_str())])
+                    .await?;
+            } else {
+                let _: i64 = conn.rpush(target, &payload).await?;
+            }
+            let _: i64 = conn.lrem(&self.key, 1, &value).await?;
+            info!(
+                "requeued trace_id: {:?} ({:?}) to {} ({})",
+                letter.trace_id, letter.stage, target, ingest_mode
+            );
+            summary.requeued += 1;
+        }
+        Ok(summary)
+    }
+}
+
+/// requeue 的结果
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RequeueSummary {
+    pub requeued: usize,
+    /// 无法写回、已移到死信队列队尾的记录数
+    pub failed: usize,
+}
+
+/// 写回的消息体，只能写回 list 或 stream
+fn requeue_payload(
+    letter: &DeadLetter,
+    ingest_mode: &str,
+    refresh_ts: bool,
+    now_ts: i64,
+) -> Result<String> {
+    if ingest_mode != "list" && ingest_mode != "stream" {
+        return Err(anyhow::anyhow!(
+            "ingest_mode {} is not a redis queue, specify a list or stream target_queue",
+            ingest_mode
+        ));
+    }
+    if refresh_ts {
+        refresh_stream_ts(&letter.payload, now_ts)
+    } else {
+        Ok(letter.payload.clone())
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// 改写消息中的 streamTs，保持原有的编码方式 (明文 JSON / 帧格式及压缩算法)
+fn refresh_stream_ts(payload: &str, stream_ts: i64) -> Result<String> {
+    if payload.trim_start().starts_with('{') {
+        let data = refresh_event_ts(payload.as_bytes(), stream_ts)?;
+        return Ok(String::from_utf8(data)?);
+    }
+    let frame = base64::engine::general_purpose::STANDARD.decode(payload.trim())?;
+    let codec = frame_codec(&frame)?;
+    let data = decode_frame(&frame, max_decompressed_size())?;
+    encode_payload(codec, &refresh_event_ts(&data, stream_ts)?)
+}
+
+fn refresh_event_ts(data: &[u8], stream_ts: i64) -> Result<Vec<u8>> {
+    if data.starts_with(BINARY_MAGIC) {
+        let mut arbi_event = decode_arbi_event(data)?;
+        arbi_event.stream_ts = stream_ts;
+        return encode_arbi_event(&arbi_event);
+    }
+    let mut value: serde_json::Value = serde_json::from_slice(data)?;
+    let event = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("event is not a JSON object"))?;
+    event.insert("streamTs".to_string(), stream_ts.into());
+    Ok(serde_json::to_vec(&value)?)
+}
+
+const DLQ_USAGE: &str =
+    "usage: dlq list [count] | dlq requeue [count] [target_queue] [--refresh-ts]";
+
+/// 死信队列工具: dlq list [count] | dlq requeue [count] [target_queue] [--refresh-ts]
+pub async fn run_dlq_command(args: &[String]) -> Result<()> {
+    let refresh_ts = args.iter().any(|arg| arg == "--refresh-ts");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--refresh-ts").collect();
+    let dead_letter = DeadLetterQueue::from_env()
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("DEAD_LETTER_QUEUE is not set"))?;
+    let count = args
+        .get(1)
+        .map(|v| v.parse::<usize>())
+        .transpose()?
+        .unwrap_or(10);
+
+    match args.first().map(|arg| arg.as_str()) {
+        Some("list") => {
+            for letter in dead_letter.list(count as isize).await? {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "traceId": letter.trace_id,
+                        "stage": letter.stage,
+                        "reason": letter.reason,
+                        "source": letter.source,
+                        "receivedTs": letter.received_ts,
+                        "streamTs": letter.stream_ts,
+                        "failedTs": letter.failed_ts,
+                    })
+                );
+            }
+        }
+        Some("requeue") => {
+            let policies = load_queue_policies()?;
+            let summary = dead_letter
+                .requeue(
+                    count,
+                    args.get(2).map(|arg| arg.as_str()),
+                    &policies,
+                    refresh_ts,
+                )
+                .await?;
+            println!(
+                "requeued {} messages from {}, {} failed and moved to its tail",
+                summary.requeued, dead_letter.key, summary.failed
+            );
+        }
+        _ => return Err(anyhow::anyhow!(DLQ_USAGE)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::submiter::codec::{decode_payload, Codec};
+    use crate::submiter::route::tests::legacy_json;
+    use crate::submiter::wire::parse_json_event;
+
+    #[test]
+    fn refresh_plain_json_stream_ts() {
+        let payload = legacy_json().to_string();
+        let refreshed = refresh_stream_ts(&payload, 42).unwrap();
+        assert!(refreshed.starts_with('{'));
+        let value: serde_json::Value = serde_json::from_str(&refreshed).unwrap();
+        assert_eq!(value["streamTs"], 42);
+    }
+
+    #[test]
+    fn refresh_keeps_frame_codec() {
+        let event = parse_json_event(&legacy_json().to_string()).unwrap();
+        let payload = encode_payload(Codec::Zstd, &encode_arbi_event(&event).unwrap()).unwrap();
+        let refreshed = refresh_stream_ts(&payload, 42).unwrap();
+        let frame = base64::engine::general_purpose::STANDARD
+            .decode(&refreshed)
+            .unwrap();
+        assert_eq!(frame_codec(&frame).unwrap(), Codec::Zstd);
+        let decoded = decode_arbi_event(&decode_payload(&refreshed).unwrap()).unwrap();
+        assert_eq!(decoded.stream_ts, 42);
+        assert_eq!(decoded.trace_id, event.trace_id);
+    }
+
+    fn letter(payload: &str) -> DeadLetter {
+        DeadLetter {
+            trace_id: Some("trace".to_string()),
+            stage: Some(FailureStage::Decode),
+            reason: "bad payload".to_string(),
+            source: "queue".to_string(),
+            payload: payload.to_string(),
+            received_ts: 0,
+            stream_ts: None,
+            failed_ts: 0,
+        }
+    }
+
+    #[test]
+    fn undecodable_payload_fails_only_when_refreshing() {
+        let letter = letter("not base64 !!");
+        assert!(requeue_payload(&letter, "list", true, 42).is_err());
+        assert_eq!(
+            requeue_payload(&letter, "stream", false, 42).unwrap(),
+            letter.payload
+        );
+    }
+
+    #[test]
+    fn requeue_needs_a_redis_target() {
+        let payload = legacy_json().to_string();
+        assert!(requeue_payload(&letter(&payload), "unix", false, 42).is_err());
+        let refreshed = requeue_payload(&letter(&payload), "list", true, 42).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&refreshed).unwrap();
+        assert_eq!(value["streamTs"], 42);
+    }
+
+    #[test]
+    fn unstaged_error_keeps_no_stage() {
+        let err = StageError::attach_event(anyhow::anyhow!("boom"), "trace", 7);
+        let letter = DeadLetter::from_error("queue", String::new(), 0, &err);
+        assert_eq!(letter.stage, None);
+        assert_eq!(letter.trace_id.as_deref(), Some("trace"));
+        assert_eq!(letter.stream_ts, Some(7));
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use log::{info, warn};
+use redis::aio::ConnectionManager;
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::submiter::submitter::get_or_init_redis;
+
+/// 本地保留的指纹数量超过该值时清理过期记录
+const LOCAL_PRUNE_LEN: usize = 4096;
+
+/// 路由在去重窗口内的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// 窗口内首次出现，由本进程占用该路由
+    First,
+    /// 本进程在窗口内已收到过该路由，只能替换仍在排队的同一路由中利润更低的事件
+    Duplicate,
+    /// 其他实例已占用该路由
+    Claimed,
+}
+
+/// 路由去重: 同一路由指纹在 DEDUP_WINDOW_MS 内只提交一次。第一次出现的事件占用该路由，
+/// 窗口内的后续事件只能在第一个仍在排队时以更高的利润替换它 (见 PendingBuffer)。
+/// 进程内所有队列共用一个实例，设置 DEDUP_REDIS_PREFIX 时通过 Redis 在多个实例间共享占用
+pub struct RouteDedup {
+    window_ms: i64,
+    /// 指纹及本进程首次收到的时间
+    local: Mutex<HashMap<String, i64>>,
+    shared: Option<(ConnectionManager, String)>,
+}
+
+impl RouteDedup {
+    /// DEDUP_WINDOW_MS 默认 50，设置为 0 关闭
+    pub async fn from_env() -> Result<Option<Self>> {
+        let window_ms = std::env::var("DEDUP_WINDOW_MS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(50);
+        if window_ms <= 0 {
+            return Ok(None);
+        }
+
+        let shared = match std::env::var("DEDUP_REDIS_PREFIX") {
+            Ok(prefix) if !prefix.is_empty() => {
+                let redis_url =
+                    std::env::var("REDIS_URL").unwrap_or_else(|_| "127.0.0.1:6379".to_string());
+                Some((get_or_init_redis(redis_url, 0).await?, prefix))
+            }
+            _ => None,
+        };
+        info!(
+            "Route dedup window: {}ms, shared: {}",
+            window_ms,
+            shared.is_some()
+        );
+        Ok(Some(RouteDedup {
+            window_ms,
+            local: Mutex::new(HashMap::new()),
+            shared,
+        }))
+    }
+
+    /// 检查并占用该路由
+    pub async fn admit(&self, fingerprint: &str, now_ts: i64) -> Admission {
+        if !self.claim_local(fingerprint, now_ts) {
+            return Admission::Duplicate;
+        }
+        let Some((conn, prefix)) = &self.shared else {
+            return Admission::First;
+        };
+
+        let mut conn = conn.clone();
+        let claimed: redis::RedisResult<Option<String>> = redis::cmd("SET")
+            .arg(format!("{}:{}", prefix, fingerprint))
+            .arg(now_ts)
+            .arg("NX")
+            .arg("PX")
+            .arg(self.window_ms)
+            .query_async(&mut conn)
+            .await;
+        match claimed {
+            Ok(Some(_)) => Admission::First,
+            Ok(None) => Admission::Claimed,
+            // Redis 不可用时只依赖本地去重
+            Err(e) => {
+                warn!("dedup redis check failed: {:?}", e);
+                Admission::First
+            }
+        }
+    }
+
+    /// 窗口内首次出现时记录并返回 true
+    fn claim_local(&self, fingerprint: &str, now_ts: i64) -> bool {
+        let mut local = self.local.lock().unwrap();
+        if let Some(ts) = local.get(fingerprint) {
+            if now_ts - ts < self.window_ms {
+                return false;
+            }
+        }
+        if local.len() >= LOCAL_PRUNE_LEN {
+            local.retain(|_, ts| now_ts - *ts < self.window_ms);
+        }
+        local.insert(fingerprint.to_string(), now_ts);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dedup(window_ms: i64) -> RouteDedup {
+        RouteDedup {
+            window_ms,
+            local: Mutex::new(HashMap::new()),
+            shared: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn first_instance_claims_the_window() {
+        let dedup = dedup(50);
+        assert_eq!(dedup.admit("route", 1_000).await, Admission::First);
+        assert_eq!(dedup.admit("route", 1_049).await, Admission::Duplicate);
+        assert_eq!(dedup.admit("other", 1_049).await, Admission::First);
+        // 窗口从第一次出现开始计算，不因后续事件延长
+        assert_eq!(dedup.admit("route", 1_050).await, Admission::First);
+    }
+}
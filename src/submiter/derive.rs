@@ -0,0 +1,744 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::Result;
+use std::str::FromStr;
+
+use crate::submiter::assembler::{
+    MeteoraCpamAccounts, MeteoraDlmmAccounts, OrcaAccounts, PumpAccounts, RaydiumAmmAccounts,
+    RaydiumClmmAccounts, RaydiumCpmmAccounts,
+};
+use crate::submiter::dex::{DexAccount, DexType};
+
+pub const RAYDIUM_AMM_PROGRAM_ID: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+pub const WHIRLPOOL_PROGRAM_ID: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+pub const RAYDIUM_CLMM_PROGRAM_ID: &str = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
+pub const RAYDIUM_CPMM_PROGRAM_ID: &str = "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C";
+pub const METEORA_DLMM_PROGRAM_ID: &str = "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo";
+pub const METEORA_CPAM_PROGRAM_ID: &str = "cpamdpZCGKUy5JxQXB4dcpGPiikHawvSWAd6mEn1sGG";
+pub const PUMP_AMM_PROGRAM_ID: &str = "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA";
+
+/// 每个 tick / bin array 包含的 tick 或 bin 数
+const WHIRLPOOL_TICK_ARRAY_SIZE: i32 = 88;
+const RAYDIUM_CLMM_TICK_ARRAY_SIZE: i32 = 60;
+const METEORA_DLMM_BINS_PER_ARRAY: i32 = 70;
+/// 池子内置 bitmap 覆盖的 tick / bin array 数，超出时需要 bitmap extension
+const RAYDIUM_CLMM_BITMAP_ARRAYS: i32 = 512;
+const METEORA_DLMM_BITMAP_ARRAYS: i32 = 512;
+/// 按当前 tick / bin 推导的 array 数
+const DERIVED_TICK_ARRAYS: usize = 3;
+/// 跳过未初始化的 array 时沿价格变化方向最多查找的 array 数
+const ARRAY_CANDIDATES: i32 = 8;
+
+#[derive(Debug, Clone)]
+pub struct RaydiumAmmState {
+    pub open_orders: Pubkey,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub market: Pubkey,
+    pub market_program: Pubkey,
+    /// 以下取自 market 账户
+    pub market_bids: Pubkey,
+    pub market_asks: Pubkey,
+    pub market_event_queue: Pubkey,
+    pub market_coin_vault: Pubkey,
+    pub market_pc_vault: Pubkey,
+    pub market_vault_signer_nonce: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct WhirlpoolState {
+    pub token_mint_a: Pubkey,
+    pub token_vault_a: Pubkey,
+    pub token_vault_b: Pubkey,
+    pub tick_spacing: u16,
+    pub tick_current_index: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RaydiumClmmState {
+    pub amm_config: Pubkey,
+    pub token_mint_0: Pubkey,
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    pub observation_key: Pubkey,
+    pub tick_spacing: u16,
+    pub tick_current: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RaydiumCpmmState {
+    pub amm_config: Pubkey,
+    pub token_0_mint: Pubkey,
+    pub token_0_vault: Pubkey,
+    pub token_1_vault: Pubkey,
+    pub observation_key: Pubkey,
+}
+
+#[derive(Debug, Clone)]
+pub struct MeteoraDlmmState {
+    pub token_x_mint: Pubkey,
+    pub reserve_x: Pubkey,
+    pub reserve_y: Pubkey,
+    pub oracle: Pubkey,
+    pub active_id: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct MeteoraCpamState {
+    pub token_a_vault: Pubkey,
+    pub token_b_vault: Pubkey,
+}
+
+#[derive(Debug, Clone)]
+pub struct PumpState {
+    pub quote_mint: Pubkey,
+    pub quote_token_program: Pubkey,
+    pub pool_base_token_account: Pubkey,
+    pub pool_quote_token_account: Pubkey,
+    pub coin_creator: Pubkey,
+    /// 取自 global config
+    pub protocol_fee_recipient: Pubkey,
+}
+
+/// 推导账户所需的池子状态
+#[derive(Debug, Clone)]
+pub enum PoolState {
+    RaydiumAmm(RaydiumAmmState),
+    Whirlpool(WhirlpoolState),
+    RaydiumClmm(RaydiumClmmState),
+    RaydiumCpmm(RaydiumCpmmState),
+    MeteoraDlmm(MeteoraDlmmState),
+    MeteoraCpam(MeteoraCpamState),
+    Pump(PumpState),
+}
+
+impl PoolState {
+    pub fn dex_type(&self) -> DexType {
+        match self {
+            PoolState::RaydiumAmm(_) => DexType::RaydiumAmm,
+            PoolState::Whirlpool(_) => DexType::Orca,
+            PoolState::RaydiumClmm(_) => DexType::RaydiumClmm,
+            PoolState::RaydiumCpmm(_) => DexType::RaydiumCpmm,
+            PoolState::MeteoraDlmm(_) => DexType::MeteoraDlmm,
+            PoolState::MeteoraCpam(_) => DexType::MeteoraCpam,
+            PoolState::Pump(_) => DexType::Pump,
+        }
+    }
+}
+
+/// 池子状态及其所在 slot
+#[derive(Debug, Clone)]
+pub struct PoolSnapshot {
+    pub state: PoolState,
+    pub slot: u64,
+}
+
+/// 按池子地址查询当前池子状态，没有状态或状态已过期时为 None
+pub trait PoolStateSource {
+    fn pool_state(&self, pool: &Pubkey) -> Option<PoolSnapshot>;
+
+    /// tick / bin array 是否已初始化，未知时为 None
+    fn account_initialized(&self, _account: &Pubkey) -> Option<bool> {
+        None
+    }
+}
+
+fn program_id(value: &str) -> Pubkey {
+    Pubkey::from_str(value).unwrap()
+}
+
+fn pda(seeds: &[&[u8]], program: &str) -> Pubkey {
+    Pubkey::find_program_address(seeds, &program_id(program)).0
+}
+
+/// anchor `#[event_cpi]` 使用的 event authority
+fn event_authority(program: &str) -> Pubkey {
+    pda(&[b"__event_authority"], program)
+}
+
+/// pump AMM 的 global config，协议费接收账户取自其中
+pub fn pump_global_config() -> Pubkey {
+    pda(&[b"global_config"], PUMP_AMM_PROGRAM_ID)
+}
+
+fn whirlpool_tick_array(pool: &Pubkey, start: i32) -> Pubkey {
+    pda(
+        &[b"tick_array", pool.as_ref(), start.to_string().as_bytes()],
+        WHIRLPOOL_PROGRAM_ID,
+    )
+}
+
+fn raydium_clmm_tick_array(pool: &Pubkey, start: i32) -> Pubkey {
+    pda(
+        &[b"tick_array", pool.as_ref(), &start.to_be_bytes()],
+        RAYDIUM_CLMM_PROGRAM_ID,
+    )
+}
+
+fn meteora_dlmm_bin_array(pool: &Pubkey, index: i32) -> Pubkey {
+    pda(
+        &[b"bin_array", pool.as_ref(), &(index as i64).to_le_bytes()],
+        METEORA_DLMM_PROGRAM_ID,
+    )
+}
+
+/// tick 所在 array 的起始下标，向负无穷取整
+fn array_start(tick: i32, ticks_per_array: i32) -> i32 {
+    tick.div_euclid(ticks_per_array) * ticks_per_array
+}
+
+/// 从起始 array 开始沿价格变化方向的候选 array，降价方向 (a -> b) 下标递减
+fn array_starts(first: i32, ticks_per_array: i32, descending: bool) -> Vec<i32> {
+    let step = if descending {
+        -ticks_per_array
+    } else {
+        ticks_per_array
+    };
+    (0..ARRAY_CANDIDATES).map(|i| first + i * step).collect()
+}
+
+/// 按顺序选出最多 DERIVED_TICK_ARRAYS 个已初始化的 array，状态未知的视为已初始化。
+/// contiguous 为 true 时 (whirlpool 要求 tick array 连续) 遇到未初始化的 array 即停止，
+/// 否则跳过 (CLMM / DLMM 按 bitmap 跳过没有流动性的 array)
+fn initialized_arrays(
+    candidates: Vec<(i32, Pubkey)>,
+    contiguous: bool,
+    pool_states: &dyn PoolStateSource,
+    pool: &Pubkey,
+) -> Result<Vec<(i32, Pubkey)>> {
+    let mut selected = Vec::with_capacity(DERIVED_TICK_ARRAYS);
+    for (start, address) in candidates {
+        if pool_states.account_initialized(&address) == Some(false) {
+            if contiguous {
+                break;
+            }
+            continue;
+        }
+        selected.push((start, address));
+        if selected.len() == DERIVED_TICK_ARRAYS {
+            break;
+        }
+    }
+    if selected.is_empty() {
+        return Err(anyhow::anyhow!(
+            "pool {}: no initialized tick / bin array near the current price",
+            pool
+        ));
+    }
+    Ok(selected)
+}
+
+fn derive_raydium_amm(pool: &Pubkey, state: &RaydiumAmmState) -> Result<DexAccount> {
+    let vault_signer = Pubkey::create_program_address(
+        &[
+            state.market.as_ref(),
+            &state.market_vault_signer_nonce.to_le_bytes(),
+        ],
+        &state.market_program,
+    )
+    .map_err(|e| anyhow::anyhow!("market {} vault signer: {}", state.market, e))?;
+    Ok(DexAccount::RaydiumAmm(RaydiumAmmAccounts {
+        raydium_amm: *pool,
+        raydium_amm_open_orders: state.open_orders,
+        raydium_amm_authority: pda(&[b"amm authority"], RAYDIUM_AMM_PROGRAM_ID),
+        raydium_amm_coin_vault: state.coin_vault,
+        raydium_amm_pc_vault: state.pc_vault,
+        raydium_amm_market: state.market,
+        raydium_amm_market_bids: state.market_bids,
+        raydium_amm_market_asks: state.market_asks,
+        raydium_amm_market_event_queue: state.market_event_queue,
+        raydium_amm_market_coin_vault: state.market_coin_vault,
+        raydium_amm_market_pc_vault: state.market_pc_vault,
+        raydium_amm_market_vault_signer: vault_signer,
+    }))
+}
+
+fn derive_whirlpool(
+    pool: &Pubkey,
+    state: &WhirlpoolState,
+    mint_a: &Pubkey,
+    a_to_b: bool,
+    pool_states: &dyn PoolStateSource,
+) -> Result<DexAccount> {
+    // whirlpool 的 a_to_b 以池子自身的 token a 为准
+    let pool_a_to_b = (state.token_mint_a == *mint_a) == a_to_b;
+    let ticks_per_array = state.tick_spacing as i32 * WHIRLPOOL_TICK_ARRAY_SIZE;
+    // 反向 swap 从当前 tick 的下一个 tick 开始
+    let shift = if pool_a_to_b {
+        0
+    } else {
+        state.tick_spacing as i32
+    };
+    let first = array_start(state.tick_current_index + shift, ticks_per_array);
+    let candidates: Vec<(i32, Pubkey)> = array_starts(first, ticks_per_array, pool_a_to_b)
+        .into_iter()
+        .map(|start| (start, whirlpool_tick_array(pool, start)))
+        .collect();
+    let tick_arrays = initialized_arrays(candidates, true, pool_states, pool)?
+        .into_iter()
+        .map(|(_, address)| address)
+        .collect();
+    let (a_to_b_tick_arrays, b_to_a_tick_arrays) = if a_to_b {
+        (tick_arrays, vec![])
+    } else {
+        (vec![], tick_arrays)
+    };
+    Ok(DexAccount::Orca(OrcaAccounts {
+        whirlpool: *pool,
+        whirlpool_token_vault_a: state.token_vault_a,
+        whirlpool_token_vault_b: state.token_vault_b,
+        whirlpool_a_to_b_tick_array_0: None,
+        whirlpool_a_to_b_tick_array_1: None,
+        whirlpool_a_to_b_tick_array_2: None,
+        whirlpool_b_to_a_tick_array_0: None,
+        whirlpool_b_to_a_tick_array_1: None,
+        whirlpool_b_to_a_tick_array_2: None,
+        whirlpool_a_to_b_tick_arrays: a_to_b_tick_arrays,
+        whirlpool_b_to_a_tick_arrays: b_to_a_tick_arrays,
+        whirlpool_oracle: pda(&[b"oracle", pool.as_ref()], WHIRLPOOL_PROGRAM_ID),
+    }))
+}
+
+fn derive_raydium_clmm(
+    pool: &Pubkey,
+    state: &RaydiumClmmState,
+    mint_a: &Pubkey,
+    a_to_b: bool,
+    pool_states: &dyn PoolStateSource,
+) -> Result<DexAccount> {
+    let zero_for_one = (state.token_mint_0 == *mint_a) == a_to_b;
+    let ticks_per_array = state.tick_spacing as i32 * RAYDIUM_CLMM_TICK_ARRAY_SIZE;
+    let candidates: Vec<(i32, Pubkey)> = array_starts(
+        array_start(state.tick_current, ticks_per_array),
+        ticks_per_array,
+        zero_for_one,
+    )
+    .into_iter()
+    .map(|start| (start, raydium_clmm_tick_array(pool, start)))
+    .collect();
+    let selected = initialized_arrays(candidates, false, pool_states, pool)?;
+    let bitmap_limit = ticks_per_array * RAYDIUM_CLMM_BITMAP_ARRAYS;
+    let bitmap_extension = selected
+        .iter()
+        .any(|(start, _)| *start < -bitmap_limit || *start >= bitmap_limit)
+        .then(|| {
+            pda(
+                &[b"pool_tick_array_bitmap_extension", pool.as_ref()],
+                RAYDIUM_CLMM_PROGRAM_ID,
+            )
+        });
+    let tick_arrays = selected.into_iter().map(|(_, address)| address).collect();
+    let (input_vault, output_vault) = if zero_for_one {
+        (state.token_vault_0, state.token_vault_1)
+    } else {
+        (state.token_vault_1, state.token_vault_0)
+    };
+    let (a_to_b_tick_arrays, b_to_a_tick_arrays) = if a_to_b {
+        (tick_arrays, vec![])
+    } else {
+        (vec![], tick_arrays)
+    };
+    Ok(DexAccount::RaydiumClmm(RaydiumClmmAccounts {
+        raydium_amm_config: state.amm_config,
+        raydium_pool_state: *pool,
+        raydium_input_vault: input_vault,
+        raydium_output_vault: output_vault,
+        raydium_observation_state: state.observation_key,
+        raydium_a_to_b_tick_array_0: None,
+        raydium_a_to_b_tick_array_1: None,
+        raydium_a_to_b_tick_array_2: None,
+        raydium_b_to_a_tick_array_0: None,
+        raydium_b_to_a_tick_array_1: None,
+        raydium_b_to_a_tick_array_2: None,
+        raydium_a_to_b_tick_arrays: a_to_b_tick_arrays,
+        raydium_b_to_a_tick_arrays: b_to_a_tick_arrays,
+        raydium_tick_array_bitmap_extension: bitmap_extension,
+    }))
+}
+
+fn derive_raydium_cpmm(
+    pool: &Pubkey,
+    state: &RaydiumCpmmState,
+    mint_a: &Pubkey,
+    a_to_b: bool,
+) -> DexAccount {
+    let zero_for_one = (state.token_0_mint == *mint_a) == a_to_b;
+    let (input_vault, output_vault) = if zero_for_one {
+        (state.token_0_vault, state.token_1_vault)
+    } else {
+        (state.token_1_vault, state.token_0_vault)
+    };
+    DexAccount::RaydiumCpmm(RaydiumCpmmAccounts {
+        authority: pda(&[b"vault_and_lp_mint_auth_seed"], RAYDIUM_CPMM_PROGRAM_ID),
+        amm_config: state.amm_config,
+        pool_state: *pool,
+        input_vault,
+        output_vault,
+        observation_state: state.observation_key,
+    })
+}
+
+fn derive_meteora_dlmm(
+    pool: &Pubkey,
+    state: &MeteoraDlmmState,
+    mint_a: &Pubkey,
+    a_to_b: bool,
+    pool_states: &dyn PoolStateSource,
+) -> Result<DexAccount> {
+    // x -> y 时 active bin 下降
+    let swap_for_y = (state.token_x_mint == *mint_a) == a_to_b;
+    let candidates: Vec<(i32, Pubkey)> = array_starts(
+        state.active_id.div_euclid(METEORA_DLMM_BINS_PER_ARRAY),
+        1,
+        swap_for_y,
+    )
+    .into_iter()
+    .map(|index| (index, meteora_dlmm_bin_array(pool, index)))
+    .collect();
+    let selected = initialized_arrays(candidates, false, pool_states, pool)?;
+    let bitmap_extension = selected
+        .iter()
+        .any(|(index, _)| {
+            *index < -METEORA_DLMM_BITMAP_ARRAYS || *index >= METEORA_DLMM_BITMAP_ARRAYS
+        })
+        .then(|| pda(&[b"bitmap", pool.as_ref()], METEORA_DLMM_PROGRAM_ID));
+    let bin_arrays = selected.into_iter().map(|(_, address)| address).collect();
+    let (a_to_b_bin_arrays, b_to_a_bin_arrays) = if a_to_b {
+        (bin_arrays, vec![])
+    } else {
+        (vec![], bin_arrays)
+    };
+    Ok(DexAccount::MeteoraDlmm(MeteoraDlmmAccounts {
+        meteora_lb_pair: *pool,
+        meteora_reserve_x: state.reserve_x,
+        meteora_reserve_y: state.reserve_y,
+        meteora_oracle: state.oracle,
+        meteora_event_authority: event_authority(METEORA_DLMM_PROGRAM_ID),
+        meteora_a_to_b_tick_array_0: None,
+        meteora_a_to_b_tick_array_1: None,
+        meteora_a_to_b_tick_array_2: None,
+        meteora_b_to_a_tick_array_0: None,
+        meteora_b_to_a_tick_array_1: None,
+        meteora_b_to_a_tick_array_2: None,
+        meteora_a_to_b_bin_arrays: a_to_b_bin_arrays,
+        meteora_b_to_a_bin_arrays: b_to_a_bin_arrays,
+        meteora_bin_array_bitmap_extension: bitmap_extension,
+    }))
+}
+
+fn derive_meteora_cpam(pool: &Pubkey, state: &MeteoraCpamState) -> DexAccount {
+    DexAccount::MeteoraCpam(MeteoraCpamAccounts {
+        meteora_cpam_pool_authority: pda(&[b"pool_authority"], METEORA_CPAM_PROGRAM_ID),
+        meteora_cpam_pool: *pool,
+        meteora_cpam_token_a_vault: state.token_a_vault,
+        meteora_cpam_token_b_vault: state.token_b_vault,
+        meteora_cpam_event_authority: event_authority(METEORA_CPAM_PROGRAM_ID),
+    })
+}
+
+fn derive_pump(pool: &Pubkey, state: &PumpState) -> DexAccount {
+    let coin_creator_vault_authority = pda(
+        &[b"creator_vault", state.coin_creator.as_ref()],
+        PUMP_AMM_PROGRAM_ID,
+    );
+    DexAccount::Pump(PumpAccounts {
+        pump_pool: *pool,
+        pump_global_config: pump_global_config(),
+        pump_pool_base_token_account: state.pool_base_token_account,
+        pump_pool_quote_token_account: state.pool_quote_token_account,
+        pump_protocol_fee_recipient: state.protocol_fee_recipient,
+        pump_protocol_fee_recipient_token_account:
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &state.protocol_fee_recipient,
+                &state.quote_mint,
+                &state.quote_token_program,
+            ),
+        pump_event_authority: event_authority(PUMP_AMM_PROGRAM_ID),
+        pump_coin_creator_vault_ata:
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &coin_creator_vault_authority,
+                &state.quote_mint,
+                &state.quote_token_program,
+            ),
+        pump_coin_creator_vault_authority: coin_creator_vault_authority,
+    })
+}
+
+/// 按池子状态推导 leg 的全部账户，a_to_b 为相对事件中 token A 的方向，
+/// tick / bin array 是否已初始化由 pool_states 给出
+pub fn derive_dex_account(
+    dex_type: DexType,
+    pool: &Pubkey,
+    state: &PoolState,
+    mint_a: &Pubkey,
+    a_to_b: bool,
+    pool_states: &dyn PoolStateSource,
+) -> Result<DexAccount> {
+    if state.dex_type() != dex_type {
+        return Err(anyhow::anyhow!(
+            "pool {} is {:?}, not {:?}",
+            pool,
+            state.dex_type(),
+            dex_type
+        ));
+    }
+    Ok(match state {
+        PoolState::RaydiumAmm(state) => derive_raydium_amm(pool, state)?,
+        PoolState::Whirlpool(state) => derive_whirlpool(pool, state, mint_a, a_to_b, pool_states)?,
+        PoolState::RaydiumClmm(state) => {
+            derive_raydium_clmm(pool, state, mint_a, a_to_b, pool_states)?
+        }
+        PoolState::RaydiumCpmm(state) => derive_raydium_cpmm(pool, state, mint_a, a_to_b),
+        PoolState::MeteoraDlmm(state) => {
+            derive_meteora_dlmm(pool, state, mint_a, a_to_b, pool_states)?
+        }
+        PoolState::MeteoraCpam(state) => derive_meteora_cpam(pool, state),
+        PoolState::Pump(state) => derive_pump(pool, state),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 以 Some(false) 回答列出的账户，其余视为已初始化
+    struct Uninitialized(Vec<Pubkey>);
+
+    impl PoolStateSource for Uninitialized {
+        fn pool_state(&self, _pool: &Pubkey) -> Option<PoolSnapshot> {
+            None
+        }
+
+        fn account_initialized(&self, account: &Pubkey) -> Option<bool> {
+            Some(!self.0.contains(account))
+        }
+    }
+
+    fn key(value: &str) -> Pubkey {
+        Pubkey::from_str(value).unwrap()
+    }
+
+    fn keys(values: &[&str]) -> Vec<Pubkey> {
+        values.iter().map(|value| key(value)).collect()
+    }
+
+    // 主网 SOL/USDC 池子，tick / bin array 的期望地址由独立实现按 seeds 计算
+    const WHIRLPOOL: &str = "Czfq3xZZDmsdGdUyrNLtRhGc47cXcZtLG4crryfu44zE";
+    const CLMM_POOL: &str = "2QdhepnKRTLjjSqPL1PtKNwqrUkoLee5Gqs8bvZhRdMv";
+    const DLMM_LB_PAIR: &str = "5rCf1DM8LjKTw4YqhnoLcngyZYeNnQqztScTogYHAS6";
+
+    #[test]
+    fn array_start_floors_negative_ticks() {
+        assert_eq!(array_start(0, 352), 0);
+        assert_eq!(array_start(351, 352), 0);
+        assert_eq!(array_start(-1, 352), -352);
+        assert_eq!(array_start(-352, 352), -352);
+        assert_eq!(array_start(-353, 352), -704);
+    }
+
+    #[test]
+    fn fixed_pdas_match_mainnet() {
+        assert_eq!(
+            pda(&[b"amm authority"], RAYDIUM_AMM_PROGRAM_ID),
+            key("5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1")
+        );
+        assert_eq!(
+            pda(&[b"vault_and_lp_mint_auth_seed"], RAYDIUM_CPMM_PROGRAM_ID),
+            key("GpMZbSM2GgvTKHJirzeGfMFoaZ8UR2X7F4v8vHTvxFbL")
+        );
+        assert_eq!(
+            event_authority(METEORA_DLMM_PROGRAM_ID),
+            key("D1ZN9Wj1fRSUQfCjhvnu1hqDMT7hzjzBBpi12nVniYD6")
+        );
+        assert_eq!(
+            pda(&[b"pool_authority"], METEORA_CPAM_PROGRAM_ID),
+            key("HLnpSz9h2S4hiLQ43rnSD9XkcUThA7B8hQMKmDaiTLcC")
+        );
+        assert_eq!(
+            event_authority(METEORA_CPAM_PROGRAM_ID),
+            key("3rmHSu74h1ZcmAisVcWerTCiRDQbUrBKmcwptYGjHfet")
+        );
+        assert_eq!(
+            pump_global_config(),
+            key("ADyA8hdefvWN2dbGGWFotbzWxrAvLW83WG6QCVXvJKqw")
+        );
+        assert_eq!(
+            event_authority(PUMP_AMM_PROGRAM_ID),
+            key("GS4CU59F31iL7aR2Q8zVS8DRrcRnXX1yjQ66TqNVQnaR")
+        );
+    }
+
+    fn whirlpool_state(mint_a: Pubkey) -> WhirlpoolState {
+        WhirlpoolState {
+            token_mint_a: mint_a,
+            token_vault_a: Pubkey::new_unique(),
+            token_vault_b: Pubkey::new_unique(),
+            tick_spacing: 64,
+            tick_current_index: -1,
+        }
+    }
+
+    #[test]
+    fn whirlpool_tick_arrays_from_negative_tick() {
+        let pool = key(WHIRLPOOL);
+        let mint_a = Pubkey::new_unique();
+        let state = whirlpool_state(mint_a);
+        let source = Uninitialized(vec![]);
+
+        let DexAccount::Orca(a_to_b) =
+            derive_whirlpool(&pool, &state, &mint_a, true, &source).unwrap()
+        else {
+            panic!("not an orca leg");
+        };
+        // tick -1 落在 [-5632, 0) 中，a -> b 时下标递减
+        assert_eq!(
+            a_to_b.whirlpool_a_to_b_tick_arrays,
+            keys(&[
+                "9P4UnkhBE8qyaPaD4vJcqwgHK5RtVS9fsEdgQaZtoRvk",
+                "GWSr2HsuLZ9wXruSj1UhdAyQAz2VnPmM3pJ6UQfswqJs",
+                "BtCDvZXqLLJyzh8bJWYW4hLPdJZi7cQWJ1CWwU9sA2G6",
+            ])
+        );
+        assert_eq!(
+            a_to_b.whirlpool_oracle,
+            key("FoKYKtRpD25TKzBMndysKpgPqbj8AdLXjfpYHXn9PGTX")
+        );
+
+        let DexAccount::Orca(b_to_a) =
+            derive_whirlpool(&pool, &state, &mint_a, false, &source).unwrap()
+        else {
+            panic!("not an orca leg");
+        };
+        // b -> a 从下一个 tick (63) 开始
+        assert_eq!(
+            b_to_a.whirlpool_b_to_a_tick_arrays,
+            keys(&[
+                "EP2GupuiKh6bHLXD6Uv6pj2vT7t34fVvfefgALNLNQjt",
+                "3oukmHA8qeB5zBVm5Vb3iNtBnYMpBYYZ3KodkU53pWAp",
+                "HPVNQYRENp2KiG2DNaMWsMNndd9EWqMVUAkjpjo6VAaB",
+            ])
+        );
+    }
+
+    #[test]
+    fn whirlpool_stops_at_uninitialized_tick_array() {
+        let pool = key(WHIRLPOOL);
+        let mint_a = Pubkey::new_unique();
+        let source = Uninitialized(keys(&["GWSr2HsuLZ9wXruSj1UhdAyQAz2VnPmM3pJ6UQfswqJs"]));
+
+        let DexAccount::Orca(accounts) =
+            derive_whirlpool(&pool, &whirlpool_state(mint_a), &mint_a, true, &source).unwrap()
+        else {
+            panic!("not an orca leg");
+        };
+        assert_eq!(
+            accounts.whirlpool_a_to_b_tick_arrays,
+            keys(&["9P4UnkhBE8qyaPaD4vJcqwgHK5RtVS9fsEdgQaZtoRvk"])
+        );
+    }
+
+    fn clmm_state(mint_0: Pubkey) -> RaydiumClmmState {
+        RaydiumClmmState {
+            amm_config: Pubkey::new_unique(),
+            token_mint_0: mint_0,
+            token_vault_0: Pubkey::new_unique(),
+            token_vault_1: Pubkey::new_unique(),
+            observation_key: Pubkey::new_unique(),
+            tick_spacing: 1,
+            tick_current: -1,
+        }
+    }
+
+    #[test]
+    fn raydium_clmm_skips_uninitialized_tick_arrays() {
+        let pool = key(CLMM_POOL);
+        let mint_a = Pubkey::new_unique();
+        let state = clmm_state(mint_a);
+
+        let DexAccount::RaydiumClmm(accounts) =
+            derive_raydium_clmm(&pool, &state, &mint_a, true, &Uninitialized(vec![])).unwrap()
+        else {
+            panic!("not a clmm leg");
+        };
+        assert_eq!(
+            accounts.raydium_a_to_b_tick_arrays,
+            keys(&[
+                "4aY5KbBV43kCL7eg6ikxExBNoAgahS6CTnXM9TDKMnX8",
+                "5dhEugGRqzr7giAGpRQmHPjFaQGu9zhPEZF861mDpBVt",
+                "DoFM2s1Za5UjLUpHoNj73KKdxFcra5Y46x6WyWtopVKq",
+            ])
+        );
+        assert_eq!(accounts.raydium_input_vault, state.token_vault_0);
+        assert_eq!(accounts.raydium_tick_array_bitmap_extension, None);
+
+        // start -120 未初始化时跳过，继续取 -180、-240
+        let source = Uninitialized(keys(&["5dhEugGRqzr7giAGpRQmHPjFaQGu9zhPEZF861mDpBVt"]));
+        let DexAccount::RaydiumClmm(accounts) =
+            derive_raydium_clmm(&pool, &state, &mint_a, true, &source).unwrap()
+        else {
+            panic!("not a clmm leg");
+        };
+        assert_eq!(
+            accounts.raydium_a_to_b_tick_arrays,
+            keys(&[
+                "4aY5KbBV43kCL7eg6ikxExBNoAgahS6CTnXM9TDKMnX8",
+                "DoFM2s1Za5UjLUpHoNj73KKdxFcra5Y46x6WyWtopVKq",
+                "2xMnrfZ1z3qsv6NwXrWopQdrJD37b7kYXdkNSrAMAJnC",
+            ])
+        );
+    }
+
+    #[test]
+    fn raydium_clmm_bitmap_extension_pda() {
+        let pool = key(CLMM_POOL);
+        let mint_a = Pubkey::new_unique();
+        let state = RaydiumClmmState {
+            // 超出内置 bitmap 覆盖的 [-30720, 30720)
+            tick_current: -30721,
+            ..clmm_state(mint_a)
+        };
+        let DexAccount::RaydiumClmm(accounts) =
+            derive_raydium_clmm(&pool, &state, &mint_a, true, &Uninitialized(vec![])).unwrap()
+        else {
+            panic!("not a clmm leg");
+        };
+        assert_eq!(
+            accounts.raydium_tick_array_bitmap_extension,
+            Some(key("9z9VTNvaTpJuwjn4LSnjHwZgUR9iGuy59BwXTNbxRF6s"))
+        );
+    }
+
+    #[test]
+    fn meteora_dlmm_bin_arrays_from_negative_active_id() {
+        let pool = key(DLMM_LB_PAIR);
+        let mint_a = Pubkey::new_unique();
+        let state = MeteoraDlmmState {
+            token_x_mint: mint_a,
+            reserve_x: Pubkey::new_unique(),
+            reserve_y: Pubkey::new_unique(),
+            oracle: Pubkey::new_unique(),
+            active_id: -1,
+        };
+        let DexAccount::MeteoraDlmm(accounts) =
+            derive_meteora_dlmm(&pool, &state, &mint_a, true, &Uninitialized(vec![])).unwrap()
+        else {
+            panic!("not a dlmm leg");
+        };
+        assert_eq!(
+            accounts.meteora_a_to_b_bin_arrays,
+            keys(&[
+                "FaEgvDgeDxdKFDrZnDt7W6qzJTSLQrg4orMLCG35GFxz",
+                "QWdcZWz7HDgus8shdajptxF9PuLXKWUrN6B6jpcnRWP",
+                "J7TfwnK2sXjUG3tecmDkckwvEkfxMMho5RtjPEWTi6RH",
+            ])
+        );
+        assert_eq!(accounts.meteora_bin_array_bitmap_extension, None);
+    }
+
+    #[test]
+    fn no_initialized_array_is_an_error() {
+        let pool = key(WHIRLPOOL);
+        let mint_a = Pubkey::new_unique();
+        let source = Uninitialized(keys(&["9P4UnkhBE8qyaPaD4vJcqwgHK5RtVS9fsEdgQaZtoRvk"]));
+        assert!(derive_whirlpool(&pool, &whirlpool_state(mint_a), &mint_a, true, &source).is_err());
+    }
+}
@@ -0,0 +1,419 @@
+use anchor_client::solana_sdk::{instruction::AccountMeta, pubkey::Pubkey};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+use crate::submiter::assembler::{
+    JupPerpAccounts, LifinityAccounts, MeteoraAmmAccounts, MeteoraCpamAccounts,
+    MeteoraDlmmAccounts, ObricAccounts, OpenbookAccounts, OrcaAccounts, PhoenixAccounts,
+    PumpAccounts, RaydiumAmmAccounts, RaydiumClmmAccounts, RaydiumCpmmAccounts, SolfiAccounts,
+};
+
+/// remaining_accounts 开头的程序槽位数，每个 DEX 的程序固定在 DexProfile::program_slots 指定的位置
+pub const PROGRAM_SLOTS: usize = 16;
+
+/// Arbi 中按需传入的可选账户
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionalAccount {
+    MemoProgram,
+    TokenProgram2022,
+    InstructionsSysvar,
+}
+
+impl OptionalAccount {
+    pub fn pubkey(&self) -> Pubkey {
+        match self {
+            OptionalAccount::MemoProgram => {
+                Pubkey::from_str("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr").unwrap()
+            }
+            OptionalAccount::TokenProgram2022 => spl_token_2022::ID,
+            OptionalAccount::InstructionsSysvar => {
+                Pubkey::from_str("Sysvar1nstructions1111111111111111111111111").unwrap()
+            }
+        }
+    }
+}
+
+/// 生成 leg 账户失败的原因，field 为出错的字段名
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountMetaError {
+    MissingAccount {
+        field: &'static str,
+    },
+    InvalidPubkey {
+        field: &'static str,
+        value: String,
+    },
+    /// 账户数量超过链上程序每个 leg 接受的上限
+    TooManyAccounts {
+        field: &'static str,
+        count: usize,
+        max: usize,
+    },
+    /// 链上程序目前的账户布局无法传入该账户
+    UnsupportedAccount {
+        field: &'static str,
+    },
+}
+
+impl fmt::Display for AccountMetaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountMetaError::MissingAccount { field } => write!(f, "{}: missing", field),
+            AccountMetaError::InvalidPubkey { field, value } => {
+                write!(f, "{}: invalid pubkey {:?}", field, value)
+            }
+            AccountMetaError::TooManyAccounts { field, count, max } => {
+                write!(
+                    f,
+                    "{}: {} accounts, at most {} supported",
+                    field, count, max
+                )
+            }
+            AccountMetaError::UnsupportedAccount { field } => {
+                write!(f, "{}: not supported by the arbitrage program", field)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AccountMetaError {}
+
+/// 带 leg 下标与 DEX 的账户错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegAccountError {
+    pub index: usize,
+    pub dex: DexType,
+    pub error: AccountMetaError,
+}
+
+impl fmt::Display for LegAccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "route.legs[{}] ({:?}).{}",
+            self.index, self.dex, self.error
+        )
+    }
+}
+
+impl std::error::Error for LegAccountError {}
+
+pub fn parse_pubkey(value: &str, field: &'static str) -> Result<Pubkey, AccountMetaError> {
+    Pubkey::from_str(value).map_err(|_| AccountMetaError::InvalidPubkey {
+        field,
+        value: value.to_string(),
+    })
+}
+
+/// 链上程序每个 leg 目前最多接受的 tick / bin array 数量
+pub const MAX_TICK_ARRAYS_PER_LEG: usize = 3;
+
+/// 某个方向的 tick / bin array: 优先使用列表，为空时取旧格式的三个字段中连续给出的部分。
+/// 按 swap 顺序原样返回，至少需要一个，超过 MAX_TICK_ARRAYS_PER_LEG 时报错
+pub fn tick_arrays(
+    list: &[Pubkey],
+    legacy: [Option<Pubkey>; 3],
+    field: &'static str,
+) -> Result<Vec<Pubkey>, AccountMetaError> {
+    let tick_arrays: Vec<Pubkey> = if list.is_empty() {
+        legacy
+            .into_iter()
+            .take_while(Option::is_some)
+            .flatten()
+            .collect()
+    } else {
+        list.to_vec()
+    };
+    if tick_arrays.is_empty() {
+        return Err(AccountMetaError::MissingAccount { field });
+    }
+    if tick_arrays.len() > MAX_TICK_ARRAYS_PER_LEG {
+        return Err(AccountMetaError::TooManyAccounts {
+            field,
+            count: tick_arrays.len(),
+            max: MAX_TICK_ARRAYS_PER_LEG,
+        });
+    }
+    Ok(tick_arrays)
+}
+
+/// DEX 的静态描述
+#[derive(Debug)]
+pub struct DexProfile {
+    /// remaining_accounts 前缀中的槽位及放入的程序
+    pub program_slots: &'static [(usize, &'static str)],
+    pub optional_accounts: &'static [OptionalAccount],
+    /// 单个 leg 的预估 CU 消耗，未在 CU_PROFILE 中配置时作为 ComputeUnitModel 的初始值
+    pub compute_units: u32,
+}
+
+/// 每个 DEX 的账户结构实现该 trait，组装交易时只通过它访问 DEX 相关的信息
+pub trait DexAdapter: Send + Sync {
+    /// 按 swap 方向生成该 leg 的账户，缺少该方向需要的账户时报错
+    fn to_account_metas(&self, direction: bool) -> Result<Vec<AccountMeta>, AccountMetaError>;
+
+    /// 标识该 leg 所在池子的账户
+    fn pool_keys(&self) -> Vec<Pubkey>;
+
+    /// swap 时与用户账户互相转账的池子 token account，用于解析 transfer hook 的 source / destination
+    fn token_vaults(&self) -> Vec<Pubkey>;
+}
+
+/// 由 DEX 注册表生成 DexType、DexAccount 及 DexAccount 的 JSON / 二进制编码用的枚举 (见 wire 模块)
+macro_rules! dex_registry {
+    ($($dex:ident = $index:literal => $accounts:ident $profile:tt,)+) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub enum DexType {
+            $($dex = $index,)+
+        }
+
+        impl DexType {
+            pub fn to_u8(self) -> u8 {
+                self as u8
+            }
+
+            pub fn profile(&self) -> &'static DexProfile {
+                match self {
+                    $(DexType::$dex => &DexProfile $profile,)+
+                }
+            }
+        }
+
+        /// JSON 中以 dexType 字段标记类型，二进制格式中带 variant 下标，见 wire 模块
+        #[derive(Debug, Clone)]
+        pub enum DexAccount {
+            $($dex($accounts),)+
+        }
+
+        impl DexAccount {
+            pub fn adapter(&self) -> &dyn DexAdapter {
+                match self {
+                    $(DexAccount::$dex(accounts) => accounts,)+
+                }
+            }
+
+            /// 账户结构对应的 dex 类型
+            pub fn dex_type(&self) -> DexType {
+                match self {
+                    $(DexAccount::$dex(_) => DexType::$dex,)+
+                }
+            }
+
+            /// 按声明的 dex 类型解析 JSON 中的账户，用于不带 dexType 字段的旧格式事件
+            pub fn from_json(
+                dex_type: DexType,
+                value: serde_json::Value,
+            ) -> serde_json::Result<Self> {
+                Ok(match dex_type {
+                    $(DexType::$dex => DexAccount::$dex(serde_json::from_value(value)?),)+
+                })
+            }
+        }
+
+        /// JSON 中 DexAccount 以 dexType 字段标记类型，值与 DexType 的 JSON 表示一致
+        #[allow(dead_code)]
+        #[derive(Serialize, Deserialize)]
+        #[serde(remote = "DexAccount", tag = "dexType", rename_all = "camelCase")]
+        pub(crate) enum JsonDexAccount {
+            $($dex($accounts),)+
+        }
+
+        /// 二进制格式中 DexAccount 以 variant 下标 (注册表中的顺序) 开头
+        #[allow(dead_code)]
+        #[derive(Serialize, Deserialize)]
+        #[serde(remote = "DexAccount")]
+        pub(crate) enum TaggedDexAccount {
+            $($dex($accounts),)+
+        }
+    };
+}
+
+// DEX 注册表: DexType = 链上下标 => 账户结构 DexProfile。
+// 新增 DEX 时在此登记一行，并为账户结构实现 DexAdapter；
+// 需要支持只给出池子地址的 leg (route.pools) 时，再在 derive / pool_cache 中加入状态的解码与推导
+dex_registry! {
+    RaydiumAmm = 1 => RaydiumAmmAccounts {
+        program_slots: &[
+            (0, "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"),
+            (1, "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX"),
+        ],
+        optional_accounts: &[],
+        compute_units: 60_000,
+    },
+    RaydiumCpmm = 2 => RaydiumCpmmAccounts {
+        program_slots: &[(2, "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C")],
+        optional_accounts: &[OptionalAccount::TokenProgram2022],
+        compute_units: 60_000,
+    },
+    RaydiumClmm = 3 => RaydiumClmmAccounts {
+        program_slots: &[(3, "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK")],
+        optional_accounts: &[
+            OptionalAccount::MemoProgram,
+            OptionalAccount::TokenProgram2022,
+        ],
+        compute_units: 120_000,
+    },
+    Orca = 4 => OrcaAccounts {
+        program_slots: &[(4, "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc")],
+        optional_accounts: &[],
+        compute_units: 100_000,
+    },
+    MeteoraDlmm = 5 => MeteoraDlmmAccounts {
+        program_slots: &[(5, "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo")],
+        optional_accounts: &[],
+        compute_units: 120_000,
+    },
+    MeteoraAmm = 6 => MeteoraAmmAccounts {
+        program_slots: &[
+            (6, "Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB"),
+            (7, "24Uqj9JCLxUeoC3hGfh5W3s9FM9uCHDS2SG3LYwBpyTi"),
+        ],
+        optional_accounts: &[],
+        compute_units: 100_000,
+    },
+    Solfi = 7 => SolfiAccounts {
+        program_slots: &[(8, "SoLFiHG9TfgtdUXUjWAxi3LtvYuFyDLVhBWxdMZxyCe")],
+        optional_accounts: &[OptionalAccount::InstructionsSysvar],
+        compute_units: 40_000,
+    },
+    Lifinity = 8 => LifinityAccounts {
+        program_slots: &[(9, "2wT8Yq49kHgDzXuPxZSaeLaH1qbmGXtEyPy64bL7aD3c")],
+        optional_accounts: &[],
+        compute_units: 60_000,
+    },
+    Phoenix = 9 => PhoenixAccounts {
+        program_slots: &[(10, "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY")],
+        optional_accounts: &[],
+        compute_units: 50_000,
+    },
+    Pump = 10 => PumpAccounts {
+        program_slots: &[(11, "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA")],
+        optional_accounts: &[],
+        compute_units: 80_000,
+    },
+    Obric = 11 => ObricAccounts {
+        program_slots: &[(12, "obriQD1zbpyLz95G5n7nJe6a4DPjpFwa5XYPoNm113y")],
+        optional_accounts: &[],
+        compute_units: 60_000,
+    },
+    Openbook = 12 => OpenbookAccounts {
+        program_slots: &[(13, "opnb2LAfJYbRMAHHvqjCwQxanZn7ReEHp1k81EohpZb")],
+        optional_accounts: &[],
+        compute_units: 80_000,
+    },
+    JupPerp = 13 => JupPerpAccounts {
+        program_slots: &[(14, "PERPHjGBqRHArX4DySjwM6UJHiR3sWAatqfdBS2qQJu")],
+        optional_accounts: &[],
+        compute_units: 150_000,
+    },
+    MeteoraCpam = 14 => MeteoraCpamAccounts {
+        program_slots: &[(15, "cpamdpZCGKUy5JxQXB4dcpGPiikHawvSWAd6mEn1sGG")],
+        optional_accounts: &[],
+        compute_units: 70_000,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(count: usize) -> Vec<Pubkey> {
+        (0..count).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    fn clmm(tick_arrays: &[Pubkey], bitmap_extension: Option<Pubkey>) -> DexAccount {
+        let mut value = serde_json::json!({
+            "raydiumAmmConfig": Pubkey::new_unique().to_string(),
+            "raydiumPoolState": Pubkey::new_unique().to_string(),
+            "raydiumInputVault": Pubkey::new_unique().to_string(),
+            "raydiumOutputVault": Pubkey::new_unique().to_string(),
+            "raydiumObservationState": Pubkey::new_unique().to_string(),
+            "raydiumAToBTickArrays": tick_arrays
+                .iter()
+                .map(|key| key.to_string())
+                .collect::<Vec<_>>(),
+        });
+        if let Some(bitmap_extension) = bitmap_extension {
+            value["raydiumTickArrayBitmapExtension"] = bitmap_extension.to_string().into();
+        }
+        DexAccount::from_json(DexType::RaydiumClmm, value).unwrap()
+    }
+
+    #[test]
+    fn tick_arrays_prefer_list_in_order() {
+        let list = keys(2);
+        let legacy = keys(3);
+        assert_eq!(
+            tick_arrays(
+                &list,
+                [Some(legacy[0]), Some(legacy[1]), Some(legacy[2])],
+                "list"
+            )
+            .unwrap(),
+            list
+        );
+    }
+
+    #[test]
+    fn tick_arrays_take_contiguous_legacy_fields() {
+        let legacy = keys(3);
+        assert_eq!(
+            tick_arrays(&[], [Some(legacy[0]), None, Some(legacy[2])], "legacy").unwrap(),
+            vec![legacy[0]]
+        );
+        assert_eq!(
+            tick_arrays(&[], [None, Some(legacy[1]), None], "legacy"),
+            Err(AccountMetaError::MissingAccount { field: "legacy" })
+        );
+    }
+
+    #[test]
+    fn tick_arrays_reject_more_than_the_program_accepts() {
+        assert_eq!(
+            tick_arrays(&keys(4), [None; 3], "list"),
+            Err(AccountMetaError::TooManyAccounts {
+                field: "list",
+                count: 4,
+                max: MAX_TICK_ARRAYS_PER_LEG,
+            })
+        );
+    }
+
+    #[test]
+    fn clmm_leg_keeps_eight_accounts() {
+        let tick_arrays = keys(3);
+        let metas = clmm(&tick_arrays, None)
+            .adapter()
+            .to_account_metas(true)
+            .unwrap();
+        assert_eq!(metas.len(), 8);
+        assert_eq!(
+            metas[5..]
+                .iter()
+                .map(|meta| meta.pubkey)
+                .collect::<Vec<_>>(),
+            tick_arrays
+        );
+        // 少于三个时原样传入，不重复补齐
+        assert_eq!(
+            clmm(&tick_arrays[..1], None)
+                .adapter()
+                .to_account_metas(true)
+                .unwrap()
+                .len(),
+            6
+        );
+    }
+
+    #[test]
+    fn clmm_bitmap_extension_is_rejected() {
+        let leg = clmm(&keys(1), Some(Pubkey::new_unique()));
+        assert_eq!(
+            leg.adapter().to_account_metas(true).unwrap_err(),
+            AccountMetaError::UnsupportedAccount {
+                field: "raydiumTickArrayBitmapExtension"
+            }
+        );
+    }
+}
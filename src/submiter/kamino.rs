@@ -1,7 +1,10 @@
 use anchor_client::solana_sdk::instruction::Instruction;
 use anchor_lang::prelude::{AccountMeta, Pubkey};
+use serde::Deserialize;
 use std::str::FromStr;
 
+use crate::submiter::wire::pubkey_serde;
+
 pub const KAMINO_LENDING_PROGRAM_ID: &str = "KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD";
 pub const KAMINO_ADDITIONAL_COMPUTE_UNITS: u32 = 80_000;
 
@@ -26,71 +29,86 @@ impl FlashRepayReserveLiquidity {
     }
 }
 
+/// 闪电贷使用的 reserve 及其所属 lending market
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct KaminoReserve {
+    #[serde(with = "pubkey_serde")]
+    pub lending_market: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub lending_market_authority: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub reserve: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub reserve_liquidity: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    pub fee_receiver: Pubkey,
+}
+
+impl KaminoReserve {
+    /// 内置的 reserve，其他 mint 在 MINT_CONFIG 中配置 kamino_reserve
+    pub fn builtin(mint: &Pubkey) -> Option<Self> {
+        let (lending_market, lending_market_authority, reserve, reserve_liquidity, fee_receiver) =
+            match mint.to_string().as_str() {
+                "So11111111111111111111111111111111111111112" => (
+                    "H6rHXmXoCQvq8Ue81MqNh7ow5ysPa1dSozwW3PU1dDH6",
+                    "Dx8iy2o46sK1DzWbEcznqSKeLbLVeu7otkibA3WohGAj",
+                    "6gTJfuPHEg6uRAijRkMqNc9kan4sVZejKMxmvx2grT1p",
+                    "ywaaLvG7t1vXJo8sT3UzE8yzzZtxLM7Fmev64Jbooye",
+                    "EQ7hw63aBS7aPQqXsoxaaBxiwbEzaAiY9Js6tCekkqxf",
+                ),
+                "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v" => (
+                    "7u3HeHxYDLhnCoErrtycNokbQYbWGzLs6JSDqGAv5PfF",
+                    "9DrvZvyWh1HuAoZxvYWMvkf2XCzryCpGgHqrMjyDWpmo",
+                    "D6q6wuQSrifJKZYpR1M8R4YawnLDtDsMmWM1NbBmgJ59",
+                    "Bgq7trRgVMeq33yt235zM2onQ4bRDBsY5EWiTetF4qw6",
+                    "BbDUrk1bVtSixgQsPLBJFZEF7mwGstnD5joA1WzYvYFX",
+                ),
+                "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB" => (
+                    "7u3HeHxYDLhnCoErrtycNokbQYbWGzLs6JSDqGAv5PfF",
+                    "9DrvZvyWh1HuAoZxvYWMvkf2XCzryCpGgHqrMjyDWpmo",
+                    "H3t6qZ1JkguCNTi9uzVKqQ7dvt2cum4XiXWom6Gn5e5S",
+                    "2Eff8Udy2G2gzNcf2619AnTx3xM4renEv4QrHKjS1o9N",
+                    "ARCZqsnUpvPffquPjZR3sxpvScLQdbfZ5BGf3SZvyij7",
+                ),
+                _ => return None,
+            };
+        Some(KaminoReserve {
+            lending_market: Pubkey::from_str(lending_market).unwrap(),
+            lending_market_authority: Pubkey::from_str(lending_market_authority).unwrap(),
+            reserve: Pubkey::from_str(reserve).unwrap(),
+            reserve_liquidity: Pubkey::from_str(reserve_liquidity).unwrap(),
+            fee_receiver: Pubkey::from_str(fee_receiver).unwrap(),
+        })
+    }
+}
+
+/// 借出的 token A 只能是 spl-token
 fn get_account_vec(
     wallet_pk: &Pubkey,
     token_account: Pubkey,
     mint: Pubkey,
+    kamino_reserve: &KaminoReserve,
 ) -> anyhow::Result<(Vec<AccountMeta>, Pubkey)> {
-    // LENDING_MARKET
-    // LENDING_MARKET_AUTHORITY
-    // RESERVE
-    // RESERVE_LIQUIDITY
-    // FEE_RECEIVER
-    let (lending_market, lending_market_authority, reserve, reserve_liquidity, fee_receiver) =
-        if mint.to_string() == "So11111111111111111111111111111111111111112" {
-            (
-                Pubkey::from_str("H6rHXmXoCQvq8Ue81MqNh7ow5ysPa1dSozwW3PU1dDH6").unwrap(),
-                Pubkey::from_str("Dx8iy2o46sK1DzWbEcznqSKeLbLVeu7otkibA3WohGAj").unwrap(),
-                Pubkey::from_str("6gTJfuPHEg6uRAijRkMqNc9kan4sVZejKMxmvx2grT1p").unwrap(),
-                Pubkey::from_str("ywaaLvG7t1vXJo8sT3UzE8yzzZtxLM7Fmev64Jbooye").unwrap(),
-                Pubkey::from_str("EQ7hw63aBS7aPQqXsoxaaBxiwbEzaAiY9Js6tCekkqxf").unwrap(),
-            )
-        } else if mint.to_string() == "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v" {
-            (
-                Pubkey::from_str("7u3HeHxYDLhnCoErrtycNokbQYbWGzLs6JSDqGAv5PfF").unwrap(),
-                Pubkey::from_str("9DrvZvyWh1HuAoZxvYWMvkf2XCzryCpGgHqrMjyDWpmo").unwrap(),
-                Pubkey::from_str("D6q6wuQSrifJKZYpR1M8R4YawnLDtDsMmWM1NbBmgJ59").unwrap(),
-                Pubkey::from_str("Bgq7trRgVMeq33yt235zM2onQ4bRDBsY5EWiTetF4qw6").unwrap(),
-                Pubkey::from_str("BbDUrk1bVtSixgQsPLBJFZEF7mwGstnD5joA1WzYvYFX").unwrap(),
-            )
-        } else if mint.to_string() == "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB" {
-            (
-                Pubkey::from_str("7u3HeHxYDLhnCoErrtycNokbQYbWGzLs6JSDqGAv5PfF").unwrap(),
-                Pubkey::from_str("9DrvZvyWh1HuAoZxvYWMvkf2XCzryCpGgHqrMjyDWpmo").unwrap(),
-                Pubkey::from_str("H3t6qZ1JkguCNTi9uzVKqQ7dvt2cum4XiXWom6Gn5e5S").unwrap(),
-                Pubkey::from_str("2Eff8Udy2G2gzNcf2619AnTx3xM4renEv4QrHKjS1o9N").unwrap(),
-                Pubkey::from_str("ARCZqsnUpvPffquPjZR3sxpvScLQdbfZ5BGf3SZvyij7").unwrap(),
-            )
-        } else {
-            return Err(anyhow::anyhow!(
-                "Unsupported mint for kamino flashloan: {}",
-                mint
-            ));
-        };
-
     let kamino_program_id = Pubkey::from_str(KAMINO_LENDING_PROGRAM_ID)?;
     let referrer_token_state = Pubkey::from_str(KAMINO_LENDING_PROGRAM_ID)?;
     let referrer_account = Pubkey::from_str(KAMINO_LENDING_PROGRAM_ID)?;
 
     let accounts = vec![
         AccountMeta::new(*wallet_pk, true), // userTransferAuthority
-        AccountMeta::new_readonly(lending_market_authority, false), // lendingMarketAuthority
-        AccountMeta::new_readonly(lending_market, false), // lendingMarket
-        AccountMeta::new(reserve, false),   // reserve
+        AccountMeta::new_readonly(kamino_reserve.lending_market_authority, false), // lendingMarketAuthority
+        AccountMeta::new_readonly(kamino_reserve.lending_market, false),           // lendingMarket
+        AccountMeta::new(kamino_reserve.reserve, false),                           // reserve
         AccountMeta::new_readonly(mint, false), // reserveLiquidityMint
-        AccountMeta::new(reserve_liquidity, false), // reserveSourceLiquidity
+        AccountMeta::new(kamino_reserve.reserve_liquidity, false), // reserveSourceLiquidity
         AccountMeta::new(token_account, false), // userDestinationLiquidity
-        AccountMeta::new(fee_receiver, false), // reserveLiquidityFeeReceiver
+        AccountMeta::new(kamino_reserve.fee_receiver, false), // reserveLiquidityFeeReceiver
         AccountMeta::new_readonly(referrer_token_state, false), // referrerTokenState
         AccountMeta::new_readonly(referrer_account, false), // referrerAccount
         AccountMeta::new_readonly(
             Pubkey::from_str("Sysvar1nstructions1111111111111111111111111").unwrap(),
             false,
         ), // sysvarInfo
-        AccountMeta::new_readonly(
-            Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").unwrap(),
-            false,
-        ), // tokenProgram
+        AccountMeta::new_readonly(spl_token::ID, false), // tokenProgram
     ];
     Ok((accounts, kamino_program_id))
 }
@@ -99,9 +117,11 @@ pub fn get_kamino_flashloan_borrow_ix(
     wallet_pk: &Pubkey,
     token_account: Pubkey,
     mint: Pubkey,
+    kamino_reserve: &KaminoReserve,
     amount: u64,
 ) -> anyhow::Result<Instruction> {
-    let (accounts, kamino_program_id) = get_account_vec(wallet_pk, token_account, mint)?;
+    let (accounts, kamino_program_id) =
+        get_account_vec(wallet_pk, token_account, mint, kamino_reserve)?;
 
     Ok(Instruction {
         program_id: kamino_program_id,
@@ -114,10 +134,12 @@ pub fn get_kamino_flashloan_repay_ix(
     wallet_pk: &Pubkey,
     token_account: Pubkey,
     mint: Pubkey,
+    kamino_reserve: &KaminoReserve,
     borrow_instruction_index: u8,
     amount: u64,
 ) -> anyhow::Result<Instruction> {
-    let (accounts, kamino_program_id) = get_account_vec(wallet_pk, token_account, mint)?;
+    let (accounts, kamino_program_id) =
+        get_account_vec(wallet_pk, token_account, mint, kamino_reserve)?;
 
     Ok(Instruction {
         program_id: kamino_program_id,
@@ -125,3 +147,95 @@ pub fn get_kamino_flashloan_repay_ix(
         data: FlashRepayReserveLiquidity::instruction_data(amount, borrow_instruction_index),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reserve() -> KaminoReserve {
+        KaminoReserve {
+            lending_market: Pubkey::new_unique(),
+            lending_market_authority: Pubkey::new_unique(),
+            reserve: Pubkey::new_unique(),
+            reserve_liquidity: Pubkey::new_unique(),
+            fee_receiver: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn instruction_data_layout() {
+        let borrow = FlashBorrowReserveLiquidity::instruction_data(1_000);
+        assert_eq!(borrow.len(), 16);
+        assert_eq!(&borrow[8..], &1_000u64.to_le_bytes());
+
+        let repay = FlashRepayReserveLiquidity::instruction_data(1_000, 3);
+        assert_eq!(repay.len(), 17);
+        assert_eq!(&repay[8..16], &1_000u64.to_le_bytes());
+        assert_eq!(repay[16], 3);
+    }
+
+    #[test]
+    fn borrow_and_repay_share_the_account_layout() {
+        let wallet = Pubkey::new_unique();
+        let token_account = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let reserve = reserve();
+        let borrow =
+            get_kamino_flashloan_borrow_ix(&wallet, token_account, mint, &reserve, 1_000).unwrap();
+        let repay = get_kamino_flashloan_repay_ix(&wallet, token_account, mint, &reserve, 2, 1_000)
+            .unwrap();
+
+        assert_eq!(borrow.program_id.to_string(), KAMINO_LENDING_PROGRAM_ID);
+        assert_eq!(borrow.accounts, repay.accounts);
+        let accounts = &borrow.accounts;
+        assert_eq!(accounts.len(), 12);
+        assert_eq!(accounts[0], AccountMeta::new(wallet, true));
+        assert_eq!(
+            accounts[1],
+            AccountMeta::new_readonly(reserve.lending_market_authority, false)
+        );
+        assert_eq!(
+            accounts[2],
+            AccountMeta::new_readonly(reserve.lending_market, false)
+        );
+        assert_eq!(accounts[3], AccountMeta::new(reserve.reserve, false));
+        assert_eq!(accounts[4], AccountMeta::new_readonly(mint, false));
+        assert_eq!(
+            accounts[5],
+            AccountMeta::new(reserve.reserve_liquidity, false)
+        );
+        assert_eq!(accounts[6], AccountMeta::new(token_account, false));
+        assert_eq!(accounts[7], AccountMeta::new(reserve.fee_receiver, false));
+        assert_eq!(
+            accounts[11],
+            AccountMeta::new_readonly(spl_token::ID, false)
+        );
+    }
+
+    #[test]
+    fn builtin_reserves_cover_wsol_usdc_usdt() {
+        for mint in [
+            "So11111111111111111111111111111111111111112",
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+            "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB",
+        ] {
+            assert!(KaminoReserve::builtin(&Pubkey::from_str(mint).unwrap()).is_some());
+        }
+        assert!(KaminoReserve::builtin(&Pubkey::new_unique()).is_none());
+    }
+
+    #[test]
+    fn reserve_parses_from_yaml() {
+        let expected = reserve();
+        let yaml = format!(
+            "lending_market: {}\nlending_market_authority: {}\nreserve: {}\nreserve_liquidity: {}\nfee_receiver: {}\n",
+            expected.lending_market,
+            expected.lending_market_authority,
+            expected.reserve,
+            expected.reserve_liquidity,
+            expected.fee_receiver,
+        );
+        let parsed: KaminoReserve = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed, expected);
+    }
+}
@@ -0,0 +1,68 @@
+use log::info;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 进程内计数器，定期输出到日志
+pub struct Metrics {
+    pub received: AtomicU64,
+    pub executed: AtomicU64,
+    pub failed: AtomicU64,
+    pub shed_oldest: AtomicU64,
+    pub shed_newest: AtomicU64,
+    pub shed_lowest_profit: AtomicU64,
+    pub deduplicated: AtomicU64,
+    /// leg 账户缺失或非法导致组装失败，同时计入 failed
+    pub invalid_accounts: AtomicU64,
+}
+
+pub static METRICS: Metrics = Metrics::new();
+
+impl Metrics {
+    const fn new() -> Self {
+        Metrics {
+            received: AtomicU64::new(0),
+            executed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            shed_oldest: AtomicU64::new(0),
+            shed_newest: AtomicU64::new(0),
+            shed_lowest_profit: AtomicU64::new(0),
+            deduplicated: AtomicU64::new(0),
+            invalid_accounts: AtomicU64::new(0),
+        }
+    }
+
+    pub fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> serde_json::Value {
+        serde_json::json!({
+            "received": self.received.load(Ordering::Relaxed),
+            "executed": self.executed.load(Ordering::Relaxed),
+            "failed": self.failed.load(Ordering::Relaxed),
+            "shedOldest": self.shed_oldest.load(Ordering::Relaxed),
+            "shedNewest": self.shed_newest.load(Ordering::Relaxed),
+            "shedLowestProfit": self.shed_lowest_profit.load(Ordering::Relaxed),
+            "deduplicated": self.deduplicated.load(Ordering::Relaxed),
+            "invalidAccounts": self.invalid_accounts.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// 每 METRICS_INTERVAL_SECS 秒输出一次计数，默认 60 秒，设置为 0 关闭
+pub fn spawn_reporter() {
+    let interval = std::env::var("METRICS_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(60);
+    if interval == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            ticker.tick().await;
+            info!("metrics: {}", METRICS.snapshot());
+        }
+    });
+}
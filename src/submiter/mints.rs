@@ -0,0 +1,426 @@
+use anchor_client::{
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+    },
+};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::Deserialize;
+use spl_token_2022::{
+    extension::{
+        transfer_fee::TransferFeeConfig, transfer_hook, BaseStateWithExtensions,
+        StateWithExtensions,
+    },
+    state::{Account, Mint},
+};
+use spl_transfer_hook_interface::{
+    get_extra_account_metas_address,
+    offchain::{add_extra_account_metas_for_execute, AccountFetchError},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use crate::submiter::kamino::KaminoReserve;
+use crate::submiter::wire::{option_pubkey_serde, pubkey_serde};
+
+/// 未配置 profit_decimals 时 min_profit 的精度，与上游的默认值一致
+const DEFAULT_PROFIT_DECIMALS: u8 = 6;
+
+/// mint 的精度、所属 token program 与利润单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MintInfo {
+    pub decimals: u8,
+    pub token_program: Pubkey,
+    /// 以该 token 计价的 min_profit 的精度，未配置时为 DEFAULT_PROFIT_DECIMALS
+    pub profit_decimals: u8,
+    /// Token-2022 TransferHook 扩展指定的 hook 程序
+    pub transfer_hook_program: Option<Pubkey>,
+    /// Token-2022 TransferFeeConfig 扩展在当前 epoch 的费率
+    pub transfer_fee: Option<TransferFeeRate>,
+}
+
+/// 转账手续费率，与 spl-token-2022 的 TransferFee 一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct TransferFeeRate {
+    pub basis_points: u16,
+    pub maximum_fee: u64,
+}
+
+impl TransferFeeRate {
+    /// 转账 amount 时扣留的手续费，向上取整且不超过 maximum_fee
+    pub fn fee(&self, amount: u64) -> u64 {
+        if self.basis_points == 0 || amount == 0 {
+            return 0;
+        }
+        let fee = (amount as u128 * self.basis_points as u128).div_ceil(10_000);
+        fee.min(self.maximum_fee as u128) as u64
+    }
+}
+
+impl MintInfo {
+    pub fn is_token_2022(&self) -> bool {
+        self.token_program == spl_token_2022::ID
+    }
+}
+
+#[derive(Deserialize)]
+struct MintConfigEntry {
+    #[serde(with = "pubkey_serde")]
+    mint: Pubkey,
+    decimals: u8,
+    /// 未配置时为 spl-token
+    #[serde(default, with = "option_pubkey_serde")]
+    token_program: Option<Pubkey>,
+    #[serde(default)]
+    profit_decimals: Option<u8>,
+    #[serde(default, with = "option_pubkey_serde")]
+    transfer_hook_program: Option<Pubkey>,
+    #[serde(default)]
+    transfer_fee: Option<TransferFeeRate>,
+    /// Kamino 闪电贷 reserve，未配置时使用 KaminoReserve::builtin
+    #[serde(default)]
+    kamino_reserve: Option<KaminoReserve>,
+}
+
+#[derive(Deserialize)]
+struct MintConfigFile {
+    mints: Vec<MintConfigEntry>,
+}
+
+/// mint 注册表: 启动时读取 MINT_CONFIG，未知的 mint 从链上读取后缓存，
+/// MINT_REFRESH_SECS 大于 0 时缓存超过该时间的记录会重新从链上读取。
+/// 没有可用的 RPC 时只使用配置中的记录
+pub struct MintRegistry {
+    cache: RwLock<HashMap<Pubkey, (MintInfo, Instant)>>,
+    /// transfer hook 的 ExtraAccountMetaList 数据，按 PDA 缓存，与 mint 使用相同的刷新间隔
+    extra_account_metas: RwLock<HashMap<Pubkey, (Vec<u8>, Instant)>>,
+    /// 池子 vault 的 mint 与 owner，读取后一直缓存
+    token_accounts: RwLock<HashMap<Pubkey, (Pubkey, Pubkey)>>,
+    /// 配置中指定的利润精度，从链上刷新时保留
+    profit_decimals: HashMap<Pubkey, u8>,
+    kamino_reserves: HashMap<Pubkey, KaminoReserve>,
+    connection: Option<Arc<RpcClient>>,
+    refresh: Option<Duration>,
+}
+
+impl MintRegistry {
+    pub fn from_env(connection: Option<Arc<RpcClient>>) -> Result<Self> {
+        let refresh = std::env::var("MINT_REFRESH_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
+
+        let mut cache = HashMap::new();
+        let mut profit_decimals = HashMap::new();
+        let mut kamino_reserves = HashMap::new();
+        if let Ok(path) = std::env::var("MINT_CONFIG") {
+            if !path.is_empty() {
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("读取配置失败: {}", path))?;
+                let config: MintConfigFile = serde_yaml::from_str(&content)
+                    .with_context(|| format!("解析配置失败: {}", path))?;
+                let now = Instant::now();
+                for entry in config.mints {
+                    let info = MintInfo {
+                        decimals: entry.decimals,
+                        token_program: entry.token_program.unwrap_or(spl_token::ID),
+                        profit_decimals: entry.profit_decimals.unwrap_or(DEFAULT_PROFIT_DECIMALS),
+                        transfer_hook_program: entry.transfer_hook_program,
+                        transfer_fee: entry.transfer_fee,
+                    };
+                    if let Some(decimals) = entry.profit_decimals {
+                        profit_decimals.insert(entry.mint, decimals);
+                    }
+                    if let Some(reserve) = entry.kamino_reserve {
+                        kamino_reserves.insert(entry.mint, reserve);
+                    }
+                    cache.insert(entry.mint, (info, now));
+                }
+                info!("Loaded {} mints from {}", cache.len(), path);
+            }
+        }
+
+        Ok(MintRegistry {
+            cache: RwLock::new(cache),
+            extra_account_metas: RwLock::new(HashMap::new()),
+            token_accounts: RwLock::new(HashMap::new()),
+            profit_decimals,
+            kamino_reserves,
+            connection,
+            refresh,
+        })
+    }
+
+    /// 查询 mint 信息，缓存中没有或已过期时从链上读取
+    pub async fn get(&self, mint: &Pubkey) -> Result<MintInfo> {
+        let cached = self.cache.read().unwrap().get(mint).copied();
+        if let Some((info, loaded_at)) = cached {
+            match self.refresh {
+                Some(refresh) if loaded_at.elapsed() >= refresh => {}
+                _ => return Ok(info),
+            }
+        }
+
+        match self.fetch(mint).await {
+            Ok(info) => {
+                if let Some((old, _)) = cached.filter(|(old, _)| *old != info) {
+                    warn!("mint {} changed: {:?} -> {:?}", mint, old, info);
+                }
+                self.cache
+                    .write()
+                    .unwrap()
+                    .insert(*mint, (info, Instant::now()));
+                Ok(info)
+            }
+            // 刷新失败时继续使用旧记录
+            Err(e) => match cached {
+                Some((info, _)) => {
+                    warn!("refresh mint {} failed: {:?}", mint, e);
+                    Ok(info)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    /// 缓存中的 mint 信息，不检查是否过期，也不从链上读取
+    pub fn cached(&self, mint: &Pubkey) -> Option<MintInfo> {
+        self.cache.read().unwrap().get(mint).map(|(info, _)| *info)
+    }
+
+    /// 借出 mint 时使用的 Kamino reserve，配置优先于内置记录
+    pub fn kamino_reserve(&self, mint: &Pubkey) -> Result<KaminoReserve> {
+        self.kamino_reserves
+            .get(mint)
+            .copied()
+            .or_else(|| KaminoReserve::builtin(mint))
+            .with_context(|| format!("Unsupported mint for kamino flashloan: {}", mint))
+    }
+
+    fn connection(&self, what: &Pubkey) -> Result<&RpcClient> {
+        self.connection
+            .as_deref()
+            .with_context(|| format!("{} requires an RPC connection", what))
+    }
+
+    /// token account 的 mint 与 owner
+    pub async fn token_account(&self, address: &Pubkey) -> Result<(Pubkey, Pubkey)> {
+        if let Some(cached) = self.token_accounts.read().unwrap().get(address).copied() {
+            return Ok(cached);
+        }
+        let account = self
+            .connection(address)?
+            .get_account(address)
+            .await
+            .with_context(|| format!("读取 token account 失败: {}", address))?;
+        let state = StateWithExtensions::<Account>::unpack(&account.data)
+            .with_context(|| format!("解析 token account 失败: {}", address))?;
+        let parsed = (state.base.mint, state.base.owner);
+        self.token_accounts
+            .write()
+            .unwrap()
+            .insert(*address, parsed);
+        Ok(parsed)
+    }
+
+    /// 在池子的 vault 中找到存放 mint 的一个，返回 vault 及其 owner
+    pub async fn find_vault(&self, vaults: &[Pubkey], mint: &Pubkey) -> Result<(Pubkey, Pubkey)> {
+        for vault in vaults {
+            let (vault_mint, owner) = self.token_account(vault).await?;
+            if vault_mint == *mint {
+                return Ok((*vault, owner));
+            }
+        }
+        Err(anyhow::anyhow!(
+            "no pool vault holds mint {} among {:?}",
+            mint,
+            vaults
+        ))
+    }
+
+    /// ExtraAccountMetaList 的数据，过期时重新读取，读取失败时继续使用旧数据
+    async fn extra_account_metas(&self, address: &Pubkey) -> Result<Vec<u8>> {
+        let cached = self
+            .extra_account_metas
+            .read()
+            .unwrap()
+            .get(address)
+            .cloned();
+        if let Some((data, loaded_at)) = &cached {
+            match self.refresh {
+                Some(refresh) if loaded_at.elapsed() >= refresh => {}
+                _ => return Ok(data.clone()),
+            }
+        }
+        let fetched = self
+            .connection(address)?
+            .get_account(address)
+            .await
+            .with_context(|| format!("读取 ExtraAccountMetaList 失败: {}", address));
+        match (fetched, cached) {
+            (Ok(account), _) => {
+                self.extra_account_metas
+                    .write()
+                    .unwrap()
+                    .insert(*address, (account.data.clone(), Instant::now()));
+                Ok(account.data)
+            }
+            (Err(e), Some((data, _))) => {
+                warn!("refresh {} failed: {:?}", address, e);
+                Ok(data)
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+
+    /// 按 mint 的 ExtraAccountMetaList 解析一次转账的 transfer hook 需要的账户，
+    /// 顺序为: 解析出的额外账户、hook 程序、ExtraAccountMetaList PDA。没有 hook 时为空
+    pub async fn transfer_hook_accounts(
+        &self,
+        mint: &Pubkey,
+        info: &MintInfo,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Vec<AccountMeta>> {
+        let Some(program_id) = info.transfer_hook_program else {
+            return Ok(vec![]);
+        };
+        let connection = self.connection(mint)?;
+        let validation_address = get_extra_account_metas_address(mint, &program_id);
+        let validation_data = self.extra_account_metas(&validation_address).await?;
+        // add_extra_account_metas_for_execute 要求指令中已包含这四个账户
+        let mut instruction = Instruction::new_with_bytes(
+            program_id,
+            &[],
+            vec![
+                AccountMeta::new_readonly(*source, false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new_readonly(*destination, false),
+                AccountMeta::new_readonly(*authority, false),
+            ],
+        );
+        let validation_data = &validation_data;
+        add_extra_account_metas_for_execute(
+            &mut instruction,
+            &program_id,
+            source,
+            mint,
+            destination,
+            authority,
+            amount,
+            // ExtraAccountMetaList 使用缓存，种子引用的其他账户按需读取
+            |address| async move {
+                if address == validation_address {
+                    return Ok(Some(validation_data.clone()));
+                }
+                connection
+                    .get_account_with_commitment(&address, connection.commitment())
+                    .await
+                    .map(|response| response.value.map(|account| account.data))
+                    .map_err(|e| Box::new(e) as AccountFetchError)
+            },
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("resolve transfer hook accounts for {}: {}", mint, e))?;
+        Ok(instruction.accounts.split_off(4))
+    }
+
+    async fn fetch(&self, mint: &Pubkey) -> Result<MintInfo> {
+        let connection = self
+            .connection
+            .as_ref()
+            .with_context(|| format!("unknown mint {} and no RPC configured", mint))?;
+        let account = connection
+            .get_account(mint)
+            .await
+            .with_context(|| format!("读取 mint 失败: {}", mint))?;
+        if account.owner != spl_token::ID && account.owner != spl_token_2022::ID {
+            return Err(anyhow::anyhow!(
+                "{} is not a token mint, owner: {}",
+                mint,
+                account.owner
+            ));
+        }
+        // Token-2022 的 mint 在基础结构之后带扩展数据
+        let state = StateWithExtensions::<Mint>::unpack(&account.data)
+            .with_context(|| format!("解析 mint 失败: {}", mint))?;
+        let decimals = state.base.decimals;
+        // 费率按 epoch 生效，取当前 epoch 的费率
+        let transfer_fee = match state.get_extension::<TransferFeeConfig>() {
+            Ok(config) => {
+                let epoch = connection
+                    .get_epoch_info()
+                    .await
+                    .context("读取 epoch 失败")?
+                    .epoch;
+                let fee = config.get_epoch_fee(epoch);
+                Some(TransferFeeRate {
+                    basis_points: u16::from(fee.transfer_fee_basis_points),
+                    maximum_fee: u64::from(fee.maximum_fee),
+                })
+            }
+            Err(_) => None,
+        };
+        Ok(MintInfo {
+            decimals,
+            token_program: account.owner,
+            profit_decimals: self
+                .profit_decimals
+                .get(mint)
+                .copied()
+                .unwrap_or(DEFAULT_PROFIT_DECIMALS),
+            transfer_hook_program: transfer_hook::get_program_id(&state),
+            transfer_fee,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// 只含给定 mint 的注册表，不连接链上
+    pub(crate) fn registry(mints: &[(Pubkey, MintInfo)]) -> MintRegistry {
+        let now = Instant::now();
+        MintRegistry {
+            cache: RwLock::new(
+                mints
+                    .iter()
+                    .map(|(mint, info)| (*mint, (*info, now)))
+                    .collect(),
+            ),
+            extra_account_metas: RwLock::new(HashMap::new()),
+            token_accounts: RwLock::new(HashMap::new()),
+            profit_decimals: HashMap::new(),
+            kamino_reserves: HashMap::new(),
+            connection: None,
+            refresh: None,
+        }
+    }
+
+    #[test]
+    fn transfer_fee_rounds_up_and_caps() {
+        let rate = TransferFeeRate {
+            basis_points: 100,
+            maximum_fee: 5_000,
+        };
+        assert_eq!(rate.fee(0), 0);
+        assert_eq!(rate.fee(1), 1);
+        assert_eq!(rate.fee(10_000), 100);
+        assert_eq!(rate.fee(10_001), 101);
+        assert_eq!(rate.fee(1_000_000), 5_000);
+        let free = TransferFeeRate {
+            basis_points: 0,
+            maximum_fee: 5_000,
+        };
+        assert_eq!(free.fee(1_000_000), 0);
+    }
+}
@@ -1,3 +1,22 @@
+pub mod amount;
 pub mod assembler;
+pub mod codec;
+pub mod compute;
+pub mod dead_letter;
+pub mod dedup;
+pub mod derive;
+pub mod dex;
 pub mod kamino;
+pub mod metrics;
+pub mod mints;
+pub mod pool_cache;
+pub mod priority_fee;
+pub mod queues;
+pub mod replay;
+pub mod route;
+pub mod scheduler;
+pub mod source;
+pub mod stream;
 pub mod submitter;
+pub mod validate;
+pub mod wire;
@@ -0,0 +1,992 @@
+use anchor_client::{
+    solana_client::{
+        nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+        rpc_config::RpcAccountInfoConfig,
+        rpc_response::Response,
+    },
+    solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey},
+};
+use anyhow::{Context, Result};
+use base64::Engine;
+use futures::{
+    future::BoxFuture,
+    stream::{BoxStream, SelectAll},
+    StreamExt,
+};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::submiter::derive::{
+    pump_global_config, MeteoraCpamState, MeteoraDlmmState, PoolSnapshot, PoolState,
+    PoolStateSource, PumpState, RaydiumAmmState, RaydiumClmmState, RaydiumCpmmState,
+    WhirlpoolState, METEORA_CPAM_PROGRAM_ID, METEORA_DLMM_PROGRAM_ID, PUMP_AMM_PROGRAM_ID,
+    RAYDIUM_AMM_PROGRAM_ID, RAYDIUM_CLMM_PROGRAM_ID, RAYDIUM_CPMM_PROGRAM_ID, WHIRLPOOL_PROGRAM_ID,
+};
+use crate::submiter::mints::MintRegistry;
+use crate::submiter::submitter::redis_client;
+use crate::submiter::wire::{pubkey_serde, pubkey_vec_serde};
+
+/// 订阅断开后重连的间隔
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+/// 检查闲置订阅的间隔
+const EVICT_INTERVAL: Duration = Duration::from_secs(10);
+/// tick / bin array 是否已初始化的检查结果的有效期，过期后后台重新检查
+const ARRAY_STATUS_TTL: Duration = Duration::from_secs(60);
+/// 检查结果超过该数量时清理过期的记录
+const ARRAY_STATUS_CAPACITY: usize = 10_000;
+
+/// 各池子账户的大小，同一程序下的其他账户 (tick array、position 等) 按大小区分
+const WHIRLPOOL_SIZE: usize = 653;
+const RAYDIUM_CLMM_POOL_SIZE: usize = 1544;
+const RAYDIUM_CPMM_POOL_SIZE: usize = 637;
+const METEORA_DLMM_LB_PAIR_SIZE: usize = 904;
+const METEORA_CPAM_POOL_SIZE: usize = 1112;
+const RAYDIUM_AMM_INFO_SIZE: usize = 752;
+/// pump pool 随版本增加字段，只要求包含 coin_creator
+const PUMP_POOL_MIN_SIZE: usize = 243;
+/// serum / openbook market: "serum" 前缀 + MarketState，读取到 asks 为止
+const SERUM_MARKET_MIN_SIZE: usize = 349;
+
+/// AMM v4 池子账户中的字段，market 相关的账户取自 market
+#[derive(Debug, Clone)]
+struct AmmInfo {
+    open_orders: Pubkey,
+    coin_vault: Pubkey,
+    pc_vault: Pubkey,
+    market: Pubkey,
+    market_program: Pubkey,
+}
+
+#[derive(Debug, Clone)]
+struct SerumMarket {
+    vault_signer_nonce: u64,
+    coin_vault: Pubkey,
+    pc_vault: Pubkey,
+    event_queue: Pubkey,
+    bids: Pubkey,
+    asks: Pubkey,
+}
+
+/// pump pool 账户中的字段，协议费接收账户取自 global config
+#[derive(Debug, Clone)]
+struct PumpPool {
+    quote_mint: Pubkey,
+    pool_base_token_account: Pubkey,
+    pool_quote_token_account: Pubkey,
+    coin_creator: Pubkey,
+}
+
+/// 解码后的账户，AMM v4 与 pump 需要与依赖的账户合并后才是完整的池子状态
+#[derive(Debug, Clone)]
+enum CachedAccount {
+    Pool(PoolState),
+    RaydiumAmm(AmmInfo),
+    Pump(PumpPool),
+    SerumMarket(SerumMarket),
+    PumpGlobalConfig { protocol_fee_recipient: Pubkey },
+}
+
+impl CachedAccount {
+    /// 组成完整状态还需要的账户
+    fn dependency(&self) -> Option<Pubkey> {
+        match self {
+            CachedAccount::RaydiumAmm(amm) => Some(amm.market),
+            CachedAccount::Pump(_) => Some(pump_global_config()),
+            _ => None,
+        }
+    }
+}
+
+fn pubkey_at(data: &[u8], offset: usize) -> Result<Pubkey> {
+    Ok(Pubkey::new_from_array(bytes_at(data, offset)?))
+}
+
+fn bytes_at<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .with_context(|| format!("account data too short: {} < {}", data.len(), offset + N))
+}
+
+fn check_size(data: &[u8], size: usize, name: &str) -> Result<()> {
+    if data.len() != size {
+        return Err(anyhow::anyhow!(
+            "not a {} account: {} bytes, expected {}",
+            name,
+            data.len(),
+            size
+        ));
+    }
+    Ok(())
+}
+
+/// 按 owner 程序解码账户，偏移量包含 anchor 账户 8 字节的 discriminator
+fn decode_account(account: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<CachedAccount> {
+    let state = match owner.to_string().as_str() {
+        WHIRLPOOL_PROGRAM_ID => {
+            check_size(data, WHIRLPOOL_SIZE, "whirlpool")?;
+            PoolState::Whirlpool(WhirlpoolState {
+                tick_spacing: u16::from_le_bytes(bytes_at(data, 41)?),
+                tick_current_index: i32::from_le_bytes(bytes_at(data, 81)?),
+                token_mint_a: pubkey_at(data, 101)?,
+                token_vault_a: pubkey_at(data, 133)?,
+                token_vault_b: pubkey_at(data, 213)?,
+            })
+        }
+        RAYDIUM_CLMM_PROGRAM_ID => {
+            check_size(data, RAYDIUM_CLMM_POOL_SIZE, "raydium clmm pool")?;
+            PoolState::RaydiumClmm(RaydiumClmmState {
+                amm_config: pubkey_at(data, 9)?,
+                token_mint_0: pubkey_at(data, 73)?,
+                token_vault_0: pubkey_at(data, 137)?,
+                token_vault_1: pubkey_at(data, 169)?,
+                observation_key: pubkey_at(data, 201)?,
+                tick_spacing: u16::from_le_bytes(bytes_at(data, 235)?),
+                tick_current: i32::from_le_bytes(bytes_at(data, 269)?),
+            })
+        }
+        RAYDIUM_CPMM_PROGRAM_ID => {
+            check_size(data, RAYDIUM_CPMM_POOL_SIZE, "raydium cpmm pool")?;
+            PoolState::RaydiumCpmm(RaydiumCpmmState {
+                amm_config: pubkey_at(data, 8)?,
+                token_0_vault: pubkey_at(data, 72)?,
+                token_1_vault: pubkey_at(data, 104)?,
+                token_0_mint: pubkey_at(data, 168)?,
+                observation_key: pubkey_at(data, 296)?,
+            })
+        }
+        METEORA_DLMM_PROGRAM_ID => {
+            check_size(data, METEORA_DLMM_LB_PAIR_SIZE, "meteora lb pair")?;
+            PoolState::MeteoraDlmm(MeteoraDlmmState {
+                active_id: i32::from_le_bytes(bytes_at(data, 76)?),
+                token_x_mint: pubkey_at(data, 88)?,
+                reserve_x: pubkey_at(data, 152)?,
+                reserve_y: pubkey_at(data, 184)?,
+                oracle: pubkey_at(data, 552)?,
+            })
+        }
+        METEORA_CPAM_PROGRAM_ID => {
+            check_size(data, METEORA_CPAM_POOL_SIZE, "meteora cpam pool")?;
+            PoolState::MeteoraCpam(MeteoraCpamState {
+                token_a_vault: pubkey_at(data, 232)?,
+                token_b_vault: pubkey_at(data, 264)?,
+            })
+        }
+        // AMM v4 没有 discriminator
+        RAYDIUM_AMM_PROGRAM_ID => {
+            check_size(data, RAYDIUM_AMM_INFO_SIZE, "raydium amm")?;
+            return Ok(CachedAccount::RaydiumAmm(AmmInfo {
+                coin_vault: pubkey_at(data, 336)?,
+                pc_vault: pubkey_at(data, 368)?,
+                open_orders: pubkey_at(data, 496)?,
+                market: pubkey_at(data, 528)?,
+                market_program: pubkey_at(data, 560)?,
+            }));
+        }
+        PUMP_AMM_PROGRAM_ID if *account == pump_global_config() => {
+            // admin、lp / protocol 费率与 disable_flags 之后为 protocol_fee_recipients，取第一个
+            return Ok(CachedAccount::PumpGlobalConfig {
+                protocol_fee_recipient: pubkey_at(data, 57)?,
+            });
+        }
+        PUMP_AMM_PROGRAM_ID => {
+            if data.len() < PUMP_POOL_MIN_SIZE {
+                return Err(anyhow::anyhow!(
+                    "not a pump pool account: {} bytes",
+                    data.len()
+                ));
+            }
+            return Ok(CachedAccount::Pump(PumpPool {
+                quote_mint: pubkey_at(data, 75)?,
+                pool_base_token_account: pubkey_at(data, 139)?,
+                pool_quote_token_account: pubkey_at(data, 171)?,
+                coin_creator: pubkey_at(data, 211)?,
+            }));
+        }
+        // AMM v4 依赖的 market 属于 serum / openbook 程序
+        _ if data.len() >= SERUM_MARKET_MIN_SIZE && data.starts_with(b"serum") => {
+            return Ok(CachedAccount::SerumMarket(SerumMarket {
+                vault_signer_nonce: u64::from_le_bytes(bytes_at(data, 45)?),
+                coin_vault: pubkey_at(data, 117)?,
+                pc_vault: pubkey_at(data, 165)?,
+                event_queue: pubkey_at(data, 253)?,
+                bids: pubkey_at(data, 285)?,
+                asks: pubkey_at(data, 317)?,
+            }));
+        }
+        other => return Err(anyhow::anyhow!("unsupported pool owner {}", other)),
+    };
+    Ok(CachedAccount::Pool(state))
+}
+
+/// 推送到 POOL_STATE_CHANNEL 的账户更新
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PoolUpdate {
+    #[serde(with = "pubkey_serde")]
+    account: Pubkey,
+    #[serde(with = "pubkey_serde")]
+    owner: Pubkey,
+    slot: u64,
+    /// base64 编码的账户数据
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct PoolConfigFile {
+    #[serde(with = "pubkey_vec_serde")]
+    pools: Vec<Pubkey>,
+}
+
+/// 订阅数量上限与闲置淘汰时间
+#[derive(Debug, Clone, Copy)]
+struct SubscriptionLimits {
+    max_subscriptions: usize,
+    idle: Option<Duration>,
+}
+
+/// 池子状态缓存: 通过 websocket 订阅账户 (POOL_WS_URL) 或接收 Redis 频道推送的账户数据
+/// (POOL_STATE_CHANNEL) 更新，按 slot 只保留最新的状态。
+/// 订阅模式下 POOL_CONFIG 中的池子启动时订阅且不会被淘汰，其他池子在首次被查询时订阅，
+/// 闲置超过 POOL_IDLE_SECS 或订阅数达到 POOL_MAX_SUBSCRIPTIONS 时淘汰最久未使用的账户。
+/// 状态落后已收到的最新 slot 超过 POOL_MAX_SLOT_LAG 时不使用，订阅模式下会重新读取一次
+pub struct PoolCache {
+    accounts: RwLock<HashMap<Pubkey, (CachedAccount, u64)>>,
+    /// 已写入的最大 slot
+    latest_slot: AtomicU64,
+    /// 为 0 时不检查状态是否过期
+    max_slot_lag: u64,
+    /// 账户最近一次被查询或订阅的时间
+    last_used: Mutex<HashMap<Pubkey, Instant>>,
+    /// POOL_CONFIG 中的池子
+    pinned: HashSet<Pubkey>,
+    /// 正在通过 RPC 读取的账户
+    loading: Mutex<HashSet<Pubkey>>,
+    /// tick / bin array 是否存在及检查时间
+    array_status: Arc<RwLock<HashMap<Pubkey, (bool, Instant)>>>,
+    /// 正在检查的 tick / bin array
+    array_checks: Arc<Mutex<HashSet<Pubkey>>>,
+    /// 用于检查 tick / bin array
+    connection: Option<Arc<RpcClient>>,
+    /// pump quote mint 所属的 token program 取自 mint 注册表
+    mints: Arc<MintRegistry>,
+    /// 需要订阅或重新读取的账户，仅订阅模式下存在
+    watch: Option<mpsc::UnboundedSender<Pubkey>>,
+}
+
+impl PoolCache {
+    /// POOL_WS_URL 与 POOL_STATE_CHANNEL 都未配置时返回 None。
+    /// connection 用于读取账户的初始状态及检查 tick / bin array 是否已初始化
+    pub fn spawn_from_env(
+        connection: Option<Arc<RpcClient>>,
+        mints: Arc<MintRegistry>,
+    ) -> Result<Option<Arc<Self>>> {
+        let ws_url = std::env::var("POOL_WS_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+        let channel = std::env::var("POOL_STATE_CHANNEL")
+            .ok()
+            .filter(|channel| !channel.is_empty());
+        if ws_url.is_none() && channel.is_none() {
+            return Ok(None);
+        }
+        let max_slot_lag = std::env::var("POOL_MAX_SLOT_LAG")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(150);
+        let limits = SubscriptionLimits {
+            max_subscriptions: std::env::var("POOL_MAX_SUBSCRIPTIONS")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(1_000)
+                .max(1),
+            idle: std::env::var("POOL_IDLE_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .or(Some(600))
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+        };
+
+        let mut pinned = HashSet::new();
+        if let Ok(path) = std::env::var("POOL_CONFIG") {
+            if !path.is_empty() {
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("读取配置失败: {}", path))?;
+                let config: PoolConfigFile = serde_yaml::from_str(&content)
+                    .with_context(|| format!("解析配置失败: {}", path))?;
+                info!("Loaded {} pools from {}", config.pools.len(), path);
+                pinned.extend(config.pools);
+            }
+        }
+
+        let (watch, watch_rx) = match ws_url {
+            Some(_) => {
+                let (tx, rx) = mpsc::unbounded_channel();
+                (Some(tx), Some(rx))
+            }
+            None => (None, None),
+        };
+        let cache = Arc::new(PoolCache {
+            accounts: RwLock::new(HashMap::new()),
+            latest_slot: AtomicU64::new(0),
+            max_slot_lag,
+            last_used: Mutex::new(HashMap::new()),
+            pinned,
+            loading: Mutex::new(HashSet::new()),
+            array_status: Arc::new(RwLock::new(HashMap::new())),
+            array_checks: Arc::new(Mutex::new(HashSet::new())),
+            connection: connection.clone(),
+            mints,
+            watch,
+        });
+        for pool in cache.pinned.iter() {
+            cache.watch(*pool);
+        }
+
+        if let (Some(ws_url), Some(watch_rx)) = (ws_url, watch_rx) {
+            tokio::spawn(run_account_subscriptions(
+                cache.clone(),
+                ws_url,
+                connection,
+                watch_rx,
+                limits,
+            ));
+        }
+        if let Some(channel) = channel {
+            tokio::spawn(run_channel_updates(cache.clone(), channel));
+        }
+        Ok(Some(cache))
+    }
+
+    /// 池子的最新状态，AMM v4 与 pump 在依赖的账户 (及 pump quote mint 的信息) 到达前为 None
+    pub fn snapshot(&self, pool: &Pubkey) -> Option<PoolSnapshot> {
+        let accounts = self.accounts.read().unwrap();
+        let (account, slot) = accounts.get(pool)?;
+        let state = match account {
+            CachedAccount::Pool(state) => state.clone(),
+            CachedAccount::RaydiumAmm(amm) => {
+                let Some((CachedAccount::SerumMarket(market), _)) = accounts.get(&amm.market)
+                else {
+                    return None;
+                };
+                PoolState::RaydiumAmm(RaydiumAmmState {
+                    open_orders: amm.open_orders,
+                    coin_vault: amm.coin_vault,
+                    pc_vault: amm.pc_vault,
+                    market: amm.market,
+                    market_program: amm.market_program,
+                    market_bids: market.bids,
+                    market_asks: market.asks,
+                    market_event_queue: market.event_queue,
+                    market_coin_vault: market.coin_vault,
+                    market_pc_vault: market.pc_vault,
+                    market_vault_signer_nonce: market.vault_signer_nonce,
+                })
+            }
+            CachedAccount::Pump(pump) => {
+                let Some((
+                    CachedAccount::PumpGlobalConfig {
+                        protocol_fee_recipient,
+                    },
+                    _,
+                )) = accounts.get(&pump_global_config())
+                else {
+                    return None;
+                };
+                PoolState::Pump(PumpState {
+                    quote_mint: pump.quote_mint,
+                    quote_token_program: self.mints.cached(&pump.quote_mint)?.token_program,
+                    pool_base_token_account: pump.pool_base_token_account,
+                    pool_quote_token_account: pump.pool_quote_token_account,
+                    coin_creator: pump.coin_creator,
+                    protocol_fee_recipient: *protocol_fee_recipient,
+                })
+            }
+            CachedAccount::SerumMarket(_) | CachedAccount::PumpGlobalConfig { .. } => return None,
+        };
+        Some(PoolSnapshot { state, slot: *slot })
+    }
+
+    /// 写入账户数据，早于已缓存 slot 的数据被忽略
+    pub fn apply(&self, account: Pubkey, owner: &Pubkey, data: &[u8], slot: u64) -> Result<()> {
+        let decoded = decode_account(&account, owner, data)
+            .with_context(|| format!("decode account {}", account))?;
+        let dependency = decoded.dependency();
+        // pump 池子的 quote mint 不在注册表中时后台读取
+        if let CachedAccount::Pump(pump) = &decoded {
+            if self.mints.cached(&pump.quote_mint).is_none() {
+                let mints = self.mints.clone();
+                let mint = pump.quote_mint;
+                tokio::spawn(async move {
+                    if let Err(e) = mints.get(&mint).await {
+                        warn!("load pump quote mint {} failed: {:?}", mint, e);
+                    }
+                });
+            }
+        }
+        let mut accounts = self.accounts.write().unwrap();
+        if accounts
+            .get(&account)
+            .is_some_and(|(_, cached_slot)| *cached_slot > slot)
+        {
+            return Ok(());
+        }
+        accounts.insert(account, (decoded, slot));
+        self.latest_slot.fetch_max(slot, Ordering::Relaxed);
+        if let Some(dependency) = dependency.filter(|key| !accounts.contains_key(key)) {
+            self.watch(dependency);
+        }
+        Ok(())
+    }
+
+    /// 订阅账户，已订阅的账户由订阅任务重新读取一次
+    fn watch(&self, account: Pubkey) {
+        if let Some(watch) = &self.watch {
+            self.touch(&account);
+            let _ = watch.send(account);
+        }
+    }
+
+    /// 记录账户被使用，池子依赖的账户一起记录
+    fn touch(&self, account: &Pubkey) {
+        let dependency = self
+            .accounts
+            .read()
+            .unwrap()
+            .get(account)
+            .and_then(|(cached, _)| cached.dependency());
+        let now = Instant::now();
+        let mut last_used = self.last_used.lock().unwrap();
+        last_used.insert(*account, now);
+        if let Some(dependency) = dependency {
+            last_used.insert(dependency, now);
+        }
+    }
+
+    fn last_used(&self, account: &Pubkey) -> Option<Instant> {
+        self.last_used.lock().unwrap().get(account).copied()
+    }
+
+    /// 后台读取 tick / bin array 是否存在，同一账户同时只有一个检查在进行
+    fn check_array(&self, account: Pubkey) {
+        let Some(connection) = self.connection.clone() else {
+            return;
+        };
+        if !self.array_checks.lock().unwrap().insert(account) {
+            return;
+        }
+        let status = self.array_status.clone();
+        let checks = self.array_checks.clone();
+        tokio::spawn(async move {
+            match connection
+                .get_account_with_commitment(&account, CommitmentConfig::processed())
+                .await
+            {
+                Ok(response) => {
+                    let mut status = status.write().unwrap();
+                    status.insert(account, (response.value.is_some(), Instant::now()));
+                    if status.len() > ARRAY_STATUS_CAPACITY {
+                        status.retain(|_, (_, checked_at)| checked_at.elapsed() < ARRAY_STATUS_TTL);
+                    }
+                }
+                Err(e) => warn!("check array {} failed: {:?}", account, e),
+            }
+            checks.lock().unwrap().remove(&account);
+        });
+    }
+
+    /// 取消订阅后丢弃账户的状态
+    fn evict(&self, account: &Pubkey) {
+        self.accounts.write().unwrap().remove(account);
+        self.last_used.lock().unwrap().remove(account);
+    }
+}
+
+impl PoolStateSource for PoolCache {
+    fn pool_state(&self, pool: &Pubkey) -> Option<PoolSnapshot> {
+        self.touch(pool);
+        let Some(snapshot) = self.snapshot(pool) else {
+            self.watch(*pool);
+            return None;
+        };
+        let lag = self
+            .latest_slot
+            .load(Ordering::Relaxed)
+            .saturating_sub(snapshot.slot);
+        if self.max_slot_lag > 0 && lag > self.max_slot_lag {
+            // 订阅只推送变更，长时间没有变化的池子重新读取后 slot 会更新
+            debug!("pool {} state is {} slots behind, reloading", pool, lag);
+            self.watch(*pool);
+            return None;
+        }
+        Some(snapshot)
+    }
+
+    /// 未检查或检查结果过期时在后台检查，结果在之后的查询中生效
+    fn account_initialized(&self, account: &Pubkey) -> Option<bool> {
+        let status = self.array_status.read().unwrap().get(account).copied();
+        if status.is_none_or(|(_, checked_at)| checked_at.elapsed() >= ARRAY_STATUS_TTL) {
+            self.check_array(*account);
+        }
+        status.map(|(initialized, _)| initialized)
+    }
+}
+
+type AccountUpdates<'a> = BoxStream<'a, (Pubkey, Response<UiAccount>)>;
+type UnsubscribeFn = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+
+/// websocket 上的一个账户订阅，drop 时对应的 stream 结束
+struct Subscription {
+    _cancel: oneshot::Sender<()>,
+    unsubscribe: UnsubscribeFn,
+}
+
+/// 取消账户的订阅并丢弃其状态
+fn unwatch(
+    cache: &PoolCache,
+    watched: &mut HashSet<Pubkey>,
+    subscriptions: &mut HashMap<Pubkey, Subscription>,
+    account: &Pubkey,
+) {
+    watched.remove(account);
+    cache.evict(account);
+    if let Some(subscription) = subscriptions.remove(account) {
+        tokio::spawn((subscription.unsubscribe)());
+    }
+}
+
+/// 通过 websocket 订阅 watch 收到的账户，连接断开或订阅失败时重连并重新订阅全部账户
+async fn run_account_subscriptions(
+    cache: Arc<PoolCache>,
+    ws_url: String,
+    connection: Option<Arc<RpcClient>>,
+    mut watch_rx: mpsc::UnboundedReceiver<Pubkey>,
+    limits: SubscriptionLimits,
+) {
+    let mut watched: HashSet<Pubkey> = HashSet::new();
+    loop {
+        let client = match PubsubClient::new(&ws_url).await {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("connect {} failed: {:?}", ws_url, e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        info!("Pool subscriptions connected: {}", ws_url);
+
+        let mut streams: SelectAll<AccountUpdates<'_>> = SelectAll::new();
+        let mut subscriptions: HashMap<Pubkey, Subscription> = HashMap::new();
+        let mut healthy = true;
+        for account in watched.iter() {
+            match subscribe(&client, *account).await {
+                Ok((stream, subscription)) => {
+                    streams.push(stream);
+                    subscriptions.insert(*account, subscription);
+                }
+                Err(e) => {
+                    warn!("subscribe {} failed: {:?}", account, e);
+                    healthy = false;
+                    break;
+                }
+            }
+        }
+        // 断线期间错过的更新由初始读取补上
+        for account in watched.iter() {
+            spawn_load(&cache, connection.as_ref(), *account);
+        }
+
+        let mut evict_ticker = tokio::time::interval(EVICT_INTERVAL);
+        while healthy {
+            tokio::select! {
+                Some(account) = watch_rx.recv() => {
+                    if !watched.contains(&account) {
+                        // 达到上限时淘汰最久未使用的账户，POOL_CONFIG 中的池子不淘汰
+                        if watched.len() >= limits.max_subscriptions {
+                            let oldest = watched
+                                .iter()
+                                .filter(|candidate| !cache.pinned.contains(*candidate))
+                                .min_by_key(|candidate| cache.last_used(candidate))
+                                .copied();
+                            match oldest {
+                                Some(oldest) => {
+                                    debug!("evict pool subscription {}", oldest);
+                                    unwatch(&cache, &mut watched, &mut subscriptions, &oldest);
+                                }
+                                None => {
+                                    warn!(
+                                        "pool subscriptions reached {}, not subscribing {}",
+                                        limits.max_subscriptions, account
+                                    );
+                                    continue;
+                                }
+                            }
+                        }
+                        watched.insert(account);
+                        match subscribe(&client, account).await {
+                            Ok((stream, subscription)) => {
+                                streams.push(stream);
+                                subscriptions.insert(account, subscription);
+                            }
+                            Err(e) => {
+                                warn!("subscribe {} failed: {:?}", account, e);
+                                healthy = false;
+                            }
+                        }
+                    }
+                    spawn_load(&cache, connection.as_ref(), account);
+                }
+                _ = evict_ticker.tick(), if limits.idle.is_some() => {
+                    let idle = limits.idle.unwrap_or_default();
+                    let expired: Vec<Pubkey> = watched
+                        .iter()
+                        .filter(|candidate| !cache.pinned.contains(*candidate))
+                        .filter(|candidate| {
+                            cache
+                                .last_used(candidate)
+                                .is_none_or(|last_used| last_used.elapsed() >= idle)
+                        })
+                        .copied()
+                        .collect();
+                    if !expired.is_empty() {
+                        debug!("evict {} idle pool subscriptions", expired.len());
+                    }
+                    for account in expired {
+                        unwatch(&cache, &mut watched, &mut subscriptions, &account);
+                    }
+                }
+                update = streams.next(), if !streams.is_empty() => {
+                    let Some((account, response)) = update else {
+                        break;
+                    };
+                    match response.value.decode::<Account>() {
+                        Some(data) => {
+                            if let Err(e) =
+                                cache.apply(account, &data.owner, &data.data, response.context.slot)
+                            {
+                                debug!("apply update failed: {:?}", e);
+                            }
+                        }
+                        None => warn!("undecodable update for {}", account),
+                    }
+                }
+            }
+            // 连接断开时所有订阅结束并从 streams 中移除，淘汰的订阅在下一次轮询时移除
+            if streams.len() < subscriptions.len() {
+                healthy = false;
+            }
+        }
+        warn!(
+            "Pool subscriptions to {} lost, reconnecting {} accounts",
+            ws_url,
+            watched.len()
+        );
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn subscribe(
+    client: &PubsubClient,
+    account: Pubkey,
+) -> Result<(AccountUpdates<'_>, Subscription)> {
+    let (stream, unsubscribe) = client
+        .account_subscribe(
+            &account,
+            Some(RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                // 池子状态以最快的 processed 为准，过期状态推导的账户在模拟时暴露
+                commitment: Some(CommitmentConfig::processed()),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    let (cancel, cancelled) = oneshot::channel();
+    Ok((
+        stream
+            .take_until(cancelled)
+            .map(move |response| (account, response))
+            .boxed(),
+        Subscription {
+            _cancel: cancel,
+            unsubscribe,
+        },
+    ))
+}
+
+/// 后台读取账户的当前状态，同一账户同时只有一个读取在进行
+fn spawn_load(cache: &Arc<PoolCache>, connection: Option<&Arc<RpcClient>>, account: Pubkey) {
+    let Some(connection) = connection.cloned() else {
+        return;
+    };
+    if !cache.loading.lock().unwrap().insert(account) {
+        return;
+    }
+    let cache = cache.clone();
+    tokio::spawn(async move {
+        load_account(&cache, &connection, &account).await;
+        cache.loading.lock().unwrap().remove(&account);
+    });
+}
+
+/// 订阅只推送之后的变更，账户的当前状态通过 RPC 读取
+async fn load_account(cache: &PoolCache, connection: &RpcClient, account: &Pubkey) {
+    match connection
+        .get_account_with_commitment(account, CommitmentConfig::processed())
+        .await
+    {
+        Ok(response) => match response.value {
+            Some(data) => {
+                if let Err(e) =
+                    cache.apply(*account, &data.owner, &data.data, response.context.slot)
+                {
+                    warn!("load pool {} failed: {:?}", account, e);
+                }
+            }
+            None => warn!("pool account {} not found", account),
+        },
+        Err(e) => warn!("load pool {} failed: {:?}", account, e),
+    }
+}
+
+/// 消费 Redis 频道推送的账户更新，断开后重连
+async fn run_channel_updates(cache: Arc<PoolCache>, channel: String) {
+    loop {
+        if let Err(e) = consume_channel_updates(&cache, &channel).await {
+            warn!("pool state channel {} failed: {:?}", channel, e);
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn consume_channel_updates(cache: &PoolCache, channel: &str) -> Result<()> {
+    let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "127.0.0.1:6379".to_string());
+    let mut pubsub = redis_client(&redis_url, 0)?.get_async_pubsub().await?;
+    pubsub.subscribe(channel).await?;
+    info!("Subscribed pool state channel {}", channel);
+
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let result = message
+            .get_payload::<String>()
+            .map_err(anyhow::Error::from)
+            .and_then(|payload| apply_channel_update(cache, &payload));
+        if let Err(e) = result {
+            warn!("pool state update from {} failed: {:?}", channel, e);
+        }
+    }
+    Err(anyhow::anyhow!("channel closed"))
+}
+
+fn apply_channel_update(cache: &PoolCache, payload: &str) -> Result<()> {
+    let update: PoolUpdate = serde_json::from_str(payload)?;
+    let data = base64::engine::general_purpose::STANDARD.decode(&update.data)?;
+    cache.apply(update.account, &update.owner, &data, update.slot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn program(id: &str) -> Pubkey {
+        Pubkey::from_str(id).unwrap()
+    }
+
+    /// 在 offset 处写入 pubkey，返回写入的值
+    fn put_key(data: &mut [u8], offset: usize) -> Pubkey {
+        let key = Pubkey::new_unique();
+        data[offset..offset + 32].copy_from_slice(key.as_ref());
+        key
+    }
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn decode_pool(owner: &str, data: &[u8]) -> PoolState {
+        match decode_account(&Pubkey::new_unique(), &program(owner), data).unwrap() {
+            CachedAccount::Pool(state) => state,
+            other => panic!("not a pool: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn whirlpool_offsets() {
+        let mut data = vec![0u8; WHIRLPOOL_SIZE];
+        put(&mut data, 41, &64u16.to_le_bytes());
+        put(&mut data, 81, &(-12345i32).to_le_bytes());
+        let mint_a = put_key(&mut data, 101);
+        let vault_a = put_key(&mut data, 133);
+        let vault_b = put_key(&mut data, 213);
+        let PoolState::Whirlpool(state) = decode_pool(WHIRLPOOL_PROGRAM_ID, &data) else {
+            panic!("not a whirlpool");
+        };
+        assert_eq!(state.tick_spacing, 64);
+        assert_eq!(state.tick_current_index, -12345);
+        assert_eq!(state.token_mint_a, mint_a);
+        assert_eq!(state.token_vault_a, vault_a);
+        assert_eq!(state.token_vault_b, vault_b);
+    }
+
+    #[test]
+    fn raydium_clmm_offsets() {
+        let mut data = vec![0u8; RAYDIUM_CLMM_POOL_SIZE];
+        let amm_config = put_key(&mut data, 9);
+        let mint_0 = put_key(&mut data, 73);
+        let vault_0 = put_key(&mut data, 137);
+        let vault_1 = put_key(&mut data, 169);
+        let observation = put_key(&mut data, 201);
+        put(&mut data, 235, &10u16.to_le_bytes());
+        put(&mut data, 269, &(-60i32).to_le_bytes());
+        let PoolState::RaydiumClmm(state) = decode_pool(RAYDIUM_CLMM_PROGRAM_ID, &data) else {
+            panic!("not a clmm pool");
+        };
+        assert_eq!(state.amm_config, amm_config);
+        assert_eq!(state.token_mint_0, mint_0);
+        assert_eq!(state.token_vault_0, vault_0);
+        assert_eq!(state.token_vault_1, vault_1);
+        assert_eq!(state.observation_key, observation);
+        assert_eq!(state.tick_spacing, 10);
+        assert_eq!(state.tick_current, -60);
+    }
+
+    #[test]
+    fn raydium_cpmm_offsets() {
+        let mut data = vec![0u8; RAYDIUM_CPMM_POOL_SIZE];
+        let amm_config = put_key(&mut data, 8);
+        let vault_0 = put_key(&mut data, 72);
+        let vault_1 = put_key(&mut data, 104);
+        let mint_0 = put_key(&mut data, 168);
+        let observation = put_key(&mut data, 296);
+        let PoolState::RaydiumCpmm(state) = decode_pool(RAYDIUM_CPMM_PROGRAM_ID, &data) else {
+            panic!("not a cpmm pool");
+        };
+        assert_eq!(state.amm_config, amm_config);
+        assert_eq!(state.token_0_vault, vault_0);
+        assert_eq!(state.token_1_vault, vault_1);
+        assert_eq!(state.token_0_mint, mint_0);
+        assert_eq!(state.observation_key, observation);
+    }
+
+    #[test]
+    fn meteora_offsets() {
+        let mut data = vec![0u8; METEORA_DLMM_LB_PAIR_SIZE];
+        put(&mut data, 76, &(-7i32).to_le_bytes());
+        let mint_x = put_key(&mut data, 88);
+        let reserve_x = put_key(&mut data, 152);
+        let reserve_y = put_key(&mut data, 184);
+        let oracle = put_key(&mut data, 552);
+        let PoolState::MeteoraDlmm(state) = decode_pool(METEORA_DLMM_PROGRAM_ID, &data) else {
+            panic!("not a lb pair");
+        };
+        assert_eq!(state.active_id, -7);
+        assert_eq!(state.token_x_mint, mint_x);
+        assert_eq!(state.reserve_x, reserve_x);
+        assert_eq!(state.reserve_y, reserve_y);
+        assert_eq!(state.oracle, oracle);
+
+        let mut data = vec![0u8; METEORA_CPAM_POOL_SIZE];
+        let vault_a = put_key(&mut data, 232);
+        let vault_b = put_key(&mut data, 264);
+        let PoolState::MeteoraCpam(state) = decode_pool(METEORA_CPAM_PROGRAM_ID, &data) else {
+            panic!("not a cpam pool");
+        };
+        assert_eq!(state.token_a_vault, vault_a);
+        assert_eq!(state.token_b_vault, vault_b);
+    }
+
+    #[test]
+    fn raydium_amm_and_market_offsets() {
+        let mut data = vec![0u8; RAYDIUM_AMM_INFO_SIZE];
+        let coin_vault = put_key(&mut data, 336);
+        let pc_vault = put_key(&mut data, 368);
+        let open_orders = put_key(&mut data, 496);
+        let market = put_key(&mut data, 528);
+        let market_program = put_key(&mut data, 560);
+        let account = Pubkey::new_unique();
+        let amm = match decode_account(&account, &program(RAYDIUM_AMM_PROGRAM_ID), &data) {
+            Ok(CachedAccount::RaydiumAmm(amm)) => amm,
+            other => panic!("not an amm: {:?}", other),
+        };
+        assert_eq!(amm.coin_vault, coin_vault);
+        assert_eq!(amm.pc_vault, pc_vault);
+        assert_eq!(amm.open_orders, open_orders);
+        assert_eq!(amm.market, market);
+        assert_eq!(amm.market_program, market_program);
+
+        let mut data = vec![0u8; SERUM_MARKET_MIN_SIZE + 12];
+        put(&mut data, 0, b"serum");
+        put(&mut data, 45, &3u64.to_le_bytes());
+        let coin_vault = put_key(&mut data, 117);
+        let pc_vault = put_key(&mut data, 165);
+        let event_queue = put_key(&mut data, 253);
+        let bids = put_key(&mut data, 285);
+        let asks = put_key(&mut data, 317);
+        let market = match decode_account(&market, &market_program, &data) {
+            Ok(CachedAccount::SerumMarket(market)) => market,
+            other => panic!("not a market: {:?}", other),
+        };
+        assert_eq!(market.vault_signer_nonce, 3);
+        assert_eq!(market.coin_vault, coin_vault);
+        assert_eq!(market.pc_vault, pc_vault);
+        assert_eq!(market.event_queue, event_queue);
+        assert_eq!(market.bids, bids);
+        assert_eq!(market.asks, asks);
+    }
+
+    #[test]
+    fn pump_offsets() {
+        let owner = program(PUMP_AMM_PROGRAM_ID);
+        let mut data = vec![0u8; PUMP_POOL_MIN_SIZE + 58];
+        let quote_mint = put_key(&mut data, 75);
+        let base_account = put_key(&mut data, 139);
+        let quote_account = put_key(&mut data, 171);
+        let coin_creator = put_key(&mut data, 211);
+        let pool = match decode_account(&Pubkey::new_unique(), &owner, &data) {
+            Ok(CachedAccount::Pump(pool)) => pool,
+            other => panic!("not a pump pool: {:?}", other),
+        };
+        assert_eq!(pool.quote_mint, quote_mint);
+        assert_eq!(pool.pool_base_token_account, base_account);
+        assert_eq!(pool.pool_quote_token_account, quote_account);
+        assert_eq!(pool.coin_creator, coin_creator);
+        assert!(decode_account(
+            &Pubkey::new_unique(),
+            &owner,
+            &data[..PUMP_POOL_MIN_SIZE - 1]
+        )
+        .is_err());
+
+        let mut data = vec![0u8; 321];
+        let recipient = put_key(&mut data, 57);
+        match decode_account(&pump_global_config(), &owner, &data) {
+            Ok(CachedAccount::PumpGlobalConfig {
+                protocol_fee_recipient,
+            }) => assert_eq!(protocol_fee_recipient, recipient),
+            other => panic!("not the global config: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_other_accounts() {
+        // 同一程序下的 tick array 等账户大小不同
+        let tick_array = vec![0u8; 9988];
+        assert!(decode_account(
+            &Pubkey::new_unique(),
+            &program(WHIRLPOOL_PROGRAM_ID),
+            &tick_array
+        )
+        .is_err());
+        assert!(decode_account(&Pubkey::new_unique(), &Pubkey::new_unique(), &[0u8; 64]).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use anchor_client::{
+    solana_client::nonblocking::rpc_client::RpcClient, solana_sdk::pubkey::Pubkey,
+};
+use futures::{stream, StreamExt};
+use log::{debug, warn};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+/// 每 CU 的优先费单位为 micro-lamports
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+/// 路由中的写账户最近一次出现后仍持续刷新的时间
+const ACCOUNT_TTL: Duration = Duration::from_secs(60);
+
+struct TrackedAccount {
+    /// getRecentPrioritizationFees 返回的各 slot 最低优先费
+    fees: Vec<(u64, u64)>,
+    last_used: Instant,
+}
+
+/// 优先费策略: 后台按 PRIORITY_FEE_REFRESH_MS 刷新近期路由中写账户的
+/// getRecentPrioritizationFees，每个 slot 取各账户的最大值，再取 PRIORITY_FEE_PERCENTILE 分位数。
+/// 总优先费不超过预期利润的 PRIORITY_FEE_MAX_PROFIT_PCT，利润不以 SOL 计时不超过上游给出的优先费。
+/// 没有 RPC 或写账户还没有数据时使用上游的优先费
+pub struct PriorityFees {
+    accounts: RwLock<HashMap<Pubkey, TrackedAccount>>,
+    percentile: f64,
+    max_profit_pct: u64,
+    /// 刷新时同时进行的查询数
+    concurrency: usize,
+}
+
+impl PriorityFees {
+    pub fn spawn_from_env(connection: Option<Arc<RpcClient>>) -> Arc<Self> {
+        let percentile = std::env::var("PRIORITY_FEE_PERCENTILE")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(75.0)
+            .clamp(0.0, 100.0);
+        let max_profit_pct = std::env::var("PRIORITY_FEE_MAX_PROFIT_PCT")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(50);
+        let refresh = std::env::var("PRIORITY_FEE_REFRESH_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(1_000);
+        let concurrency = std::env::var("PRIORITY_FEE_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(8)
+            .max(1);
+        let fees = Arc::new(PriorityFees {
+            accounts: RwLock::new(HashMap::new()),
+            percentile,
+            max_profit_pct,
+            concurrency,
+        });
+        if let Some(connection) = connection.filter(|_| refresh > 0) {
+            let fees = fees.clone();
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(Duration::from_millis(refresh));
+                loop {
+                    ticker.tick().await;
+                    fees.refresh(&connection).await;
+                }
+            });
+        }
+        fees
+    }
+
+    async fn refresh(&self, connection: &Arc<RpcClient>) {
+        let accounts: Vec<Pubkey> = {
+            let mut tracked = self.accounts.write().unwrap();
+            tracked.retain(|_, account| account.last_used.elapsed() < ACCOUNT_TTL);
+            tracked.keys().copied().collect()
+        };
+        // 多个账户一起查询时返回的是同时锁定所有账户的交易，因此逐个账户查询，
+        // 最多同时进行 PRIORITY_FEE_CONCURRENCY 个请求
+        let results: Vec<_> = stream::iter(accounts.into_iter().map(|account| {
+            let connection = connection.clone();
+            async move {
+                let result = connection.get_recent_prioritization_fees(&[account]).await;
+                (account, result)
+            }
+        }))
+        .buffer_unordered(self.concurrency)
+        .collect()
+        .await;
+        let mut tracked = self.accounts.write().unwrap();
+        for (account, result) in results {
+            match result {
+                Ok(fees) => {
+                    if let Some(tracked) = tracked.get_mut(&account) {
+                        tracked.fees = fees
+                            .into_iter()
+                            .map(|fee| (fee.slot, fee.prioritization_fee))
+                            .collect();
+                    }
+                }
+                Err(e) => warn!(
+                    "getRecentPrioritizationFees for {} failed: {:?}",
+                    account, e
+                ),
+            }
+        }
+    }
+
+    /// 写账户近期优先费的分位数 (micro-lamports)，没有数据时为 None。
+    /// 未跟踪的账户从下一次刷新开始跟踪
+    pub fn recent_fee(&self, accounts: &[Pubkey]) -> Option<u64> {
+        let mut tracked = self.accounts.write().unwrap();
+        let now = Instant::now();
+        // 交易需要同时锁定所有写账户，每个 slot 以竞争最激烈的账户为准
+        let mut by_slot: BTreeMap<u64, u64> = BTreeMap::new();
+        for account in accounts {
+            let tracked = tracked.entry(*account).or_insert_with(|| TrackedAccount {
+                fees: vec![],
+                last_used: now,
+            });
+            tracked.last_used = now;
+            for (slot, fee) in &tracked.fees {
+                let max = by_slot.entry(*slot).or_default();
+                *max = (*max).max(*fee);
+            }
+        }
+        if by_slot.is_empty() {
+            return None;
+        }
+        let mut fees: Vec<u64> = by_slot.into_values().collect();
+        fees.sort_unstable();
+        let index = ((fees.len() - 1) as f64 * self.percentile / 100.0).round() as usize;
+        Some(fees[index])
+    }
+
+    /// 交易的 compute unit price (micro-lamports)。
+    /// upstream_price 为上游给出的每 CU 优先费，profit_lamports 为以 SOL 计的预期利润
+    pub fn unit_price(
+        &self,
+        accounts: &[Pubkey],
+        unit_limit: u32,
+        upstream_price: u64,
+        profit_lamports: Option<i128>,
+    ) -> u64 {
+        let Some(recent) = self.recent_fee(accounts) else {
+            return upstream_price;
+        };
+        let cap = match profit_lamports {
+            Some(profit) => {
+                let cap_lamports = profit.max(0) as u128 * self.max_profit_pct as u128 / 100;
+                (cap_lamports * MICRO_LAMPORTS_PER_LAMPORT / unit_limit.max(1) as u128) as u64
+            }
+            None => upstream_price,
+        };
+        debug!(
+            "priority fee: recent {}, cap {}, upstream {}",
+            recent, cap, upstream_price
+        );
+        recent.min(cap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fees(percentile: f64, tracked: Vec<(Pubkey, Vec<(u64, u64)>)>) -> PriorityFees {
+        let now = Instant::now();
+        PriorityFees {
+            accounts: RwLock::new(
+                tracked
+                    .into_iter()
+                    .map(|(account, fees)| {
+                        (
+                            account,
+                            TrackedAccount {
+                                fees,
+                                last_used: now,
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+            percentile,
+            max_profit_pct: 50,
+            concurrency: 1,
+        }
+    }
+
+    #[test]
+    fn percentile_over_per_slot_maximum() {
+        let pool = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let tracked = vec![
+            (pool, vec![(1, 100), (2, 300), (3, 200)]),
+            (vault, vec![(2, 50), (3, 500), (4, 10)]),
+        ];
+        // 各 slot 最大值为 [100, 300, 500, 10]，排序后 [10, 100, 300, 500]
+        assert_eq!(
+            fees(75.0, tracked.clone()).recent_fee(&[pool, vault]),
+            Some(300)
+        );
+        assert_eq!(
+            fees(100.0, tracked.clone()).recent_fee(&[pool, vault]),
+            Some(500)
+        );
+        assert_eq!(
+            fees(0.0, tracked.clone()).recent_fee(&[pool, vault]),
+            Some(10)
+        );
+        assert_eq!(fees(50.0, tracked).recent_fee(&[pool]), Some(200));
+    }
+
+    #[test]
+    fn untracked_accounts_start_tracking() {
+        let fees = fees(75.0, vec![]);
+        let account = Pubkey::new_unique();
+        assert_eq!(fees.recent_fee(&[account]), None);
+        assert!(fees.accounts.read().unwrap().contains_key(&account));
+    }
+
+    #[test]
+    fn unit_price_capped_by_profit() {
+        let account = Pubkey::new_unique();
+        let fees = fees(75.0, vec![(account, vec![(1, 300)])]);
+        // 没有近期数据时使用上游的每 CU 优先费
+        assert_eq!(
+            fees.unit_price(&[Pubkey::new_unique()], 200_000, 50_000, None),
+            50_000
+        );
+        assert_eq!(
+            fees.unit_price(&[account], 200_000, 50_000, Some(1_000_000)),
+            300
+        );
+        // 利润不以 SOL 计时不超过上游的每 CU 优先费
+        assert_eq!(fees.unit_price(&[account], 200_000, 100, None), 100);
+        // 利润 10 lamports 的 50% 分摊到 200k CU
+        assert_eq!(fees.unit_price(&[account], 200_000, 50_000, Some(10)), 25);
+        assert_eq!(fees.unit_price(&[account], 200_000, 50_000, Some(-5)), 0);
+    }
+}
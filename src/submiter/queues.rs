@@ -0,0 +1,226 @@
+use anyhow::{Context, Result};
+use log::info;
+use serde::Deserialize;
+
+use crate::submiter::assembler::SendPath;
+
+/// 提交钱包池
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalletPool {
+    /// SUBMITTER_KEYS，代理账户 / Kamino 事件使用 PROXY_SUBMITTER_KEYS
+    #[default]
+    Default,
+    /// RPC_SUBMITTER_KEYS
+    Rpc,
+    /// PROXY_SUBMITTER_KEYS
+    Proxy,
+}
+
+/// 单个队列的消费与提交策略
+#[derive(Clone, Deserialize)]
+pub struct QueuePolicy {
+    /// 队列 / stream 名称
+    pub name: String,
+    /// list | stream | unix | file，未配置时使用 INGEST_MODE
+    #[serde(default = "default_ingest_mode")]
+    pub ingest_mode: String,
+    #[serde(default)]
+    pub wallet_pool: WalletPool,
+    /// 从该环境变量读取逗号分隔的私钥，配置后替代 wallet_pool
+    #[serde(default)]
+    pub wallet_env: Option<String>,
+    /// 不输出到日志
+    #[serde(skip)]
+    pub wallet_keys: Vec<String>,
+    #[serde(default = "default_send_path")]
+    pub send_path: SendPath,
+    #[serde(default = "default_parallelism")]
+    pub parallelism: usize,
+    #[serde(default = "default_staleness_budget_ms")]
+    pub staleness_budget_ms: i64,
+}
+
+#[derive(Deserialize)]
+struct QueueConfigFile {
+    queues: Vec<QueuePolicy>,
+}
+
+fn default_ingest_mode() -> String {
+    std::env::var("INGEST_MODE").unwrap_or_else(|_| "list".to_string())
+}
+
+fn default_send_path() -> SendPath {
+    SendPath::Auto
+}
+
+fn default_parallelism() -> usize {
+    std::env::var("PARALLELISM")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(1)
+}
+
+fn default_staleness_budget_ms() -> i64 {
+    std::env::var("STALENESS_BUDGET_MS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(1000)
+}
+
+impl QueuePolicy {
+    /// 未使用配置文件时的单队列策略: PARALLELISM / STALENESS_BUDGET_MS / SEND_PATH / WALLET_POOL
+    pub fn from_env(name: &str) -> Result<Self> {
+        let send_path = match std::env::var("SEND_PATH") {
+            Ok(value) => serde_yaml::from_str(&value)
+                .with_context(|| format!("Unsupported SEND_PATH: {}", value))?,
+            Err(_) => default_send_path(),
+        };
+        let wallet_pool = match std::env::var("WALLET_POOL") {
+            Ok(value) => serde_yaml::from_str(&value)
+                .with_context(|| format!("Unsupported WALLET_POOL: {}", value))?,
+            Err(_) => WalletPool::Default,
+        };
+        let mut policy = QueuePolicy {
+            name: name.to_string(),
+            ingest_mode: default_ingest_mode(),
+            wallet_pool,
+            wallet_env: std::env::var("WALLET_ENV").ok(),
+            wallet_keys: vec![],
+            send_path,
+            parallelism: default_parallelism(),
+            staleness_budget_ms: default_staleness_budget_ms(),
+        };
+        policy.load_wallet_keys()?;
+        Ok(policy)
+    }
+
+    fn load_wallet_keys(&mut self) -> Result<()> {
+        if let Some(wallet_env) = &self.wallet_env {
+            let keys = std::env::var(wallet_env)
+                .with_context(|| format!("queue {} 需要设置 {}", self.name, wallet_env))?;
+            self.wallet_keys = keys
+                .split(',')
+                .map(|key| key.trim().to_string())
+                .filter(|key| !key.is_empty())
+                .collect();
+            if self.wallet_keys.is_empty() {
+                return Err(anyhow::anyhow!("{} has no wallet keys", wallet_env));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 读取 QUEUE_CONFIG 指定的 YAML 文件，未设置时只消费 REDIS_QUEUE_NAME
+pub fn load_queue_policies() -> Result<Vec<QueuePolicy>> {
+    let path = match std::env::var("QUEUE_CONFIG") {
+        Ok(path) if !path.is_empty() => path,
+        _ => {
+            let name = match default_ingest_mode().as_str() {
+                "unix" => std::env::var("UNIX_SOCKET_PATH")
+                    .unwrap_or_else(|_| "/tmp/arbi_submiter.sock".to_string()),
+                "file" => {
+                    std::env::var("EVENT_FILE").context("INGEST_MODE=file 需要设置 EVENT_FILE")?
+                }
+                // 单队列的 stream 模式可用 REDIS_STREAM_NAME 单独指定 stream 名
+                "stream" => std::env::var("REDIS_STREAM_NAME")
+                    .or_else(|_| std::env::var("REDIS_QUEUE_NAME"))
+                    .unwrap_or_else(|_| "arbi_swap_queue".to_string()),
+                _ => std::env::var("REDIS_QUEUE_NAME")
+                    .unwrap_or_else(|_| "arbi_swap_queue".to_string()),
+            };
+            return Ok(vec![QueuePolicy::from_env(&name)?]);
+        }
+    };
+
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("读取配置失败: {}", path))?;
+    let config: QueueConfigFile =
+        serde_yaml::from_str(&content).with_context(|| format!("解析配置失败: {}", path))?;
+    if config.queues.is_empty() {
+        return Err(anyhow::anyhow!("{} has no queues", path));
+    }
+
+    let mut policies = config.queues;
+    for policy in policies.iter_mut() {
+        policy.parallelism = policy.parallelism.max(1);
+        policy.load_wallet_keys()?;
+        info!(
+            "Queue {}: ingest_mode: {}, wallet_pool: {:?}, wallet_env: {:?}, send_path: {:?}, parallelism: {}, staleness_budget_ms: {}",
+            policy.name,
+            policy.ingest_mode,
+            policy.wallet_pool,
+            policy.wallet_env,
+            policy.send_path,
+            policy.parallelism,
+            policy.staleness_budget_ms
+        );
+    }
+    Ok(policies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Vec<QueuePolicy> {
+        serde_yaml::from_str::<QueueConfigFile>(yaml)
+            .unwrap()
+            .queues
+    }
+
+    #[test]
+    fn sample_config_parses() {
+        let content = std::fs::read_to_string("config/queues.yaml").unwrap();
+        let queues = parse(&content);
+        assert_eq!(queues.len(), 2);
+        assert_eq!(queues[0].name, "arbi_swap_queue");
+        assert_eq!(queues[0].wallet_pool, WalletPool::Default);
+        assert_eq!(queues[0].send_path, SendPath::Auto);
+        assert_eq!(queues[0].parallelism, 4);
+        assert_eq!(queues[1].name, "arbi_swap_queue_rpc");
+        assert_eq!(queues[1].wallet_pool, WalletPool::Rpc);
+        assert_eq!(queues[1].send_path, SendPath::Rpc);
+        assert_eq!(queues[1].staleness_budget_ms, 800);
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let queues = parse("queues:\n  - name: only_name\n");
+        let policy = &queues[0];
+        assert_eq!(policy.ingest_mode, default_ingest_mode());
+        assert_eq!(policy.wallet_pool, WalletPool::Default);
+        assert_eq!(policy.wallet_env, None);
+        assert!(policy.wallet_keys.is_empty());
+        assert_eq!(policy.send_path, SendPath::Auto);
+        assert_eq!(policy.parallelism, default_parallelism());
+        assert_eq!(policy.staleness_budget_ms, default_staleness_budget_ms());
+    }
+
+    #[test]
+    fn unknown_send_path_is_rejected() {
+        assert!(serde_yaml::from_str::<QueueConfigFile>(
+            "queues:\n  - name: q\n    send_path: carrier_pigeon\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn wallet_env_replaces_wallet_pool() {
+        std::env::set_var("ARBI_SUBMITER_TEST_QUEUE_KEYS", " key1, ,key2 ");
+        let mut policy = parse(
+            "queues:\n  - name: q\n    wallet_pool: rpc\n    wallet_env: ARBI_SUBMITER_TEST_QUEUE_KEYS\n",
+        )
+        .remove(0);
+        policy.load_wallet_keys().unwrap();
+        assert_eq!(policy.wallet_keys, vec!["key1", "key2"]);
+
+        std::env::set_var("ARBI_SUBMITER_TEST_QUEUE_EMPTY", " , ");
+        policy.wallet_env = Some("ARBI_SUBMITER_TEST_QUEUE_EMPTY".to_string());
+        assert!(policy.load_wallet_keys().is_err());
+
+        policy.wallet_env = Some("ARBI_SUBMITER_TEST_QUEUE_UNSET".to_string());
+        assert!(policy.load_wallet_keys().is_err());
+    }
+}
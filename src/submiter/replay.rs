@@ -0,0 +1,257 @@
+use anyhow::{Context, Result};
+use log::{error, info};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, BufReader},
+    sync::Semaphore,
+    task::JoinSet,
+};
+
+use crate::submiter::assembler::SendPath;
+use crate::submiter::queues::QueuePolicy;
+use crate::submiter::submitter::{check_stream_ts, decode_event, submit_event, SubmitContext};
+
+const REPLAY_USAGE: &str = "usage: replay <file> [--mode simulate|dry-run|real] [--speed <x>|max] [--stream-ts keep|ignore|rewrite]";
+
+/// 回放时对 stream_ts 超时检查的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamTsPolicy {
+    /// 保留原始 stream_ts，超时的事件被丢弃
+    Keep,
+    /// 跳过超时检查
+    Ignore,
+    /// 提交前将 stream_ts 改写为当前时间
+    Rewrite,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+    pub path: String,
+    pub send_path: SendPath,
+    /// 回放倍速，None 表示不等待，尽快回放
+    pub speed: Option<f64>,
+    pub stream_ts: StreamTsPolicy,
+    pub parallelism: usize,
+}
+
+impl ReplayOptions {
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let path = args.first().context(REPLAY_USAGE)?.clone();
+        let mut options = ReplayOptions {
+            path,
+            send_path: SendPath::Simulate,
+            speed: Some(1.0),
+            stream_ts: StreamTsPolicy::Rewrite,
+            parallelism: std::env::var("PARALLELISM")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(1),
+        };
+
+        let mut iter = args[1..].iter();
+        while let Some(flag) = iter.next() {
+            let value = iter
+                .next()
+                .with_context(|| format!("missing value for {}", flag))?;
+            match flag.as_str() {
+                "--mode" => {
+                    options.send_path = match value.as_str() {
+                        "simulate" => SendPath::Simulate,
+                        "dry-run" => SendPath::DryRun,
+                        "real" => SendPath::Auto,
+                        other => return Err(anyhow::anyhow!("Unsupported replay mode: {}", other)),
+                    }
+                }
+                "--speed" => {
+                    options.speed = if value == "max" {
+                        None
+                    } else {
+                        let speed = value.parse::<f64>()?;
+                        if speed <= 0.0 {
+                            return Err(anyhow::anyhow!("--speed must be positive"));
+                        }
+                        Some(speed)
+                    }
+                }
+                "--stream-ts" => {
+                    options.stream_ts = match value.as_str() {
+                        "keep" => StreamTsPolicy::Keep,
+                        "ignore" => StreamTsPolicy::Ignore,
+                        "rewrite" => StreamTsPolicy::Rewrite,
+                        other => return Err(anyhow::anyhow!("Unsupported --stream-ts: {}", other)),
+                    }
+                }
+                other => return Err(anyhow::anyhow!("Unknown replay option: {}", other)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// 按文件中的顺序回放事件，每行一条 base64 + LZ4 或明文 JSON 的 ArbiEvent，
+/// 事件间隔取自相邻 stream_ts 之差再除以倍速
+pub async fn replay(options: ReplayOptions) -> Result<()> {
+    info!("Replay {} with {:?}", options.path, options);
+    let file = File::open(&options.path)
+        .await
+        .with_context(|| format!("打开文件失败: {}", options.path))?;
+    let mut lines = BufReader::new(file).lines();
+
+    let context = SubmitContext::init()?;
+    // 钱包池与超时预算沿用 WALLET_POOL / STALENESS_BUDGET_MS，提交方式取自 --mode
+    let mut policy = QueuePolicy::from_env(&options.path)?;
+    policy.send_path = options.send_path;
+    let policy = Arc::new(policy);
+    let semaphore = Arc::new(Semaphore::new(options.parallelism));
+    let mut tasks = JoinSet::new();
+    // 第一条事件的 stream_ts 与回放开始时间
+    let mut origin: Option<(i64, Instant)> = None;
+    let mut line_no = 0;
+    let mut skipped = 0;
+
+    while let Some(line) = lines.next_line().await? {
+        line_no += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let key = format!("{}:{}", options.path, line_no);
+        let mut arbi_event = match decode_event(&key, line.trim(), context.pool_state_source()) {
+            Ok(arbi_event) => arbi_event,
+            Err(e) => {
+                error!("line {} decode failed: {:?}", line_no, e);
+                skipped += 1;
+                continue;
+            }
+        };
+
+        if let Some(speed) = options.speed {
+            match origin {
+                None => origin = Some((arbi_event.stream_ts, Instant::now())),
+                Some((origin_ts, started)) => {
+                    let offset_ms = (arbi_event.stream_ts - origin_ts).max(0) as f64 / speed;
+                    let due = started + Duration::from_millis(offset_ms as u64);
+                    tokio::time::sleep_until(due.into()).await;
+                }
+            }
+        }
+
+        let now_ts = now_ms();
+        match options.stream_ts {
+            StreamTsPolicy::Keep => {
+                if let Err(e) = check_stream_ts(&arbi_event, now_ts, policy.staleness_budget_ms) {
+                    error!("line {} skipped: {:?}", line_no, e);
+                    skipped += 1;
+                    continue;
+                }
+            }
+            StreamTsPolicy::Ignore => {}
+            StreamTsPolicy::Rewrite => arbi_event.stream_ts = now_ts,
+        }
+
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let context_clone = context.clone();
+        let policy_clone = policy.clone();
+        tasks.spawn(async move {
+            let trace_id = arbi_event.trace_id.clone();
+            if let Err(e) = submit_event(
+                &key,
+                arbi_event,
+                &context_clone,
+                &policy_clone,
+                now_ts,
+                now_ts,
+            )
+            .await
+            {
+                error!("replay {} trace_id: {} failed: {:?}", key, trace_id, e);
+            }
+            drop(permit);
+        });
+    }
+
+    while tasks.join_next().await.is_some() {}
+    info!(
+        "Replay {} finished, lines: {}, skipped: {}",
+        options.path, line_no, skipped
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn defaults_to_simulate_at_original_speed_with_rewrite() {
+        let options = ReplayOptions::from_args(&args(&["events.jsonl"])).unwrap();
+        assert_eq!(options.path, "events.jsonl");
+        assert_eq!(options.send_path, SendPath::Simulate);
+        assert_eq!(options.speed, Some(1.0));
+        assert_eq!(options.stream_ts, StreamTsPolicy::Rewrite);
+    }
+
+    #[test]
+    fn parses_mode_speed_and_stream_ts() {
+        let options = ReplayOptions::from_args(&args(&[
+            "events.jsonl",
+            "--mode",
+            "dry-run",
+            "--speed",
+            "max",
+            "--stream-ts",
+            "keep",
+        ]))
+        .unwrap();
+        assert_eq!(options.send_path, SendPath::DryRun);
+        assert_eq!(options.speed, None);
+        assert_eq!(options.stream_ts, StreamTsPolicy::Keep);
+
+        let options = ReplayOptions::from_args(&args(&[
+            "events.jsonl",
+            "--mode",
+            "real",
+            "--speed",
+            "2.5",
+            "--stream-ts",
+            "ignore",
+        ]))
+        .unwrap();
+        assert_eq!(options.send_path, SendPath::Auto);
+        assert_eq!(options.speed, Some(2.5));
+        assert_eq!(options.stream_ts, StreamTsPolicy::Ignore);
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        for invalid in [
+            vec![],
+            vec!["events.jsonl", "--mode"],
+            vec!["events.jsonl", "--mode", "jito"],
+            vec!["events.jsonl", "--speed", "0"],
+            vec!["events.jsonl", "--speed", "fast"],
+            vec!["events.jsonl", "--stream-ts", "drop"],
+            vec!["events.jsonl", "--loop", "1"],
+        ] {
+            assert!(
+                ReplayOptions::from_args(&args(&invalid)).is_err(),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+}
@@ -0,0 +1,448 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::submiter::amount::{Amount, Rounding};
+use crate::submiter::assembler::{ArbiEvent, SwapAccounts, TransactionDetail};
+use crate::submiter::derive::{derive_dex_account, PoolStateSource};
+use crate::submiter::dex::{DexAccount, DexType};
+use crate::submiter::mints::MintInfo;
+use crate::submiter::validate::{check_len, ValidationError};
+use crate::submiter::wire::pubkey_serde;
+
+/// 路由中的一次 swap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Leg {
+    /// 池子账户，JSON 中以 dexType 字段标记 DEX
+    pub dex: DexAccount,
+    /// swap 方向，true 为 token a -> token b
+    pub a_to_b: bool,
+    /// 反向 leg 的输入计入 token_b_amount_list，正向 leg 计入 token_a_amount_list
+    #[serde(default)]
+    pub reverse: bool,
+    /// 输入数量，按 decimals 换算前的值或最小单位
+    pub input_amount: Amount,
+    /// 上游给出的输入 token 精度，仅用于与 mint 注册表比对，组装时不使用
+    #[serde(default)]
+    pub decimals: Option<u8>,
+    /// 预期输出数量 (最小单位)
+    pub expected_output: u64,
+}
+
+impl Leg {
+    pub fn dex_type(&self) -> DexType {
+        self.dex.dex_type()
+    }
+
+    /// 按输入 token 的精度换算为最小单位，向下取整
+    pub fn input_units(&self, decimals: u8) -> Result<u64> {
+        self.input_amount.to_units(decimals, Rounding::Down)
+    }
+
+    /// 合约中的 same_ab: 正向 leg 与方向相同，反向 leg 相反
+    pub fn same_a_b(&self) -> bool {
+        self.a_to_b ^ self.reverse
+    }
+}
+
+/// 只给出池子地址的 leg，账户由 derive 模块按缓存的池子状态推导
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolLeg {
+    pub dex_type: DexType,
+    #[serde(with = "pubkey_serde")]
+    pub pool: Pubkey,
+    pub a_to_b: bool,
+    #[serde(default)]
+    pub reverse: bool,
+    pub input_amount: Amount,
+    #[serde(default)]
+    pub decimals: Option<u8>,
+    pub expected_output: u64,
+}
+
+/// 套利路由，正向 leg 在前、反向 leg 在后 (由 validate_event 保证)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Route {
+    #[serde(default)]
+    pub legs: Vec<Leg>,
+    /// 与 legs 二选一，解码后由 resolve_pools 转换为 legs
+    #[serde(default)]
+    pub pools: Vec<PoolLeg>,
+}
+
+/// ArbiArgs 中与路由相关的列表
+pub struct RouteArgs {
+    pub dex_type_list: Vec<u8>,
+    pub same_ab_list: Vec<bool>,
+    pub token_a_amount_list: Vec<u64>,
+    pub token_b_amount_list: Vec<u64>,
+    pub token_output_amount_list: Vec<u64>,
+}
+
+impl Route {
+    pub fn forward_legs(&self) -> impl Iterator<Item = &Leg> {
+        self.legs.iter().filter(|leg| !leg.reverse)
+    }
+
+    pub fn reverse_legs(&self) -> impl Iterator<Item = &Leg> {
+        self.legs.iter().filter(|leg| leg.reverse)
+    }
+
+    /// 按池子状态推导 pools 中每个 leg 的账户并转换为 legs，mint_a 为事件中的 token A
+    pub fn resolve_pools(
+        &mut self,
+        mint_a: &Pubkey,
+        pool_states: Option<&dyn PoolStateSource>,
+    ) -> Result<()> {
+        if self.pools.is_empty() {
+            return Ok(());
+        }
+        if !self.legs.is_empty() {
+            return Err(anyhow::anyhow!(
+                "route: legs and pools cannot be given together"
+            ));
+        }
+        let pool_states = pool_states
+            .ok_or_else(|| anyhow::anyhow!("route.pools: no pool state source configured"))?;
+        self.legs = self
+            .pools
+            .drain(..)
+            .enumerate()
+            .map(|(index, pool)| {
+                let snapshot = pool_states.pool_state(&pool.pool).ok_or_else(|| {
+                    anyhow::anyhow!("route.pools[{}]: no state for pool {}", index, pool.pool)
+                })?;
+                let dex = derive_dex_account(
+                    pool.dex_type,
+                    &pool.pool,
+                    &snapshot.state,
+                    mint_a,
+                    pool.a_to_b,
+                    pool_states,
+                )
+                .map_err(|e| anyhow::anyhow!("route.pools[{}]: {}", index, e))?;
+                Ok(Leg {
+                    dex,
+                    a_to_b: pool.a_to_b,
+                    reverse: pool.reverse,
+                    input_amount: pool.input_amount,
+                    decimals: pool.decimals,
+                    expected_output: pool.expected_output,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(())
+    }
+
+    /// 正向 leg 的输入为 token A，反向 leg 的输入为 token B
+    pub fn to_args(&self, decimals_a: u8, decimals_b: u8) -> Result<RouteArgs> {
+        Ok(RouteArgs {
+            dex_type_list: self.legs.iter().map(|leg| leg.dex_type().to_u8()).collect(),
+            same_ab_list: self.legs.iter().map(Leg::same_a_b).collect(),
+            token_a_amount_list: self
+                .forward_legs()
+                .map(|leg| leg.input_units(decimals_a))
+                .collect::<Result<_>>()?,
+            token_b_amount_list: self
+                .reverse_legs()
+                .map(|leg| leg.input_units(decimals_b))
+                .collect::<Result<_>>()?,
+            token_output_amount_list: self.legs.iter().map(|leg| leg.expected_output).collect(),
+        })
+    }
+}
+
+/// 路由中 token A / B 转账被扣留的手续费 (最小单位)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WithheldFees {
+    pub token_a: u64,
+    pub token_b: u64,
+}
+
+impl WithheldFees {
+    pub fn is_zero(&self) -> bool {
+        self.token_a == 0 && self.token_b == 0
+    }
+}
+
+impl RouteArgs {
+    /// 按 Token-2022 转账手续费调整每个 leg 的预期输出: 池子收到的是扣费后的输入，
+    /// 预期输出按比例缩减后再扣除输出 token 的手续费
+    pub fn apply_transfer_fees(
+        &mut self,
+        route: &Route,
+        mint_a: &MintInfo,
+        mint_b: &MintInfo,
+    ) -> WithheldFees {
+        let mut withheld = WithheldFees::default();
+        let mut token_a_amounts = self.token_a_amount_list.iter().copied();
+        let mut token_b_amounts = self.token_b_amount_list.iter().copied();
+        for (leg, output) in route
+            .legs
+            .iter()
+            .zip(self.token_output_amount_list.iter_mut())
+        {
+            let (input, input_fee, output_fee) = if leg.reverse {
+                (
+                    token_b_amounts.next().unwrap_or_default(),
+                    mint_b.transfer_fee,
+                    mint_a.transfer_fee,
+                )
+            } else {
+                (
+                    token_a_amounts.next().unwrap_or_default(),
+                    mint_a.transfer_fee,
+                    mint_b.transfer_fee,
+                )
+            };
+            let input_fee = input_fee.map_or(0, |rate| rate.fee(input));
+            let scaled = if input == 0 {
+                *output
+            } else {
+                (*output as u128 * (input - input_fee) as u128 / input as u128) as u64
+            };
+            let output_fee = output_fee.map_or(0, |rate| rate.fee(scaled));
+            *output = scaled - output_fee;
+
+            let (input_withheld, output_withheld) = if leg.reverse {
+                (&mut withheld.token_b, &mut withheld.token_a)
+            } else {
+                (&mut withheld.token_a, &mut withheld.token_b)
+            };
+            *input_withheld += input_fee;
+            *output_withheld += output_fee;
+        }
+        withheld
+    }
+
+    /// 以 token A 计的预期利润: 反向 leg 的预期输出之和减去正向 leg 的输入之和，没有反向 leg 时为 None
+    pub fn expected_profit(&self, route: &Route) -> Option<i128> {
+        route.reverse_legs().next()?;
+        let returned: i128 = route
+            .legs
+            .iter()
+            .zip(&self.token_output_amount_list)
+            .filter(|(leg, _)| leg.reverse)
+            .map(|(_, output)| *output as i128)
+            .sum();
+        let spent: i128 = self.token_a_amount_list.iter().map(|a| *a as i128).sum();
+        Some(returned - spent)
+    }
+}
+
+/// schema_version 1 的事件: leg 的字段分散在并行数组中，前 input_amounts.len() 个为正向 leg，
+/// 方向为 !(same_a_b[i] ^ 正向)
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyArbiEvent {
+    pub accounts: SwapAccounts,
+    pub dex_types: Vec<DexType>,
+    pub decimals: Vec<u8>,
+    pub transaction: TransactionDetail,
+    pub same_a_b: Vec<bool>,
+    pub input_amounts: Vec<Amount>,
+    pub reverse_input_amounts: Vec<Amount>,
+    pub token_output_amounts: Vec<u64>,
+    pub is_token_b_2022: bool,
+    pub blockhash: String,
+    pub trace_id: String,
+    pub submit_count: u64,
+    pub stream_ts: i64,
+}
+
+impl LegacyArbiEvent {
+    /// 转换为 Route 结构，各数组长度不一致时报错
+    pub fn into_event(self) -> Result<ArbiEvent, ValidationError> {
+        let legs = self.accounts.dexes.len();
+        check_len("dexTypes", legs, self.dex_types.len())?;
+        check_len("sameAB", legs, self.same_a_b.len())?;
+        check_len("decimals", legs, self.decimals.len())?;
+        check_len("tokenOutputAmounts", legs, self.token_output_amounts.len())?;
+        check_len(
+            "inputAmounts + reverseInputAmounts",
+            legs,
+            self.input_amounts.len() + self.reverse_input_amounts.len(),
+        )?;
+
+        let forward = self.input_amounts.len();
+        let input_amounts = self
+            .input_amounts
+            .into_iter()
+            .chain(self.reverse_input_amounts);
+        let legs = self
+            .accounts
+            .dexes
+            .into_iter()
+            .zip(self.same_a_b)
+            .zip(self.decimals)
+            .zip(self.token_output_amounts)
+            .zip(input_amounts)
+            .enumerate()
+            .map(
+                |(index, ((((dex, same_a_b), decimals), expected_output), input_amount))| {
+                    let reverse = index >= forward;
+                    Leg {
+                        dex,
+                        a_to_b: same_a_b ^ reverse,
+                        reverse,
+                        input_amount,
+                        decimals: Some(decimals),
+                        expected_output,
+                    }
+                },
+            )
+            .collect();
+
+        Ok(ArbiEvent {
+            schema_version: 1,
+            common_accounts: self.accounts.common_accounts,
+            route: Route {
+                legs,
+                pools: vec![],
+            },
+            transaction: self.transaction,
+            is_token_b_2022: self.is_token_b_2022,
+            blockhash: self.blockhash,
+            trace_id: self.trace_id,
+            submit_count: self.submit_count,
+            stream_ts: self.stream_ts,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::submiter::mints::TransferFeeRate;
+    use crate::submiter::wire::parse_json_event;
+    use serde_json::{json, Value};
+
+    fn key() -> String {
+        Pubkey::new_unique().to_string()
+    }
+
+    fn cpmm() -> Value {
+        json!({
+            "authority": key(),
+            "ammConfig": key(),
+            "poolState": key(),
+            "inputVault": key(),
+            "outputVault": key(),
+            "observationState": key(),
+        })
+    }
+
+    /// schema_version 1 的两 leg 事件: 1 token A -> 2 token B，再以 2 token B 换回 1.01 token A
+    pub(crate) fn legacy_json() -> Value {
+        json!({
+            "accounts": {
+                "commonAccounts": {
+                    "tokenVaultAMint": key(),
+                    "tokenVaultBMint": key(),
+                    "vault": key(),
+                    "userTokenAccountA": key(),
+                    "userTokenAccountB": key(),
+                },
+                "dexes": [cpmm(), cpmm()],
+            },
+            "dexTypes": ["raydiumCpmm", "raydiumCpmm"],
+            "decimals": [6, 6],
+            "transaction": {
+                "minProfit": "0.001",
+                "jitoTipRatio": 50,
+                "jitoTip": 0.0001,
+                "priorityFee": 0.00001,
+            },
+            "sameAB": [true, true],
+            "inputAmounts": [1.0],
+            "reverseInputAmounts": ["2"],
+            "tokenOutputAmounts": [2_000_000, 1_010_000],
+            "isTokenB2022": false,
+            "blockhash": "11111111111111111111111111111111",
+            "traceId": "trace",
+            "submitCount": 0,
+            "streamTs": 1_700_000_000_000i64,
+        })
+    }
+
+    fn legacy_event() -> ArbiEvent {
+        parse_json_event(&legacy_json().to_string()).unwrap()
+    }
+
+    fn mint(transfer_fee: Option<TransferFeeRate>) -> MintInfo {
+        MintInfo {
+            decimals: 6,
+            token_program: spl_token_2022::ID,
+            profit_decimals: 6,
+            transfer_hook_program: None,
+            transfer_fee,
+        }
+    }
+
+    #[test]
+    fn legacy_same_a_b_becomes_direction() {
+        let event = legacy_event();
+        let legs = &event.route.legs;
+        assert_eq!(event.schema_version, 1);
+        assert_eq!(legs.len(), 2);
+        // 正向 leg: a_to_b = sameAB，反向 leg: a_to_b = !sameAB
+        assert!(!legs[0].reverse && legs[0].a_to_b);
+        assert!(legs[1].reverse && !legs[1].a_to_b);
+        // 转回合约参数时 same_ab 与原始数组一致
+        let args = event.route.to_args(6, 6).unwrap();
+        assert_eq!(args.same_ab_list, vec![true, true]);
+        assert_eq!(args.dex_type_list, vec![2, 2]);
+        assert_eq!(args.token_a_amount_list, vec![1_000_000]);
+        assert_eq!(args.token_b_amount_list, vec![2_000_000]);
+        assert_eq!(args.token_output_amount_list, vec![2_000_000, 1_010_000]);
+    }
+
+    #[test]
+    fn legacy_reverse_leg_flips_direction() {
+        let mut json = legacy_json();
+        json["sameAB"] = json!([false, false]);
+        let legs = parse_json_event(&json.to_string()).unwrap().route.legs;
+        assert!(!legs[0].a_to_b);
+        assert!(legs[1].a_to_b);
+        assert!(legs.iter().all(|leg| !leg.same_a_b()));
+    }
+
+    #[test]
+    fn legacy_length_mismatch_is_rejected() {
+        let mut json = legacy_json();
+        json["sameAB"] = json!([true]);
+        assert!(parse_json_event(&json.to_string()).is_err());
+        let mut json = legacy_json();
+        json["reverseInputAmounts"] = json!([]);
+        assert!(parse_json_event(&json.to_string()).is_err());
+    }
+
+    #[test]
+    fn transfer_fees_scale_expected_outputs() {
+        let event = legacy_event();
+        let mut args = event.route.to_args(6, 6).unwrap();
+        let mint_a = mint(Some(TransferFeeRate {
+            basis_points: 100,
+            maximum_fee: u64::MAX,
+        }));
+        let withheld = args.apply_transfer_fees(&event.route, &mint_a, &mint(None));
+        // 池子收到 990_000，输出按比例缩减为 1_980_000；
+        // 反向 leg 输出 1_010_000 再扣 1% 为 999_900
+        assert_eq!(args.token_output_amount_list, vec![1_980_000, 999_900]);
+        assert_eq!(withheld.token_a, 10_000 + 10_100);
+        assert_eq!(withheld.token_b, 0);
+        assert_eq!(args.expected_profit(&event.route), Some(-100));
+    }
+
+    #[test]
+    fn transfer_fees_without_extension_keep_outputs() {
+        let event = legacy_event();
+        let mut args = event.route.to_args(6, 6).unwrap();
+        let withheld = args.apply_transfer_fees(&event.route, &mint(None), &mint(None));
+        assert!(withheld.is_zero());
+        assert_eq!(args.token_output_amount_list, vec![2_000_000, 1_010_000]);
+        assert_eq!(args.expected_profit(&event.route), Some(10_000));
+    }
+}
@@ -0,0 +1,506 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use log::{debug, warn};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+use tokio::sync::Notify;
+
+use crate::submiter::assembler::ArbiEvent;
+use crate::submiter::metrics::{Metrics, METRICS};
+use crate::submiter::mints::MintInfo;
+use crate::submiter::source::RawEvent;
+
+/// 积压超过上限时的丢弃策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShedPolicy {
+    /// drop_oldest: 丢弃最早进入队列的事件
+    Oldest,
+    /// drop_newest: 丢弃新到的事件
+    Newest,
+    /// drop_lowest_profit: 丢弃利润最低的事件
+    LowestProfit,
+}
+
+impl ShedPolicy {
+    fn from_env() -> Self {
+        match std::env::var("SHED_POLICY")
+            .unwrap_or_else(|_| "drop_oldest".to_string())
+            .as_str()
+        {
+            "drop_newest" => ShedPolicy::Newest,
+            "drop_lowest_profit" => ShedPolicy::LowestProfit,
+            "drop_oldest" => ShedPolicy::Oldest,
+            other => {
+                warn!("Unsupported SHED_POLICY {}, use drop_oldest", other);
+                ShedPolicy::Oldest
+            }
+        }
+    }
+}
+
+/// 已解码、等待执行的事件
+pub struct PendingEvent {
+    pub raw: RawEvent,
+    pub arbi_event: ArbiEvent,
+    pub expected_profit: f64,
+    /// 按权重折算后的输入量，利润相同时优先规模大的机会
+    pub notional: f64,
+    /// 路由指纹，用于合并重复的机会
+    pub fingerprint: String,
+    /// 去重窗口内的后续事件，只能替换仍在排队的同一路由，不单独排队
+    pub replace_only: bool,
+}
+
+impl PendingEvent {
+    /// mint_a 为 token A 的 mint 信息，用于折算利润与输入规模
+    pub fn new(raw: RawEvent, arbi_event: ArbiEvent, mint_a: &MintInfo) -> Self {
+        let expected_profit = expected_profit(&arbi_event, mint_a);
+        let notional = arbi_event
+            .route
+            .forward_legs()
+            .next()
+            .map(|leg| leg.input_amount.to_f64(mint_a.decimals))
+            .unwrap_or_default()
+            * token_weight(&arbi_event.common_accounts.token_vault_a_mint);
+        let fingerprint = arbi_event.route_fingerprint();
+        PendingEvent {
+            raw,
+            arbi_event,
+            expected_profit,
+            notional,
+            fingerprint,
+            replace_only: false,
+        }
+    }
+
+    /// 距离 stream_ts 超时还剩的时间 (ms)
+    pub fn remaining_ms(&self, now_ts: i64, budget_ms: i64) -> i64 {
+        budget_ms - (now_ts - self.arbi_event.stream_ts)
+    }
+}
+
+/// 以 token A 计价的 min_profit 乘以该 token 的权重 (PROFIT_TOKEN_WEIGHTS)，
+/// 用于比较不同 token 的机会，未配置的 token 权重为 1
+pub fn expected_profit(arbi_event: &ArbiEvent, mint_a: &MintInfo) -> f64 {
+    arbi_event
+        .transaction
+        .min_profit
+        .to_f64(mint_a.profit_decimals)
+        * token_weight(&arbi_event.common_accounts.token_vault_a_mint)
+}
+
+/// 利润所在的档位: 按 1 + step 的倍数分档，非正利润在最低档
+fn profit_bucket(profit: f64, step: f64) -> i64 {
+    if profit <= 0.0 || profit.is_nan() {
+        return i64::MIN;
+    }
+    (profit.ln() / step.ln_1p()).floor() as i64
+}
+
+/// 调度顺序: 利润按 profit_step 分档，档位高的优先；同一档内剩余时间少的优先，再按输入规模
+fn priority_cmp(
+    a: &PendingEvent,
+    b: &PendingEvent,
+    profit_step: f64,
+    now_ts: i64,
+    budget_ms: i64,
+) -> Ordering {
+    profit_bucket(a.expected_profit, profit_step)
+        .cmp(&profit_bucket(b.expected_profit, profit_step))
+        .then_with(|| {
+            a.remaining_ms(now_ts, budget_ms)
+                .cmp(&b.remaining_ms(now_ts, budget_ms))
+                .reverse()
+        })
+        .then_with(|| a.notional.total_cmp(&b.notional))
+}
+
+fn token_weight(mint: &Pubkey) -> f64 {
+    static WEIGHTS: std::sync::OnceLock<HashMap<Pubkey, f64>> = std::sync::OnceLock::new();
+    WEIGHTS
+        .get_or_init(|| {
+            // PROFIT_TOKEN_WEIGHTS=mint:weight,mint:weight
+            std::env::var("PROFIT_TOKEN_WEIGHTS")
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|pair| {
+                    let (mint, weight) = pair.trim().split_once(':')?;
+                    Some((Pubkey::from_str(mint).ok()?, weight.parse::<f64>().ok()?))
+                })
+                .collect()
+        })
+        .get(mint)
+        .copied()
+        .unwrap_or(1.0)
+}
+
+/// push_batch 中没有留在队列里的事件，均已计数，调用方确认后丢弃
+#[derive(Default)]
+pub struct Rejected {
+    /// 超过上限按 SHED_POLICY 丢弃
+    pub shed: Vec<PendingEvent>,
+    /// 同一路由已在排队，合并时利润较低的一方
+    pub duplicates: Vec<PendingEvent>,
+}
+
+/// 读取与执行之间的缓冲区，限制进程内持有的事件总数 (排队 + 执行中)，
+/// 按利润与剩余时间调度
+pub struct PendingBuffer {
+    queue: Mutex<VecDeque<PendingEvent>>,
+    notify: Notify,
+    closed: AtomicBool,
+    in_flight: AtomicUsize,
+    max_in_flight: usize,
+    policy: ShedPolicy,
+    staleness_budget_ms: i64,
+    /// 利润分档的比例，利润相差在该比例内的事件按剩余时间调度
+    profit_step: f64,
+}
+
+impl PendingBuffer {
+    /// MAX_IN_FLIGHT 默认为并行度的 4 倍
+    pub fn from_env(parallelism: usize, staleness_budget_ms: i64) -> Self {
+        let max_in_flight = std::env::var("MAX_IN_FLIGHT")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(parallelism * 4)
+            .max(parallelism)
+            .max(1);
+        let policy = ShedPolicy::from_env();
+        let profit_step = std::env::var("PROFIT_BUCKET_PCT")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|pct| *pct > 0.0)
+            .unwrap_or(10.0)
+            / 100.0;
+        debug!(
+            "pending buffer max_in_flight: {}, policy: {:?}, profit_step: {}",
+            max_in_flight, policy, profit_step
+        );
+        Self::new(max_in_flight, policy, staleness_budget_ms, profit_step)
+    }
+
+    pub fn new(
+        max_in_flight: usize,
+        policy: ShedPolicy,
+        staleness_budget_ms: i64,
+        profit_step: f64,
+    ) -> Self {
+        PendingBuffer {
+            queue: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+            closed: AtomicBool::new(false),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight,
+            policy,
+            staleness_budget_ms,
+            profit_step,
+        }
+    }
+
+    /// 放入一批事件，返回未留在队列中的事件: 超过上限时按策略丢弃的，以及同一路由合并掉的
+    pub fn push_batch(&self, events: Vec<PendingEvent>) -> Rejected {
+        let mut rejected = Rejected::default();
+        let mut queue = self.queue.lock().unwrap();
+        for event in events {
+            self.push_locked(&mut queue, event, &mut rejected);
+        }
+        drop(queue);
+        self.notify.notify_one();
+        rejected
+    }
+
+    fn push_locked(
+        &self,
+        queue: &mut VecDeque<PendingEvent>,
+        event: PendingEvent,
+        rejected: &mut Rejected,
+    ) {
+        // 同一路由仍在排队时只保留利润更高的一个，不额外占用名额
+        if let Some(index) = queue
+            .iter()
+            .position(|queued| queued.fingerprint == event.fingerprint)
+        {
+            Metrics::incr(&METRICS.deduplicated);
+            let duplicate = if queue[index].expected_profit >= event.expected_profit {
+                event
+            } else {
+                std::mem::replace(&mut queue[index], event)
+            };
+            rejected.duplicates.push(duplicate);
+            return;
+        }
+        // 同一路由已在执行或已提交
+        if event.replace_only {
+            Metrics::incr(&METRICS.deduplicated);
+            rejected.duplicates.push(event);
+            return;
+        }
+        if self.in_flight.load(AtomicOrdering::Acquire) < self.max_in_flight {
+            self.in_flight.fetch_add(1, AtomicOrdering::AcqRel);
+            queue.push_back(event);
+            return;
+        }
+        let shed = match self.policy {
+            ShedPolicy::Newest => None,
+            ShedPolicy::Oldest => queue.pop_front().inspect(|_| {
+                Metrics::incr(&METRICS.shed_oldest);
+            }),
+            ShedPolicy::LowestProfit => queue
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.expected_profit.total_cmp(&b.expected_profit))
+                .filter(|(_, lowest)| lowest.expected_profit < event.expected_profit)
+                .map(|(index, _)| index)
+                .and_then(|index| queue.remove(index))
+                .inspect(|_| Metrics::incr(&METRICS.shed_lowest_profit)),
+        };
+        match shed {
+            // 腾出的名额由新事件占用
+            Some(shed) => {
+                rejected.shed.push(shed);
+                queue.push_back(event);
+            }
+            // 全部在执行中或新事件利润最低，丢弃新事件
+            None => {
+                Metrics::incr(&METRICS.shed_newest);
+                rejected.shed.push(event);
+            }
+        }
+    }
+
+    /// 取出优先级最高的事件，缓冲区关闭且为空时返回 None；
+    /// 已超时的事件最先取出，直接在超时检查处失败，不占用提交资源
+    pub async fn pop(&self) -> Option<PendingEvent> {
+        loop {
+            let next = {
+                let mut queue = self.queue.lock().unwrap();
+                let now_ts = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as i64;
+                let index = match queue
+                    .iter()
+                    .position(|event| event.remaining_ms(now_ts, self.staleness_budget_ms) <= 0)
+                {
+                    Some(index) => Some(index),
+                    None => queue
+                        .iter()
+                        .enumerate()
+                        .max_by(|(_, a), (_, b)| {
+                            priority_cmp(a, b, self.profit_step, now_ts, self.staleness_budget_ms)
+                        })
+                        .map(|(index, _)| index),
+                };
+                index.and_then(|index| queue.remove(index))
+            };
+            if let Some(event) = next {
+                return Some(event);
+            }
+            if self.closed.load(AtomicOrdering::Acquire) {
+                return None;
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// 事件执行结束，释放占用的名额
+    pub fn finish(&self) {
+        self.in_flight.fetch_sub(1, AtomicOrdering::AcqRel);
+    }
+
+    pub fn close(&self) {
+        self.closed.store(true, AtomicOrdering::Release);
+        self.notify.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::submiter::route::tests::legacy_json;
+    use crate::submiter::wire::parse_json_event;
+
+    fn now_ms() -> i64 {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64
+    }
+
+    /// 每次调用生成不同的池子，fingerprint 互不相同
+    fn pending(expected_profit: f64, stream_ts: i64) -> PendingEvent {
+        let mut arbi_event = parse_json_event(&legacy_json().to_string()).unwrap();
+        arbi_event.stream_ts = stream_ts;
+        arbi_event.trace_id = format!("{}@{}", expected_profit, stream_ts);
+        let fingerprint = arbi_event.route_fingerprint();
+        PendingEvent {
+            raw: RawEvent {
+                source: "test".to_string(),
+                value: String::new(),
+                received_ts: stream_ts,
+                ack: None,
+            },
+            arbi_event,
+            expected_profit,
+            notional: 1.0,
+            fingerprint,
+            replace_only: false,
+        }
+    }
+
+    fn profits(events: &[PendingEvent]) -> Vec<f64> {
+        events.iter().map(|event| event.expected_profit).collect()
+    }
+
+    async fn drain(buffer: &PendingBuffer) -> Vec<f64> {
+        buffer.close();
+        let mut popped = vec![];
+        while let Some(event) = buffer.pop().await {
+            popped.push(event.expected_profit);
+        }
+        popped
+    }
+
+    #[tokio::test]
+    async fn pops_highest_profit_first() {
+        let buffer = PendingBuffer::new(10, ShedPolicy::Oldest, 60_000, 0.1);
+        let now = now_ms();
+        let rejected = buffer.push_batch(vec![
+            pending(1.0, now),
+            pending(3.0, now),
+            pending(2.0, now),
+        ]);
+        assert!(rejected.shed.is_empty() && rejected.duplicates.is_empty());
+        assert_eq!(drain(&buffer).await, vec![3.0, 2.0, 1.0]);
+    }
+
+    #[tokio::test]
+    async fn close_profits_prefer_less_remaining_time() {
+        let buffer = PendingBuffer::new(10, ShedPolicy::Oldest, 60_000, 0.1);
+        let now = now_ms();
+        // 1.0 与 1.05 在同一档，先取剩余时间少的 1.0；2.0 在更高的档
+        buffer.push_batch(vec![
+            pending(1.05, now),
+            pending(1.0, now - 30_000),
+            pending(2.0, now),
+        ]);
+        assert_eq!(drain(&buffer).await, vec![2.0, 1.0, 1.05]);
+    }
+
+    #[test]
+    fn profit_buckets() {
+        assert_eq!(profit_bucket(1.0, 0.1), 0);
+        assert_eq!(profit_bucket(1.05, 0.1), 0);
+        assert_eq!(profit_bucket(1.2, 0.1), 1);
+        assert_eq!(profit_bucket(0.95, 0.1), -1);
+        assert_eq!(profit_bucket(0.0, 0.1), i64::MIN);
+        assert_eq!(profit_bucket(-1.0, 0.1), i64::MIN);
+    }
+
+    #[tokio::test]
+    async fn expired_events_are_popped_first() {
+        let buffer = PendingBuffer::new(10, ShedPolicy::Oldest, 1_000, 0.1);
+        let now = now_ms();
+        buffer.push_batch(vec![pending(5.0, now), pending(1.0, now - 10_000)]);
+        assert_eq!(drain(&buffer).await, vec![1.0, 5.0]);
+    }
+
+    #[test]
+    fn drop_oldest_sheds_front() {
+        let buffer = PendingBuffer::new(2, ShedPolicy::Oldest, 60_000, 0.1);
+        let now = now_ms();
+        let rejected = buffer.push_batch(vec![
+            pending(1.0, now),
+            pending(2.0, now),
+            pending(3.0, now),
+        ]);
+        assert_eq!(profits(&rejected.shed), vec![1.0]);
+    }
+
+    #[test]
+    fn drop_newest_rejects_new_event() {
+        let buffer = PendingBuffer::new(2, ShedPolicy::Newest, 60_000, 0.1);
+        let now = now_ms();
+        let rejected = buffer.push_batch(vec![
+            pending(1.0, now),
+            pending(2.0, now),
+            pending(3.0, now),
+        ]);
+        assert_eq!(profits(&rejected.shed), vec![3.0]);
+    }
+
+    #[test]
+    fn drop_lowest_profit_keeps_best() {
+        let buffer = PendingBuffer::new(2, ShedPolicy::LowestProfit, 60_000, 0.1);
+        let now = now_ms();
+        let rejected = buffer.push_batch(vec![
+            pending(2.0, now),
+            pending(1.0, now),
+            pending(3.0, now),
+            pending(0.5, now),
+        ]);
+        // 3.0 挤掉 1.0，0.5 低于队列中所有事件被直接丢弃
+        assert_eq!(profits(&rejected.shed), vec![1.0, 0.5]);
+    }
+
+    #[test]
+    fn finished_events_free_capacity() {
+        let buffer = PendingBuffer::new(1, ShedPolicy::Newest, 60_000, 0.1);
+        let now = now_ms();
+        assert!(buffer.push_batch(vec![pending(1.0, now)]).shed.is_empty());
+        assert_eq!(
+            profits(&buffer.push_batch(vec![pending(2.0, now)]).shed),
+            vec![2.0]
+        );
+        buffer.finish();
+        assert!(buffer.push_batch(vec![pending(3.0, now)]).shed.is_empty());
+    }
+
+    #[test]
+    fn same_route_keeps_higher_profit() {
+        let buffer = PendingBuffer::new(10, ShedPolicy::Oldest, 60_000, 0.1);
+        let now = now_ms();
+        let first = pending(1.0, now);
+        let mut better = pending(2.0, now);
+        better.fingerprint = first.fingerprint.clone();
+        let mut worse = pending(0.5, now);
+        worse.fingerprint = first.fingerprint.clone();
+        let rejected = buffer.push_batch(vec![first, better, worse]);
+        assert!(rejected.shed.is_empty());
+        assert_eq!(profits(&rejected.duplicates), vec![1.0, 0.5]);
+        assert_eq!(buffer.queue.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn replace_only_needs_a_queued_instance() {
+        let buffer = PendingBuffer::new(10, ShedPolicy::Oldest, 60_000, 0.1);
+        let now = now_ms();
+        let first = pending(1.0, now);
+        let fingerprint = first.fingerprint.clone();
+        assert!(buffer.push_batch(vec![first]).duplicates.is_empty());
+
+        let mut better = pending(2.0, now);
+        better.fingerprint = fingerprint.clone();
+        better.replace_only = true;
+        let rejected = buffer.push_batch(vec![better]);
+        assert_eq!(profits(&rejected.duplicates), vec![1.0]);
+
+        // 排队中的实例已被取出执行
+        buffer.queue.lock().unwrap().clear();
+        let mut late = pending(3.0, now);
+        late.fingerprint = fingerprint;
+        late.replace_only = true;
+        assert_eq!(
+            profits(&buffer.push_batch(vec![late]).duplicates),
+            vec![3.0]
+        );
+    }
+}
@@ -0,0 +1,469 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::{debug, error, info, warn};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use std::{
+    num::NonZeroUsize,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, BufReader, Lines},
+    net::UnixListener,
+    sync::mpsc,
+};
+
+use crate::submiter::stream::RedisStreamConsumer;
+use crate::submiter::submitter::get_or_init_redis;
+
+/// 数据源取出的一条原始消息
+pub struct RawEvent {
+    /// 来源名称 (队列名 / stream 名 / 文件路径)
+    pub source: String,
+    /// 原始消息体 (base64 + LZ4 或明文 JSON)
+    pub value: String,
+    /// 取出消息的时间戳 (ms)
+    pub received_ts: i64,
+    /// 处理完毕后的确认动作，不需要确认的数据源为 None
+    pub ack: Option<Box<dyn EventAck>>,
+}
+
+#[async_trait]
+pub trait EventAck: Send + Sync {
+    async fn ack(&self) -> Result<()>;
+}
+
+#[async_trait]
+pub trait EventSource: Send {
+    fn name(&self) -> &str;
+
+    /// 拉取下一批消息；空 Vec 表示本轮无消息，None 表示数据源已结束
+    async fn next_batch(&mut self) -> Result<Option<Vec<RawEvent>>>;
+}
+
+/// 按读取模式构建数据源: list | stream 时 name 为队列 / stream 名，unix | file 时为路径
+pub async fn event_source(ingest_mode: &str, name: &str) -> Result<Box<dyn EventSource>> {
+    let source: Box<dyn EventSource> = match ingest_mode {
+        "list" => {
+            let redis_conn = redis_from_env().await?;
+            Box::new(RedisListSource::new(redis_conn, name.to_string()))
+        }
+        "stream" => {
+            let redis_conn = redis_from_env().await?;
+            let consumer = RedisStreamConsumer::from_env(redis_conn, name);
+            Box::new(RedisStreamSource::new(consumer).await?)
+        }
+        "unix" => Box::new(UnixSocketSource::bind(name)?),
+        "file" => Box::new(JsonlFileSource::open(name).await?),
+        other => return Err(anyhow::anyhow!("Unsupported INGEST_MODE: {}", other)),
+    };
+    info!("Event source: {} ({})", source.name(), ingest_mode);
+    Ok(source)
+}
+
+async fn redis_from_env() -> Result<ConnectionManager> {
+    let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "127.0.0.1:6379".to_string());
+    get_or_init_redis(redis_url, 0).await
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// Redis list，BLPOP 取出即删除，取到消息后再用 LPOP 顺带取出最多 batch_size - 1 条
+pub struct RedisListSource {
+    conn: ConnectionManager,
+    key: String,
+    batch_size: usize,
+}
+
+impl RedisListSource {
+    pub fn new(conn: ConnectionManager, key: String) -> Self {
+        let batch_size = std::env::var("REDIS_LIST_BATCH")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(16);
+        RedisListSource {
+            conn,
+            key,
+            batch_size,
+        }
+    }
+}
+
+#[async_trait]
+impl EventSource for RedisListSource {
+    fn name(&self) -> &str {
+        &self.key
+    }
+
+    async fn next_batch(&mut self) -> Result<Option<Vec<RawEvent>>> {
+        // 使用 BLPOP 阻塞等待队列中的消息
+        let popped: Option<(String, String)> = self.conn.blpop(&self.key, 300.0).await?;
+        let Some((key, value)) = popped else {
+            return Ok(Some(vec![]));
+        };
+        let mut values = vec![value];
+        if let Some(count) = NonZeroUsize::new(self.batch_size.saturating_sub(1)) {
+            let rest: Vec<String> = self.conn.lpop(&self.key, Some(count)).await?;
+            values.extend(rest);
+        }
+
+        let received_ts = now_ms();
+        Ok(Some(
+            values
+                .into_iter()
+                .map(|value| RawEvent {
+                    source: key.clone(),
+                    value,
+                    received_ts,
+                    ack: None,
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Redis stream 消费组，处理完毕后 XACK，并定期 XAUTOCLAIM 接管失联消费者的消息
+pub struct RedisStreamSource {
+    consumer: RedisStreamConsumer,
+    claim_interval: Duration,
+    last_claim: Option<Instant>,
+}
+
+impl RedisStreamSource {
+    pub async fn new(mut consumer: RedisStreamConsumer) -> Result<Self> {
+        consumer.ensure_group().await?;
+        info!(
+            "Consuming stream {} as {}/{}",
+            consumer.stream, consumer.group, consumer.consumer
+        );
+        let claim_interval = Duration::from_millis(
+            std::env::var("REDIS_STREAM_CLAIM_INTERVAL_MS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(5_000),
+        );
+        Ok(RedisStreamSource {
+            consumer,
+            claim_interval,
+            // 启动时先接管一次遗留的待确认消息
+            last_claim: None,
+        })
+    }
+}
+
+struct StreamAck {
+    consumer: RedisStreamConsumer,
+    id: String,
+}
+
+#[async_trait]
+impl EventAck for StreamAck {
+    async fn ack(&self) -> Result<()> {
+        self.consumer.ack(&self.id).await
+    }
+}
+
+#[async_trait]
+impl EventSource for RedisStreamSource {
+    fn name(&self) -> &str {
+        &self.consumer.stream
+    }
+
+    async fn next_batch(&mut self) -> Result<Option<Vec<RawEvent>>> {
+        let claim_due = match self.last_claim {
+            Some(ts) => ts.elapsed() >= self.claim_interval,
+            None => true,
+        };
+        let messages = if claim_due {
+            self.last_claim = Some(Instant::now());
+            self.consumer.reclaim().await?
+        } else {
+            self.consumer.read().await?
+        };
+
+        let received_ts = now_ms();
+        Ok(Some(
+            messages
+                .into_iter()
+                .map(|message| RawEvent {
+                    source: self.consumer.stream.clone(),
+                    value: message.value,
+                    received_ts,
+                    ack: Some(Box::new(StreamAck {
+                        consumer: self.consumer.clone(),
+                        id: message.id,
+                    })),
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Unix domain socket，每个连接按行发送消息
+pub struct UnixSocketSource {
+    path: String,
+    receiver: mpsc::Receiver<String>,
+}
+
+impl UnixSocketSource {
+    pub fn bind(path: &str) -> Result<Self> {
+        // 清理上次遗留的 socket 文件
+        if std::path::Path::new(path).exists() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let (sender, receiver) = mpsc::channel::<String>(1024);
+
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let sender = sender.clone();
+                        tokio::spawn(async move {
+                            let mut lines = BufReader::new(stream).lines();
+                            loop {
+                                match lines.next_line().await {
+                                    Ok(Some(line)) if line.trim().is_empty() => continue,
+                                    Ok(Some(line)) => {
+                                        if sender.send(line).await.is_err() {
+                                            break;
+                                        }
+                                    }
+                                    Ok(None) => break,
+                                    Err(e) => {
+                                        warn!("unix socket read failed: {:?}", e);
+                                        break;
+                                    }
+                                }
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("unix socket accept failed: {:?}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                }
+            }
+        });
+
+        info!("Listening on unix socket {}", path);
+        Ok(UnixSocketSource {
+            path: path.to_string(),
+            receiver,
+        })
+    }
+}
+
+#[async_trait]
+impl EventSource for UnixSocketSource {
+    fn name(&self) -> &str {
+        &self.path
+    }
+
+    async fn next_batch(&mut self) -> Result<Option<Vec<RawEvent>>> {
+        match tokio::time::timeout(Duration::from_secs(300), self.receiver.recv()).await {
+            Ok(Some(value)) => {
+                let received_ts = now_ms();
+                let mut events = vec![RawEvent {
+                    source: self.path.clone(),
+                    value,
+                    received_ts,
+                    ack: None,
+                }];
+                // 顺带取出已到达的消息
+                while let Ok(value) = self.receiver.try_recv() {
+                    events.push(RawEvent {
+                        source: self.path.clone(),
+                        value,
+                        received_ts,
+                        ack: None,
+                    });
+                }
+                Ok(Some(events))
+            }
+            Ok(None) => Ok(None),
+            Err(_) => Ok(Some(vec![])),
+        }
+    }
+}
+
+/// JSONL 文件，每行一条消息，读到文件末尾即结束
+pub struct JsonlFileSource {
+    path: String,
+    lines: Lines<BufReader<File>>,
+}
+
+impl JsonlFileSource {
+    pub async fn open(path: &str) -> Result<Self> {
+        let file = File::open(path)
+            .await
+            .with_context(|| format!("打开文件失败: {}", path))?;
+        Ok(JsonlFileSource {
+            path: path.to_string(),
+            lines: BufReader::new(file).lines(),
+        })
+    }
+}
+
+#[async_trait]
+impl EventSource for JsonlFileSource {
+    fn name(&self) -> &str {
+        &self.path
+    }
+
+    async fn next_batch(&mut self) -> Result<Option<Vec<RawEvent>>> {
+        loop {
+            match self.lines.next_line().await? {
+                Some(line) if line.trim().is_empty() => continue,
+                Some(line) => {
+                    debug!("read line from {}", self.path);
+                    return Ok(Some(vec![RawEvent {
+                        source: self.path.clone(),
+                        value: line.trim().to_string(),
+                        received_ts: now_ms(),
+                        ack: None,
+                    }]));
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::UnixStream;
+
+    /// 内存数据源，按顺序返回预置的批次，取完后结束
+    pub(crate) struct MemorySource {
+        batches: VecDeque<Vec<String>>,
+        acked: Arc<AtomicUsize>,
+    }
+
+    impl MemorySource {
+        /// 返回数据源与已确认消息的计数
+        pub(crate) fn new(batches: Vec<Vec<String>>) -> (Self, Arc<AtomicUsize>) {
+            let acked = Arc::new(AtomicUsize::new(0));
+            let source = MemorySource {
+                batches: batches.into(),
+                acked: acked.clone(),
+            };
+            (source, acked)
+        }
+    }
+
+    struct CountingAck {
+        acked: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl EventAck for CountingAck {
+        async fn ack(&self) -> Result<()> {
+            self.acked.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl EventSource for MemorySource {
+        fn name(&self) -> &str {
+            "memory"
+        }
+
+        async fn next_batch(&mut self) -> Result<Option<Vec<RawEvent>>> {
+            let Some(batch) = self.batches.pop_front() else {
+                return Ok(None);
+            };
+            let received_ts = now_ms();
+            Ok(Some(
+                batch
+                    .into_iter()
+                    .map(|value| RawEvent {
+                        source: "memory".to_string(),
+                        value,
+                        received_ts,
+                        ack: Some(Box::new(CountingAck {
+                            acked: self.acked.clone(),
+                        })),
+                    })
+                    .collect(),
+            ))
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("arbi_submiter_{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    async fn values(source: &mut dyn EventSource) -> Vec<String> {
+        let mut values = vec![];
+        while let Some(batch) = source.next_batch().await.unwrap() {
+            values.extend(batch.into_iter().map(|raw| raw.value));
+        }
+        values
+    }
+
+    #[tokio::test]
+    async fn memory_source_acks_and_ends() {
+        let (mut source, acked) =
+            MemorySource::new(vec![vec!["a".into(), "b".into()], vec!["c".into()]]);
+        let first = source.next_batch().await.unwrap().unwrap();
+        assert_eq!(first.len(), 2);
+        for raw in first {
+            raw.ack.unwrap().ack().await.unwrap();
+        }
+        assert_eq!(acked.load(Ordering::SeqCst), 2);
+        assert_eq!(values(&mut source).await, vec!["c"]);
+        assert!(source.next_batch().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn jsonl_file_skips_blank_lines_and_ends() {
+        let path = temp_path("source.jsonl");
+        std::fs::write(&path, "{\"a\":1}\n\n  \n {\"b\":2} \n").unwrap();
+        let mut source = JsonlFileSource::open(&path).await.unwrap();
+        let values = values(&mut source).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(values, vec!["{\"a\":1}", "{\"b\":2}"]);
+    }
+
+    #[tokio::test]
+    async fn jsonl_file_missing_is_an_error() {
+        assert!(JsonlFileSource::open(&temp_path("missing.jsonl"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn unix_socket_reads_lines_from_connections() {
+        let path = temp_path("source.sock");
+        let mut source = UnixSocketSource::bind(&path).unwrap();
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        stream.write_all(b"first\n\nsecond\n").await.unwrap();
+        stream.shutdown().await.unwrap();
+
+        let mut received = vec![];
+        while received.len() < 2 {
+            let batch = source.next_batch().await.unwrap().unwrap();
+            assert!(batch.iter().all(|raw| raw.ack.is_none()));
+            received.extend(batch.into_iter().map(|raw| raw.value));
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(received, vec!["first", "second"]);
+    }
+}
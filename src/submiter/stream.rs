@@ -0,0 +1,206 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+use redis::{
+    aio::ConnectionManager,
+    streams::{
+        StreamAutoClaimOptions, StreamAutoClaimReply, StreamId, StreamReadOptions, StreamReadReply,
+    },
+    AsyncCommands,
+};
+
+/// 基于 Redis Stream 消费组的消息读取，处理完成后才 XACK，
+/// 进程崩溃时未确认的消息会留在 PEL 中，由其他消费者通过 XAUTOCLAIM 接管
+#[derive(Clone)]
+pub struct RedisStreamConsumer {
+    conn: ConnectionManager,
+    pub stream: String,
+    pub group: String,
+    pub consumer: String,
+    pub field: String,
+    block_ms: usize,
+    batch_size: usize,
+    claim_idle_ms: usize,
+    claim_cursor: String,
+}
+
+pub struct StreamMessage {
+    pub id: String,
+    pub value: String,
+}
+
+impl RedisStreamConsumer {
+    /// 从环境变量构建消费者，stream 名为队列配置中的名称
+    pub fn from_env(conn: ConnectionManager, stream: &str) -> Self {
+        let stream = stream.to_string();
+        let group =
+            std::env::var("REDIS_STREAM_GROUP").unwrap_or_else(|_| "arbi_submiter".to_string());
+        let consumer = std::env::var("REDIS_STREAM_CONSUMER").unwrap_or_else(|_| {
+            format!(
+                "{}-{}",
+                std::env::var("HOSTNAME").unwrap_or_else(|_| "submiter".to_string()),
+                std::process::id()
+            )
+        });
+        let field = std::env::var("REDIS_STREAM_FIELD").unwrap_or_else(|_| "data".to_string());
+
+        RedisStreamConsumer {
+            conn,
+            stream,
+            group,
+            consumer,
+            field,
+            block_ms: env_usize("REDIS_STREAM_BLOCK_MS", 5_000),
+            batch_size: env_usize("REDIS_STREAM_BATCH", 16),
+            claim_idle_ms: env_usize("REDIS_STREAM_CLAIM_IDLE_MS", 30_000),
+            claim_cursor: "0-0".to_string(),
+        }
+    }
+
+    /// 创建消费组，已存在时忽略 BUSYGROUP 错误
+    pub async fn ensure_group(&mut self) -> Result<()> {
+        let result: redis::RedisResult<()> = self
+            .conn
+            .xgroup_create_mkstream(&self.stream, &self.group, "$")
+            .await;
+        match result {
+            Ok(_) => {
+                info!(
+                    "Created consumer group {} on stream {}",
+                    self.group, self.stream
+                );
+                Ok(())
+            }
+            Err(err) if err.code() == Some("BUSYGROUP") => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// 阻塞读取新消息 (XREADGROUP ... >)
+    pub async fn read(&mut self) -> Result<Vec<StreamMessage>> {
+        let options = StreamReadOptions::default()
+            .group(&self.group, &self.consumer)
+            .block(self.block_ms)
+            .count(self.batch_size);
+        let reply: Option<StreamReadReply> = self
+            .conn
+            .xread_options(&[&self.stream], &[">"], &options)
+            .await?;
+
+        let ids = reply
+            .map(|reply| reply.keys.into_iter().flat_map(|key| key.ids).collect())
+            .unwrap_or_default();
+        Ok(self.collect_messages(ids).await)
+    }
+
+    /// 接管空闲超过 REDIS_STREAM_CLAIM_IDLE_MS 的待确认消息 (XAUTOCLAIM)
+    pub async fn reclaim(&mut self) -> Result<Vec<StreamMessage>> {
+        let reply: StreamAutoClaimReply = self
+            .conn
+            .xautoclaim_options(
+                &self.stream,
+                &self.group,
+                &self.consumer,
+                self.claim_idle_ms,
+                &self.claim_cursor,
+                StreamAutoClaimOptions::default().count(self.batch_size),
+            )
+            .await?;
+
+        // 游标回到 0-0 说明 PEL 已扫描一轮
+        self.claim_cursor = reply.next_stream_id;
+        if !reply.claimed.is_empty() {
+            warn!(
+                "Reclaimed {} pending messages from stream {}",
+                reply.claimed.len(),
+                self.stream
+            );
+        }
+        Ok(self.collect_messages(reply.claimed).await)
+    }
+
+    pub async fn ack(&self, id: &str) -> Result<()> {
+        let mut conn = self.conn.clone();
+        let _: i64 = conn.xack(&self.stream, &self.group, &[id]).await?;
+        Ok(())
+    }
+
+    /// 取出消息体字段，缺少字段的消息无法处理，直接确认丢弃
+    async fn collect_messages(&self, ids: Vec<StreamId>) -> Vec<StreamMessage> {
+        let (messages, missing) = split_messages(ids, &self.field);
+        for id in missing {
+            debug!(
+                "stream message {} has no field {}, ack and skip",
+                id, self.field
+            );
+            if let Err(e) = self.ack(&id).await {
+                warn!("ack stream message {} failed: {:?}", id, e);
+            }
+        }
+        messages
+    }
+}
+
+/// 按字段名取出消息体，返回可处理的消息与缺少该字段的消息 id
+fn split_messages(ids: Vec<StreamId>, field: &str) -> (Vec<StreamMessage>, Vec<String>) {
+    let mut messages = Vec::with_capacity(ids.len());
+    let mut missing = vec![];
+    for stream_id in ids {
+        match stream_id.get::<String>(field) {
+            Some(value) => messages.push(StreamMessage {
+                id: stream_id.id,
+                value,
+            }),
+            None => missing.push(stream_id.id),
+        }
+    }
+    (messages, missing)
+}
+
+fn env_usize(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::Value;
+    use std::collections::HashMap;
+
+    fn stream_id(id: &str, fields: &[(&str, &str)]) -> StreamId {
+        StreamId {
+            id: id.to_string(),
+            map: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), Value::BulkString(v.as_bytes().to_vec())))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn split_messages_keeps_order_and_reports_missing_field() {
+        let ids = vec![
+            stream_id("1-0", &[("data", "first")]),
+            stream_id("2-0", &[("other", "x")]),
+            stream_id("3-0", &[("data", "third"), ("other", "y")]),
+        ];
+        let (messages, missing) = split_messages(ids, "data");
+        let messages: Vec<_> = messages
+            .iter()
+            .map(|m| (m.id.as_str(), m.value.as_str()))
+            .collect();
+        assert_eq!(messages, vec![("1-0", "first"), ("3-0", "third")]);
+        assert_eq!(missing, vec!["2-0"]);
+    }
+
+    #[test]
+    fn env_usize_falls_back_on_missing_or_invalid() {
+        assert_eq!(env_usize("ARBI_SUBMITER_TEST_UNSET_USIZE", 7), 7);
+        std::env::set_var("ARBI_SUBMITER_TEST_BAD_USIZE", "many");
+        assert_eq!(env_usize("ARBI_SUBMITER_TEST_BAD_USIZE", 16), 16);
+        std::env::set_var("ARBI_SUBMITER_TEST_GOOD_USIZE", "32");
+        assert_eq!(env_usize("ARBI_SUBMITER_TEST_GOOD_USIZE", 16), 32);
+    }
+}
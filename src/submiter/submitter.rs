@@ -1,6 +1,4 @@
-use base64::Engine;
-use log::{debug, error, info, trace};
-use lz4_flex::decompress_size_prepended;
+use log::{debug, error, info, trace, warn};
 use rand::{rngs::OsRng, seq::SliceRandom};
 use reqwest::Client as ReqwestClient;
 
@@ -14,16 +12,31 @@ use anchor_client::{
     },
     Client, Cluster,
 };
-use anyhow::{Context, Result};
-use async_compression::tokio::bufread::ZlibDecoder;
+use anyhow::Result;
 use redis::{
     aio::{ConnectionManager, ConnectionManagerConfig},
-    AsyncCommands, ConnectionAddr, ConnectionInfo, RedisConnectionInfo,
+    ConnectionAddr, ConnectionInfo, RedisConnectionInfo,
 };
-use tokio::io::AsyncReadExt as _;
-use tokio::sync::Semaphore;
+use tokio::{sync::Semaphore, task::JoinSet};
 
 use crate::submiter::assembler::assemble_and_submit_transaction;
+use crate::submiter::codec::decode_payload;
+use crate::submiter::compute::ComputeUnitModel;
+use crate::submiter::dead_letter::{
+    DeadLetter, DeadLetterQueue, FailureStage, StageContext, StageError,
+};
+use crate::submiter::dedup::{Admission, RouteDedup};
+use crate::submiter::derive::PoolStateSource;
+use crate::submiter::dex::LegAccountError;
+use crate::submiter::metrics::{self, Metrics, METRICS};
+use crate::submiter::mints::MintRegistry;
+use crate::submiter::pool_cache::PoolCache;
+use crate::submiter::priority_fee::PriorityFees;
+use crate::submiter::queues::{load_queue_policies, QueuePolicy, WalletPool};
+use crate::submiter::scheduler::{PendingBuffer, PendingEvent};
+use crate::submiter::source::{event_source, EventSource, RawEvent};
+use crate::submiter::validate::validate_event;
+use crate::submiter::wire::decode_arbi_event;
 
 use super::assembler::{ArbiEvent, TransactionHelpers};
 
@@ -36,146 +49,377 @@ const RPC_SUBMITTER_KEYS: [&str; 1] = [""];
 
 const PROXY_SUBMITTER_KEYS: [&str; 1] = [""];
 
+/// 提交流程共享的资源
+#[derive(Clone)]
+pub struct SubmitContext {
+    pub alt_account: Arc<AddressLookupTableAccount>,
+    pub connections: Vec<Arc<RpcClient>>,
+    pub request_client: Arc<ReqwestClient>,
+    pub mints: Arc<MintRegistry>,
+    /// 未配置 POOL_WS_URL / POOL_STATE_CHANNEL 时为 None
+    pub pool_states: Option<Arc<PoolCache>>,
+    pub compute_units: Arc<ComputeUnitModel>,
+    pub priority_fees: Arc<PriorityFees>,
+}
+
+impl SubmitContext {
+    pub fn pool_state_source(&self) -> Option<&dyn PoolStateSource> {
+        self.pool_states
+            .as_deref()
+            .map(|cache| cache as &dyn PoolStateSource)
+    }
+
+    pub fn init() -> Result<Self> {
+        let alt_account = Arc::new(AddressLookupTableAccount {
+            key: Pubkey::from_str("5JeXxBnqMU4kVPciskf4DBtdQEXPL6qowC8mSiyo4F49").unwrap(),
+            addresses: vec![
+                Pubkey::from_str("11111111111111111111111111111111").unwrap(),
+                Pubkey::from_str("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc").unwrap(),
+                Pubkey::from_str("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK").unwrap(),
+                Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").unwrap(),
+                Pubkey::from_str("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb").unwrap(),
+                Pubkey::from_str("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr").unwrap(),
+                Pubkey::from_str("So11111111111111111111111111111111111111112").unwrap(),
+            ],
+        });
+
+        let connections: Vec<Arc<RpcClient>> = RPC_URLS
+            .iter()
+            .map(|rpc_url| {
+                Arc::new(RpcClient::new_with_commitment(
+                    rpc_url.to_string(),
+                    CommitmentConfig::confirmed(),
+                ))
+            })
+            .collect();
+        let mints = Arc::new(MintRegistry::from_env(connections.first().cloned())?);
+        let pool_states = PoolCache::spawn_from_env(connections.first().cloned(), mints.clone())?;
+        let priority_fees = PriorityFees::spawn_from_env(connections.first().cloned());
+
+        Ok(SubmitContext {
+            alt_account,
+            connections,
+            // 初始化ReqwestClient
+            request_client: Arc::new(ReqwestClient::new()),
+            mints,
+            pool_states,
+            compute_units: Arc::new(ComputeUnitModel::from_env()),
+            priority_fees,
+        })
+    }
+}
+
 pub async fn monitor_and_submit() -> Result<()> {
-    let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "127.0.0.1:6379".to_string());
-    let mut redis_conn = get_or_init_redis(redis_url, 0).await?;
-    let redis_key_name =
-        std::env::var("REDIS_QUEUE_NAME").unwrap_or_else(|_| "arbi_swap_queue".to_string());
-
-    let alt_account = Arc::new(AddressLookupTableAccount {
-        key: Pubkey::from_str("5JeXxBnqMU4kVPciskf4DBtdQEXPL6qowC8mSiyo4F49").unwrap(),
-        addresses: vec![
-            Pubkey::from_str("11111111111111111111111111111111").unwrap(),
-            Pubkey::from_str("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc").unwrap(),
-            Pubkey::from_str("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK").unwrap(),
-            Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").unwrap(),
-            Pubkey::from_str("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb").unwrap(),
-            Pubkey::from_str("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr").unwrap(),
-            Pubkey::from_str("So11111111111111111111111111111111111111112").unwrap(),
-        ],
-    });
-
-    let connections = RPC_URLS.map(|rpc_url| {
-        Arc::new(RpcClient::new_with_commitment(
-            rpc_url.to_string(),
-            CommitmentConfig::confirmed(),
-        ))
-    });
-
-    // 控制子线程并发
-    // 从环境变量获取并行度，默认值为 1
-    let parallelism = std::env::var("PARALLELISM")
-        .unwrap_or_else(|_| "1".to_string())
-        .parse::<usize>()
-        .unwrap_or(1); // 解析为 usize，解析失败时使用默认值
+    // 每个队列独立的读取模式、钱包池、提交方式、并行度与超时预算，见 QUEUE_CONFIG
+    let policies = load_queue_policies()?;
+
+    // 处理失败的消息写入死信队列
+    let dead_letter = DeadLetterQueue::from_env().await?;
+    metrics::spawn_reporter();
+
+    let context = SubmitContext::init()?;
+    // 所有队列共用一个去重实例，同一路由出现在不同队列中也只提交一次
+    let dedup = RouteDedup::from_env().await?.map(Arc::new);
+    let mut pipelines = JoinSet::new();
+    for policy in policies {
+        // 读取模式: list 使用 BLPOP，stream 使用消费组 XREADGROUP/XACK，unix / file 用于对接其他进程或离线数据
+        let source = event_source(&policy.ingest_mode, &policy.name).await?;
+        pipelines.spawn(run_pipeline(
+            source,
+            context.clone(),
+            Arc::new(policy),
+            dead_letter.clone(),
+            dedup.clone(),
+        ));
+    }
+    while let Some(result) = pipelines.join_next().await {
+        result??;
+    }
+    Ok(())
+}
 
+/// 从数据源持续取消息并按队列策略提交，数据源结束后等待所有子任务完成再返回
+pub async fn run_pipeline(
+    source: Box<dyn EventSource>,
+    context: SubmitContext,
+    policy: Arc<QueuePolicy>,
+    dead_letter: Option<DeadLetterQueue>,
+    dedup: Option<Arc<RouteDedup>>,
+) -> Result<()> {
+    let parallelism = policy.parallelism;
     let semaphore = Arc::new(Semaphore::new(parallelism));
-
-    // 初始化ReqwestClient
-    let request_client = Arc::new(ReqwestClient::new());
+    let buffer = Arc::new(PendingBuffer::from_env(
+        parallelism,
+        policy.staleness_budget_ms,
+    ));
+    let mut tasks = JoinSet::new();
+
+    // 读取与解码在独立任务中进行，执行跟不上时由缓冲区按 SHED_POLICY 丢弃
+    let reader = tokio::spawn(ingest(
+        source,
+        buffer.clone(),
+        dedup,
+        context.clone(),
+        dead_letter.clone(),
+    ));
 
     loop {
+        // 回收已结束的子任务
+        while tasks.try_join_next().is_some() {}
+
         // 获取一个信号量许可，如果没有可用许可，则等待
-        let _permit = semaphore.clone().acquire_owned().await.unwrap();
-        let connection = connections.choose(&mut OsRng).unwrap();
-
-        let start_ts = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
-        // 使用 BLPOP 阻塞等待队列中的消息
-        match redis_conn.blpop(&redis_key_name, 300.0).await {
-            Ok(Some((key, value))) => {
-                trace!(
-                    "tokio available_permits count: {}",
-                    semaphore.available_permits()
-                );
-                // 启动子线程
-                let alt_account_clone = alt_account.clone();
-                let request_client_clone = request_client.clone();
-                let connection_clone = connection.clone();
-
-                let _handle = tokio::spawn(async move {
-                    match execute_transaction(
-                        key,
-                        value,
-                        alt_account_clone,
-                        request_client_clone,
-                        connection_clone,
-                        start_ts,
-                    )
-                    .await
-                    {
-                        Ok(_) => debug!("Transaction executed successfully."),
-                        Err(e) => error!("Error executing transaction: {:?}", e),
-                    }
-                });
+        // 许可随事件移交给子任务，子任务结束才释放
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let Some(pending) = buffer.pop().await else {
+            break;
+        };
+        trace!(
+            "tokio available_permits count: {}",
+            semaphore.available_permits()
+        );
+
+        // 启动子线程
+        let context_clone = context.clone();
+        let policy_clone = policy.clone();
+        let dead_letter_clone = dead_letter.clone();
+        let buffer_clone = buffer.clone();
+
+        tasks.spawn(async move {
+            let PendingEvent {
+                raw, arbi_event, ..
+            } = pending;
+            match execute_transaction(
+                &raw.source,
+                arbi_event,
+                &context_clone,
+                &policy_clone,
+                raw.received_ts,
+            )
+            .await
+            {
+                Ok(_) => {
+                    Metrics::incr(&METRICS.executed);
+                    debug!("Transaction executed successfully.");
+                }
+                Err(e) => report_failure(&raw, e, dead_letter_clone.as_ref()).await,
             }
+            ack_event(raw).await;
+            buffer_clone.finish();
+            drop(permit);
+        });
+    }
+
+    reader.await?;
+    // 等待所有子任务结束
+    while tasks.join_next().await.is_some() {}
+    Ok(())
+}
+
+/// 持续读取数据源并解码放入缓冲区，数据源结束时关闭缓冲区
+async fn ingest(
+    mut source: Box<dyn EventSource>,
+    buffer: Arc<PendingBuffer>,
+    dedup: Option<Arc<RouteDedup>>,
+    context: SubmitContext,
+    dead_letter: Option<DeadLetterQueue>,
+) {
+    loop {
+        let events = match source.next_batch().await {
+            Ok(Some(events)) => events,
             Ok(None) => {
-                debug!("no message, continue");
-                continue;
-            } // 超时无消息，继续轮询
+                info!("event source {} exhausted", source.name());
+                break;
+            }
             Err(err) => {
-                error!("Redis BLPOP failed: {:?}, 100ms later retry...", err);
+                error!(
+                    "Read from {} failed: {:?}, 100ms later retry...",
+                    source.name(),
+                    err
+                );
                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        if events.is_empty() {
+            debug!("no message, continue");
+            continue;
+        } // 超时无消息，继续轮询
+
+        // 整批解码后一起放入缓冲区，由调度按利润排序
+        let mut pending = Vec::with_capacity(events.len());
+        for raw in events {
+            Metrics::incr(&METRICS.received);
+            let arbi_event =
+                match decode_event(&raw.source, &raw.value, context.pool_state_source()) {
+                    Ok(arbi_event) => arbi_event,
+                    Err(e) => {
+                        report_failure(&raw, e, dead_letter.as_ref()).await;
+                        ack_event(raw).await;
+                        continue;
+                    }
+                };
+            // 利润按 token A 的精度折算，未知的 mint 首次从链上读取
+            let mint_a = match context
+                .mints
+                .get(&arbi_event.common_accounts.token_vault_a_mint)
+                .await
+                .stage(FailureStage::Validate)
+            {
+                Ok(mint_a) => mint_a,
+                Err(e) => {
+                    let e = StageError::attach_event(e, &arbi_event.trace_id, arbi_event.stream_ts);
+                    report_failure(&raw, e, dead_letter.as_ref()).await;
+                    ack_event(raw).await;
+                    continue;
+                }
+            };
+            let mut event = PendingEvent::new(raw, arbi_event, &mint_a);
+            // 窗口内已收到过同一路由时只能替换仍在排队的那一个，其他实例已占用时直接丢弃
+            if let Some(dedup) = &dedup {
+                match dedup.admit(&event.fingerprint, event.raw.received_ts).await {
+                    Admission::First => {}
+                    Admission::Duplicate => event.replace_only = true,
+                    Admission::Claimed => {
+                        Metrics::incr(&METRICS.deduplicated);
+                        debug!(
+                            "route claimed by another instance, trace_id: {}, fingerprint: {}",
+                            event.arbi_event.trace_id, event.fingerprint
+                        );
+                        ack_event(event.raw).await;
+                        continue;
+                    }
+                }
             }
+            pending.push(event);
+        }
+        let rejected = buffer.push_batch(pending);
+        for shed in rejected.shed {
+            warn!(
+                "shed trace_id: {}, expected_profit: {}",
+                shed.arbi_event.trace_id, shed.expected_profit
+            );
+            ack_event(shed.raw).await;
+        }
+        for duplicate in rejected.duplicates {
+            debug!(
+                "deduplicated trace_id: {}, expected_profit: {}",
+                duplicate.arbi_event.trace_id, duplicate.expected_profit
+            );
+            ack_event(duplicate.raw).await;
         }
-        //break;
     }
+    buffer.close();
+}
+
+async fn report_failure(raw: &RawEvent, e: anyhow::Error, dead_letter: Option<&DeadLetterQueue>) {
+    Metrics::incr(&METRICS.failed);
+    if e.chain().any(|cause| cause.is::<LegAccountError>()) {
+        Metrics::incr(&METRICS.invalid_accounts);
+    }
+    error!("Error executing transaction: {:?}", e);
+    // 失败的原始消息写入死信队列
+    if let Some(dead_letter) = dead_letter {
+        let letter = DeadLetter::from_error(&raw.source, raw.value.clone(), raw.received_ts, &e);
+        dead_letter.push_or_log(&letter).await;
+    }
+}
+
+/// 无论成功、失败还是被丢弃都已处理完毕，确认后不再被重新投递
+async fn ack_event(raw: RawEvent) {
+    if let Some(ack) = raw.ack {
+        if let Err(e) = ack.ack().await {
+            error!("Ack message failed: {:?}", e);
+        }
+    }
+}
+
+/// pool_states 为 None 时只接受带完整账户的 legs
+pub fn decode_event(
+    key: &str,
+    value: &str,
+    pool_states: Option<&dyn PoolStateSource>,
+) -> Result<ArbiEvent> {
+    // 解码并处理消息，JSON 与二进制格式见 wire::decode_arbi_event
+    let data = decode_payload(value)?;
+    trace!("Received message from {}: {} bytes", key, data.len());
+
+    let mut arbi_event = decode_arbi_event(&data)?;
+    let mint_a = arbi_event.common_accounts.token_vault_a_mint;
+    arbi_event
+        .route
+        .resolve_pools(&mint_a, pool_states)
+        .stage(FailureStage::Validate)?;
+    validate_event(&arbi_event).stage(FailureStage::Validate)?;
+    // 处理解码后的消息
+    debug!("Parse message from {}: {:?}", key, arbi_event.clone());
+    Ok(arbi_event)
 }
 
 async fn execute_transaction(
-    key: String,
-    value: String,
-    alt_account: Arc<AddressLookupTableAccount>,
-    request_client: Arc<ReqwestClient>,
-    connection: Arc<RpcClient>,
+    key: &str,
+    arbi_event: ArbiEvent,
+    context: &SubmitContext,
+    policy: &QueuePolicy,
     start_ts: i64,
 ) -> Result<()> {
     let config_ts = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_millis() as i64;
-    // 解码并处理消息
-    let compressed_message = base64::engine::general_purpose::STANDARD.decode(value)?;
-    let arbi_data_str = lz4_inflate_message(compressed_message)?;
-
-    let arbi_event: ArbiEvent = serde_json::from_str(&arbi_data_str)?;
-    let stream_ts = arbi_event.stream_ts;
     let trace_id = arbi_event.trace_id.clone();
-    // 超时退出
-    if config_ts - stream_ts > 1000 {
+    let stream_ts = arbi_event.stream_ts;
+
+    let result: Result<()> = async {
+        // 超时退出
+        check_stream_ts(&arbi_event, config_ts, policy.staleness_budget_ms)
+            .stage(FailureStage::Stale)?;
+        submit_event(key, arbi_event, context, policy, start_ts, config_ts)
+            .await
+            .stage(FailureStage::Assemble)
+    }
+    .await;
+    result.map_err(|e| StageError::attach_event(e, &trace_id, stream_ts))
+}
+
+pub fn check_stream_ts(arbi_event: &ArbiEvent, now_ts: i64, budget_ms: i64) -> Result<()> {
+    if now_ts - arbi_event.stream_ts > budget_ms {
         return Err(anyhow::anyhow!(format!(
             "{} stream_ts timeout {}",
-            trace_id,
-            config_ts - stream_ts
+            arbi_event.trace_id,
+            now_ts - arbi_event.stream_ts
         )));
     }
-    // 处理解码后的消息
-    debug!("Parse message from {}: {:?}", key, arbi_event.clone());
-    trace!("Received message from {}: {:?}", key, arbi_data_str.clone());
-
-    let private_key = if std::env::var("REDIS_QUEUE_NAME")
-        .unwrap_or_else(|_| "arbi_swap_queue".to_string())
-        == "arbi_swap_queue_rpc"
-    {
-        RPC_SUBMITTER_KEYS[0]
-    } else if arbi_event.transaction.use_proxy_account || arbi_event.transaction.use_kamino {
-        PROXY_SUBMITTER_KEYS[0]
-    } else {
-        SUBMITTER_KEYS.choose(&mut OsRng).unwrap()
-    };
+    Ok(())
+}
+
+/// 选择钱包、组装并提交已解码的事件
+pub async fn submit_event(
+    key: &str,
+    arbi_event: ArbiEvent,
+    context: &SubmitContext,
+    policy: &QueuePolicy,
+    start_ts: i64,
+    config_ts: i64,
+) -> Result<()> {
+    let stream_ts = arbi_event.stream_ts;
+    let trace_id = arbi_event.trace_id.clone();
+
+    let private_key = select_private_key(policy, &arbi_event);
     let wallet = &Keypair::from_base58_string(private_key);
 
-    let client: Client<&Keypair> = Client::new(Cluster::Localnet, &wallet);
+    let client: Client<&Keypair> = Client::new(Cluster::Localnet, wallet);
     let program_id = Pubkey::from_str("")?;
     let program = Arc::new(client.program(program_id)?);
 
     let transaction_helpers = TransactionHelpers {
         program,
-        alt_account, // Ensure alt_account is defined in the scope
-        connection,
+        alt_account: context.alt_account.clone(),
+        connection: context.connections.choose(&mut OsRng).unwrap().clone(),
         wallet,
+        mints: context.mints.clone(),
+        pool_states: context.pool_states.clone(),
+        compute_units: context.compute_units.clone(),
+        priority_fees: context.priority_fees.clone(),
     };
 
     let submit_ts = SystemTime::now()
@@ -183,7 +427,13 @@ async fn execute_transaction(
         .unwrap()
         .as_millis() as i64;
 
-    assemble_and_submit_transaction(arbi_event, transaction_helpers, request_client).await?;
+    assemble_and_submit_transaction(
+        arbi_event,
+        transaction_helpers,
+        context.request_client.clone(),
+        policy.send_path,
+    )
+    .await?;
 
     let end_ts = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -191,8 +441,9 @@ async fn execute_transaction(
         .as_millis() as i64;
 
     info!(
-        "trace_id: {}, Submit Cost: {}",
+        "trace_id: {}, from: {}, Submit Cost: {}",
         trace_id,
+        key,
         serde_json::json!({
             "redis": config_ts - start_ts,
             "parse": submit_ts - config_ts,
@@ -204,24 +455,39 @@ async fn execute_transaction(
     Ok(())
 }
 
-async fn inflate_message(compressed_data: Vec<u8>) -> Result<String> {
-    // 使用 ZlibDecoder 创建解压缩器
-    let mut decoder = ZlibDecoder::new(&compressed_data[..]);
-    let mut decompressed_data = String::new();
+/// 按队列配置的钱包池选择提交钱包
+fn select_private_key<'a>(policy: &'a QueuePolicy, arbi_event: &ArbiEvent) -> &'a str {
+    if let Some(key) = policy.wallet_keys.choose(&mut OsRng) {
+        return key;
+    }
+    match policy.wallet_pool {
+        WalletPool::Rpc => RPC_SUBMITTER_KEYS.choose(&mut OsRng).unwrap(),
+        WalletPool::Proxy => PROXY_SUBMITTER_KEYS.choose(&mut OsRng).unwrap(),
+        WalletPool::Default => {
+            if arbi_event.transaction.use_proxy_account || arbi_event.transaction.use_kamino {
+                PROXY_SUBMITTER_KEYS[0]
+            } else {
+                SUBMITTER_KEYS.choose(&mut OsRng).unwrap()
+            }
+        }
+    }
+}
 
-    // 读取解压缩的数据
-    decoder.read_to_string(&mut decompressed_data).await?;
+pub async fn get_or_init_redis(redis_url: String, redis_db: i64) -> Result<ConnectionManager> {
+    let conn = redis_client(&redis_url, redis_db)?
+        .get_connection_manager_with_config(
+            ConnectionManagerConfig::new().set_connection_timeout(std::time::Duration::new(10, 0)),
+        )
+        .await
+        .unwrap();
 
-    Ok(decompressed_data)
-}
+    info!("Success connected to redis:{}, db:{}", redis_url, redis_db);
 
-fn lz4_inflate_message(compressed_data: Vec<u8>) -> Result<String> {
-    let decompressed_bytes = decompress_size_prepended(&compressed_data).context("LZ4 解压失败")?;
-    let result = String::from_utf8(decompressed_bytes).context("UTF-8 解码失败")?;
-    Ok(result)
+    Ok(conn)
 }
 
-pub async fn get_or_init_redis(redis_url: String, redis_db: i64) -> Result<ConnectionManager> {
+/// 按 host:port 构建 redis 客户端
+pub fn redis_client(redis_url: &str, redis_db: i64) -> Result<redis::Client> {
     let ip: String;
     let port: u16;
     let password = Some("x".to_string());
@@ -249,22 +515,110 @@ pub async fn get_or_init_redis(redis_url: String, redis_db: i64) -> Result<Conne
         ));
     }
 
-    let conn = redis::Client::open(ConnectionInfo {
-        addr: ConnectionAddr::Tcp(ip.clone(), port),
+    Ok(redis::Client::open(ConnectionInfo {
+        addr: ConnectionAddr::Tcp(ip, port),
         redis: RedisConnectionInfo {
             db: redis_db,
             password,
             ..Default::default()
         },
     })
-    .unwrap()
-    .get_connection_manager_with_config(
-        ConnectionManagerConfig::new().set_connection_timeout(std::time::Duration::new(10, 0)),
-    )
-    .await
-    .unwrap();
+    .unwrap())
+}
 
-    info!("Success connected to redis:{}, db:{}", redis_url, redis_db);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::submiter::assembler::SendPath;
+    use crate::submiter::mints::{tests::registry, MintInfo};
+    use crate::submiter::route::tests::legacy_json;
+    use crate::submiter::source::tests::MemorySource;
+    use std::sync::atomic::Ordering;
+
+    fn context(mint_a: Pubkey) -> SubmitContext {
+        let info = MintInfo {
+            decimals: 6,
+            token_program: spl_token::ID,
+            profit_decimals: 6,
+            transfer_hook_program: None,
+            transfer_fee: None,
+        };
+        SubmitContext {
+            alt_account: Arc::new(AddressLookupTableAccount {
+                key: Pubkey::new_unique(),
+                addresses: vec![],
+            }),
+            connections: vec![],
+            request_client: Arc::new(ReqwestClient::new()),
+            mints: Arc::new(registry(&[(mint_a, info)])),
+            pool_states: None,
+            compute_units: Arc::new(ComputeUnitModel::from_env()),
+            priority_fees: PriorityFees::spawn_from_env(None),
+        }
+    }
 
-    Ok(conn)
+    fn policy(parallelism: usize) -> Arc<QueuePolicy> {
+        Arc::new(QueuePolicy {
+            name: "memory".to_string(),
+            ingest_mode: "memory".to_string(),
+            wallet_pool: WalletPool::Default,
+            wallet_env: None,
+            wallet_keys: vec![],
+            send_path: SendPath::Simulate,
+            parallelism,
+            staleness_budget_ms: 1000,
+        })
+    }
+
+    /// stream_ts 早已超时的事件，在超时检查处失败，不会走到钱包与 RPC
+    fn stale_event(mint_a: &Pubkey) -> String {
+        let mut value = legacy_json();
+        value["accounts"]["commonAccounts"]["tokenVaultAMint"] = mint_a.to_string().into();
+        value.to_string()
+    }
+
+    #[tokio::test]
+    async fn pipeline_acks_every_event_and_stops_when_source_ends() {
+        let mint_a = Pubkey::new_unique();
+        let unknown_mint = Pubkey::new_unique();
+        let batches = vec![
+            vec![
+                stale_event(&mint_a),
+                "not an event".to_string(),
+                stale_event(&mint_a),
+            ],
+            vec![],
+            vec![
+                stale_event(&unknown_mint),
+                stale_event(&mint_a),
+                stale_event(&mint_a),
+                stale_event(&mint_a),
+            ],
+        ];
+        let total: usize = batches.iter().map(Vec::len).sum();
+        let (source, acked) = MemorySource::new(batches);
+
+        // 数据源结束后缓冲区关闭，run_pipeline 等待全部事件处理完再返回
+        tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            run_pipeline(Box::new(source), context(mint_a), policy(2), None, None),
+        )
+        .await
+        .expect("pipeline did not stop after the source ended")
+        .unwrap();
+        assert_eq!(acked.load(Ordering::SeqCst), total);
+    }
+
+    #[tokio::test]
+    async fn pipeline_acks_events_shed_by_the_buffer() {
+        let mint_a = Pubkey::new_unique();
+        // 一批远超 MAX_IN_FLIGHT (默认 parallelism * 4) 的事件，超出部分被丢弃也要确认
+        let batch: Vec<String> = (0..20).map(|_| stale_event(&mint_a)).collect();
+        let (source, acked) = MemorySource::new(vec![batch]);
+
+        run_pipeline(Box::new(source), context(mint_a), policy(1), None, None)
+            .await
+            .unwrap();
+        assert_eq!(acked.load(Ordering::SeqCst), 20);
+    }
 }
@@ -0,0 +1,240 @@
+use anchor_client::solana_sdk::hash::Hash;
+use std::{fmt, str::FromStr};
+
+use crate::submiter::assembler::{ArbiEvent, CURRENT_SCHEMA_VERSION};
+use crate::submiter::dex::AccountMetaError;
+
+/// token 精度上限，超过时 10^decimals 会溢出 u64 或不是真实的 mint
+const MAX_DECIMALS: u8 = 18;
+
+/// 事件结构校验失败的原因，field 为出错的字段名
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    UnsupportedSchemaVersion(u16),
+    EmptyRoute,
+    /// 兼容格式中并行数组的长度不一致
+    LengthMismatch {
+        field: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    /// 正向 leg 出现在反向 leg 之后，无法按下标拆分为 token a / b 数量列表
+    LegOrder {
+        index: usize,
+    },
+    DecimalsOutOfRange {
+        index: usize,
+        decimals: u8,
+    },
+    /// 该方向需要的账户缺失，或超出链上程序支持的账户布局
+    LegAccounts {
+        index: usize,
+        error: AccountMetaError,
+    },
+    InvalidBlockhash(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::UnsupportedSchemaVersion(version) => write!(
+                f,
+                "schemaVersion {} is not supported (current {})",
+                version, CURRENT_SCHEMA_VERSION
+            ),
+            ValidationError::EmptyRoute => write!(f, "route.legs: no forward leg"),
+            ValidationError::LengthMismatch {
+                field,
+                expected,
+                actual,
+            } => write!(f, "{}: expected {} items, got {}", field, expected, actual),
+            ValidationError::LegOrder { index } => {
+                write!(f, "route.legs[{}]: forward leg after reverse legs", index)
+            }
+            ValidationError::DecimalsOutOfRange { index, decimals } => write!(
+                f,
+                "route.legs[{}].decimals: {} exceeds {}",
+                index, decimals, MAX_DECIMALS
+            ),
+            ValidationError::LegAccounts { index, error } => {
+                write!(f, "route.legs[{}].dex.{}", index, error)
+            }
+            ValidationError::InvalidBlockhash(blockhash) => {
+                write!(f, "blockhash: invalid {}", blockhash)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+pub fn check_len(
+    field: &'static str,
+    expected: usize,
+    actual: usize,
+) -> Result<(), ValidationError> {
+    if expected != actual {
+        return Err(ValidationError::LengthMismatch {
+            field,
+            expected,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// 组装交易前校验事件结构: 路由非空、正反向 leg 的顺序、精度范围以及每个 leg 的账户
+pub fn validate_event(arbi_event: &ArbiEvent) -> Result<(), ValidationError> {
+    if arbi_event.schema_version == 0 || arbi_event.schema_version > CURRENT_SCHEMA_VERSION {
+        return Err(ValidationError::UnsupportedSchemaVersion(
+            arbi_event.schema_version,
+        ));
+    }
+
+    let legs = &arbi_event.route.legs;
+    if legs.is_empty() || legs[0].reverse {
+        return Err(ValidationError::EmptyRoute);
+    }
+    if let Some(index) = legs
+        .windows(2)
+        .position(|pair| pair[0].reverse && !pair[1].reverse)
+    {
+        return Err(ValidationError::LegOrder { index: index + 1 });
+    }
+
+    for (index, leg) in legs.iter().enumerate() {
+        if let Some(decimals) = leg.decimals.filter(|decimals| *decimals > MAX_DECIMALS) {
+            return Err(ValidationError::DecimalsOutOfRange { index, decimals });
+        }
+        // 该方向需要的账户 (如 tick / bin array) 缺失或数量超出上限
+        if let Err(error) = leg.dex.adapter().to_account_metas(leg.a_to_b) {
+            return Err(ValidationError::LegAccounts { index, error });
+        }
+    }
+
+    if Hash::from_str(&arbi_event.blockhash).is_err() {
+        return Err(ValidationError::InvalidBlockhash(
+            arbi_event.blockhash.clone(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::submiter::dex::{DexAccount, DexType};
+    use crate::submiter::route::tests::legacy_json;
+    use crate::submiter::wire::parse_json_event;
+    use anchor_client::solana_sdk::pubkey::Pubkey;
+
+    fn event() -> ArbiEvent {
+        parse_json_event(&legacy_json().to_string()).unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_event() {
+        assert_eq!(validate_event(&event()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_unsupported_schema_version() {
+        for version in [0, CURRENT_SCHEMA_VERSION + 1] {
+            let mut event = event();
+            event.schema_version = version;
+            assert_eq!(
+                validate_event(&event),
+                Err(ValidationError::UnsupportedSchemaVersion(version))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_route_without_forward_leg() {
+        let mut event = event();
+        event.route.legs.clear();
+        assert_eq!(validate_event(&event), Err(ValidationError::EmptyRoute));
+
+        let mut event = self::event();
+        event.route.legs[0].reverse = true;
+        assert_eq!(validate_event(&event), Err(ValidationError::EmptyRoute));
+    }
+
+    #[test]
+    fn rejects_length_mismatch() {
+        assert_eq!(check_len("decimals", 2, 2), Ok(()));
+        assert_eq!(
+            check_len("decimals", 2, 3),
+            Err(ValidationError::LengthMismatch {
+                field: "decimals",
+                expected: 2,
+                actual: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_forward_leg_after_reverse_legs() {
+        let mut event = event();
+        let forward = event.route.legs[0].clone();
+        event.route.legs.push(forward);
+        assert_eq!(
+            validate_event(&event),
+            Err(ValidationError::LegOrder { index: 2 })
+        );
+    }
+
+    #[test]
+    fn rejects_decimals_out_of_range() {
+        let mut event = event();
+        event.route.legs[1].decimals = Some(MAX_DECIMALS + 1);
+        assert_eq!(
+            validate_event(&event),
+            Err(ValidationError::DecimalsOutOfRange {
+                index: 1,
+                decimals: MAX_DECIMALS + 1,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_leg_without_tick_arrays() {
+        let key = || Pubkey::new_unique().to_string();
+        let mut event = event();
+        event.route.legs[0].dex = DexAccount::from_json(
+            DexType::RaydiumClmm,
+            serde_json::json!({
+                "raydiumAmmConfig": key(),
+                "raydiumPoolState": key(),
+                "raydiumInputVault": key(),
+                "raydiumOutputVault": key(),
+                "raydiumObservationState": key(),
+            }),
+        )
+        .unwrap();
+        let field = if event.route.legs[0].a_to_b {
+            "raydiumAToBTickArrays"
+        } else {
+            "raydiumBToATickArrays"
+        };
+        assert_eq!(
+            validate_event(&event),
+            Err(ValidationError::LegAccounts {
+                index: 0,
+                error: AccountMetaError::MissingAccount { field },
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_blockhash() {
+        let mut event = event();
+        event.blockhash = "not a blockhash".to_string();
+        assert_eq!(
+            validate_event(&event),
+            Err(ValidationError::InvalidBlockhash(
+                "not a blockhash".to_string()
+            ))
+        );
+    }
+}
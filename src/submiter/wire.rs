@@ -0,0 +1,321 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::Result;
+use bincode::Options;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::submiter::assembler::ArbiEvent;
+use crate::submiter::dead_letter::{FailureStage, StageContext};
+use crate::submiter::dex::{DexAccount, DexType, JsonDexAccount, TaggedDexAccount};
+use crate::submiter::route::LegacyArbiEvent;
+
+/// 二进制事件: MAGIC(4) + VERSION(1) + bincode(ArbiEvent)，
+/// 公钥为 32 字节原始数据，整数为定长小端，ArbiEvent 以 schema_version 开头，
+/// 结构与 route 格式的 JSON 事件一致
+pub const BINARY_MAGIC: &[u8; 4] = b"ARBB";
+pub const BINARY_VERSION: u8 = 1;
+
+/// bincode 编解码的大小上限，远大于实际事件，防止伪造的长度前缀导致大量分配
+const MAX_BINARY_EVENT_SIZE: u64 = 64 * 1024;
+
+/// 与 bincode::serialize / deserialize 相同的编码 (定长整数)，加上大小上限
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_BINARY_EVENT_SIZE)
+}
+
+/// Pubkey 在 JSON 中为 base58 字符串，在二进制格式中为 32 字节
+pub mod pubkey_serde {
+    use super::*;
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(key: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&key.to_string())
+        } else {
+            key.to_bytes().serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+        if deserializer.is_human_readable() {
+            let value = String::deserialize(deserializer)?;
+            Pubkey::from_str(&value)
+                .map_err(|e| serde::de::Error::custom(format!("invalid pubkey {}: {}", value, e)))
+        } else {
+            <[u8; 32]>::deserialize(deserializer).map(Pubkey::new_from_array)
+        }
+    }
+}
+
+/// 可选的 Pubkey，JSON 中缺失、null 或空字符串均为 None
+pub mod option_pubkey_serde {
+    use super::*;
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(
+        key: &Option<Pubkey>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            key.map(|key| key.to_string()).serialize(serializer)
+        } else {
+            key.map(|key| key.to_bytes()).serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Pubkey>, D::Error> {
+        if deserializer.is_human_readable() {
+            match Option::<String>::deserialize(deserializer)? {
+                Some(value) if !value.is_empty() => {
+                    Pubkey::from_str(&value).map(Some).map_err(|e| {
+                        serde::de::Error::custom(format!("invalid pubkey {}: {}", value, e))
+                    })
+                }
+                _ => Ok(None),
+            }
+        } else {
+            Option::<[u8; 32]>::deserialize(deserializer).map(|key| key.map(Pubkey::new_from_array))
+        }
+    }
+}
+
+/// Pubkey 列表，JSON 中为 base58 字符串数组，二进制格式中为 32 字节数组的列表
+pub mod pubkey_vec_serde {
+    use super::*;
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(keys: &[Pubkey], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_seq(keys.iter().map(|key| key.to_string()))
+        } else {
+            serializer.collect_seq(keys.iter().map(|key| key.to_bytes()))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Pubkey>, D::Error> {
+        if deserializer.is_human_readable() {
+            Vec::<String>::deserialize(deserializer)?
+                .iter()
+                .map(|value| {
+                    Pubkey::from_str(value).map_err(|e| {
+                        serde::de::Error::custom(format!("invalid pubkey {}: {}", value, e))
+                    })
+                })
+                .collect()
+        } else {
+            Vec::<[u8; 32]>::deserialize(deserializer)
+                .map(|keys| keys.into_iter().map(Pubkey::new_from_array).collect())
+        }
+    }
+}
+
+/// DexAccount 的 JSON 表示以 dexType 字段标记类型，二进制格式中以 variant 下标开头，
+/// 两者的枚举均由 dex 模块的 DEX 注册表生成。
+/// 旧格式事件中的 dexes 由 parse_json_event 按 dexTypes 解析，dexType 字段可省略
+impl Serialize for DexAccount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            JsonDexAccount::serialize(self, serializer)
+        } else {
+            TaggedDexAccount::serialize(self, serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DexAccount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            JsonDexAccount::deserialize(deserializer)
+        } else {
+            TaggedDexAccount::deserialize(deserializer)
+        }
+    }
+}
+
+/// 按内容解析事件: 以 BINARY_MAGIC 开头为二进制格式，否则为 JSON
+pub fn decode_arbi_event(data: &[u8]) -> Result<ArbiEvent> {
+    if let Some(body) = data.strip_prefix(BINARY_MAGIC) {
+        let (&version, body) = body
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("binary event too short"))
+            .stage(FailureStage::Parse)?;
+        if version != BINARY_VERSION {
+            return Err(anyhow::anyhow!(
+                "unsupported binary event version: {}",
+                version
+            ))
+            .stage(FailureStage::Parse);
+        }
+        return bincode_options()
+            .deserialize(body)
+            .stage(FailureStage::Parse);
+    }
+    let json = std::str::from_utf8(data).stage(FailureStage::Utf8)?;
+    parse_json_event(json).stage(FailureStage::Parse)
+}
+
+/// 解析 JSON 事件。schemaVersion 为 2 及以上时为 route 格式，
+/// 否则按旧的并行数组格式解析后转换，accounts.dexes[i] 按 dexTypes[i] 声明的类型解析，
+/// dex 对象带 dexType 字段时必须与声明一致
+pub fn parse_json_event(json: &str) -> Result<ArbiEvent> {
+    let mut value: Value = serde_json::from_str(json)?;
+    let schema_version = value
+        .get("schemaVersion")
+        .and_then(Value::as_u64)
+        .unwrap_or(1);
+    if schema_version >= 2 {
+        return Ok(serde_json::from_value(value)?);
+    }
+
+    // 先取出 dexes，其余字段解析完成后再按 dex_types 逐个解析
+    let dexes = match value
+        .get_mut("accounts")
+        .and_then(|accounts| accounts.get_mut("dexes"))
+    {
+        Some(dexes) => std::mem::replace(dexes, Value::Array(vec![])),
+        None => Value::Array(vec![]),
+    };
+    let mut legacy: LegacyArbiEvent = serde_json::from_value(value)?;
+    let Value::Array(dexes) = dexes else {
+        return Err(anyhow::anyhow!("accounts.dexes: expected an array"));
+    };
+    legacy.accounts.dexes = dexes
+        .into_iter()
+        .enumerate()
+        .map(|(index, dex)| decode_dex_leg(index, legacy.dex_types.get(index).copied(), dex))
+        .collect::<Result<_>>()?;
+    legacy.into_event().stage(FailureStage::Validate)
+}
+
+fn decode_dex_leg(index: usize, declared: Option<DexType>, dex: Value) -> Result<DexAccount> {
+    let tagged = match dex.get("dexType") {
+        Some(tag) => Some(
+            DexType::deserialize(tag)
+                .map_err(|e| anyhow::anyhow!("accounts.dexes[{}].dexType: {}", index, e))?,
+        ),
+        None => None,
+    };
+    let dex_type = match (declared, tagged) {
+        (Some(declared), Some(tagged)) if declared != tagged => {
+            return Err(anyhow::anyhow!(
+                "accounts.dexes[{}]: dexType {:?} does not match dexTypes[{}] {:?}",
+                index,
+                tagged,
+                index,
+                declared
+            ));
+        }
+        (Some(dex_type), _) | (None, Some(dex_type)) => dex_type,
+        (None, None) => {
+            return Err(anyhow::anyhow!(
+                "accounts.dexes[{}]: no dexTypes entry and no dexType tag",
+                index
+            ));
+        }
+    };
+    DexAccount::from_json(dex_type, dex).map_err(|e| {
+        anyhow::anyhow!(
+            "accounts.dexes[{}]: accounts do not match declared {:?}: {}",
+            index,
+            dex_type,
+            e
+        )
+    })
+}
+
+/// 生产端使用: 编码为二进制事件，再经 codec::encode_payload 压缩加帧
+pub fn encode_arbi_event(arbi_event: &ArbiEvent) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(1024);
+    data.extend_from_slice(BINARY_MAGIC);
+    data.push(BINARY_VERSION);
+    bincode_options().serialize_into(&mut data, arbi_event)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::submiter::assembler::CURRENT_SCHEMA_VERSION;
+    use crate::submiter::route::tests::legacy_json;
+
+    fn event() -> ArbiEvent {
+        parse_json_event(&legacy_json().to_string()).unwrap()
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let event = event();
+        let data = encode_arbi_event(&event).unwrap();
+        assert!(data.starts_with(BINARY_MAGIC));
+        let decoded = decode_arbi_event(&data).unwrap();
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&event).unwrap()
+        );
+    }
+
+    #[test]
+    fn binary_round_trip_through_codec() {
+        use crate::submiter::codec::{decode_payload, encode_payload, Codec};
+
+        let event = event();
+        let payload = encode_payload(Codec::Zstd, &encode_arbi_event(&event).unwrap()).unwrap();
+        let decoded = decode_arbi_event(&decode_payload(&payload).unwrap()).unwrap();
+        assert_eq!(decoded.trace_id, event.trace_id);
+        assert_eq!(decoded.route.legs.len(), event.route.legs.len());
+    }
+
+    #[test]
+    fn json_route_round_trip() {
+        let mut event = event();
+        event.schema_version = CURRENT_SCHEMA_VERSION;
+        let json = serde_json::to_string(&event).unwrap();
+        // route 格式中每个 dex 带 dexType 标记
+        assert!(json.contains("\"dexType\":\"raydiumCpmm\""));
+        let decoded = decode_arbi_event(json.as_bytes()).unwrap();
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&event).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_other_binary_versions() {
+        let mut data = encode_arbi_event(&event()).unwrap();
+        data[BINARY_MAGIC.len()] = BINARY_VERSION + 1;
+        assert!(decode_arbi_event(&data).is_err());
+        assert!(decode_arbi_event(BINARY_MAGIC).is_err());
+    }
+
+    #[test]
+    fn rejects_forged_length_prefix() {
+        let mut data = BINARY_MAGIC.to_vec();
+        data.push(BINARY_VERSION);
+        // schema_version 之后的 CommonAccounts 为定长，route.legs 的长度前缀伪造为 2^40
+        let mut body = bincode_options()
+            .serialize(&event())
+            .unwrap()
+            .into_iter()
+            .take(2 + 5 * 32)
+            .collect::<Vec<u8>>();
+        body.extend_from_slice(&(1u64 << 40).to_le_bytes());
+        data.extend_from_slice(&body);
+        assert!(decode_arbi_event(&data).is_err());
+    }
+
+    #[test]
+    fn legacy_dex_type_tag_must_match_declaration() {
+        let mut json = legacy_json();
+        json["accounts"]["dexes"][0]["dexType"] = "raydiumCpmm".into();
+        assert!(parse_json_event(&json.to_string()).is_ok());
+        json["accounts"]["dexes"][0]["dexType"] = "orca".into();
+        assert!(parse_json_event(&json.to_string()).is_err());
+    }
+}